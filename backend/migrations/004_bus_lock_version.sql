@@ -0,0 +1 @@
+ALTER TABLE bus_locks ADD COLUMN version INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE transactions ADD COLUMN archived_at TIMESTAMP;
@@ -0,0 +1,6 @@
+ALTER TABLE transactions ADD COLUMN expires_at TIMESTAMP;
+
+-- Existing pending rows get the default 24h TTL; new rows are stamped by the API
+UPDATE transactions SET expires_at = created_at + INTERVAL '24 hours' WHERE status = 'pending';
+
+CREATE INDEX idx_transactions_pending_expiry ON transactions(expires_at) WHERE status = 'pending';
@@ -0,0 +1,10 @@
+CREATE TABLE bus_lock_history (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    locked_amount DECIMAL(20, 8) NOT NULL,
+    required_amount DECIMAL(20, 8) NOT NULL,
+    trigger VARCHAR(32) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_bus_lock_history_user_created ON bus_lock_history(user_id, created_at DESC, id DESC);
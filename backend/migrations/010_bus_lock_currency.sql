@@ -0,0 +1,9 @@
+-- Locks were a single lump per user; existing rows are treated as USD-denominated
+ALTER TABLE bus_locks ADD COLUMN currency VARCHAR(10) NOT NULL DEFAULT 'USD';
+ALTER TABLE bus_locks ALTER COLUMN currency DROP DEFAULT;
+
+ALTER TABLE bus_locks DROP CONSTRAINT bus_locks_user_id_key;
+ALTER TABLE bus_locks ADD CONSTRAINT bus_locks_user_id_currency_key UNIQUE (user_id, currency);
+
+ALTER TABLE bus_lock_history ADD COLUMN currency VARCHAR(10) NOT NULL DEFAULT 'USD';
+ALTER TABLE bus_lock_history ALTER COLUMN currency DROP DEFAULT;
@@ -0,0 +1,3 @@
+ALTER TABLE transactions ADD COLUMN net_amount DECIMAL(20, 8);
+
+UPDATE transactions SET net_amount = amount - COALESCE(fee_amount, 0);
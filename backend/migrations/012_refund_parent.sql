@@ -0,0 +1,5 @@
+ALTER TABLE transactions ADD COLUMN parent_transaction_id UUID REFERENCES transactions(id);
+
+CREATE INDEX idx_transactions_parent_transaction_id
+    ON transactions(parent_transaction_id, created_at, id)
+    WHERE parent_transaction_id IS NOT NULL;
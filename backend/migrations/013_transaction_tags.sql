@@ -0,0 +1,3 @@
+ALTER TABLE transactions ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX idx_transactions_tags ON transactions USING GIN (tags);
@@ -0,0 +1,2 @@
+-- Serves both list pagination modes, which order by (created_at DESC, id DESC)
+CREATE INDEX idx_transactions_user_created ON transactions(user_id, created_at DESC, id DESC);
@@ -0,0 +1,14 @@
+-- Outbox of transaction events for at-least-once webhook delivery. `id` is the stable
+-- event_id consumers dedup on; one row per status a transaction reaches.
+CREATE TABLE webhook_events (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    transaction_id UUID NOT NULL REFERENCES transactions(id) ON DELETE CASCADE,
+    event_type VARCHAR(64) NOT NULL,
+    status VARCHAR(50) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMP,
+    UNIQUE (transaction_id, status)
+);
+
+CREATE INDEX idx_webhook_events_undelivered ON webhook_events(created_at) WHERE delivered_at IS NULL;
@@ -0,0 +1,11 @@
+-- Requests per user per calendar month (UTC), kept in the database so quotas survive restarts.
+CREATE TABLE usage_counters (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    period DATE NOT NULL,
+    request_count BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, period)
+);
+
+-- Per-merchant plan limit; NULL falls back to MONTHLY_REQUEST_QUOTA
+ALTER TABLE users ADD COLUMN monthly_request_quota BIGINT;
@@ -0,0 +1,8 @@
+-- When a payment moved to `settled`, kept apart from created_at for settlement-timing reports
+ALTER TABLE transactions ADD COLUMN settled_at TIMESTAMP;
+
+-- Best effort for rows settled before this column: the settlement outbox event's time
+UPDATE transactions t
+SET settled_at = e.created_at
+FROM webhook_events e
+WHERE e.transaction_id = t.id AND e.status = 'settled' AND t.status = 'settled';
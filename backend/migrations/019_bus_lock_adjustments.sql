@@ -0,0 +1,3 @@
+-- Who made a manual adjustment and why; NULL for system recalculations
+ALTER TABLE bus_lock_history ADD COLUMN actor_id UUID REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE bus_lock_history ADD COLUMN reason TEXT;
@@ -0,0 +1,9 @@
+-- Per-user HMAC secret for signing webhook deliveries. After a rotation the previous
+-- secret keeps verifying until previous_expires_at, so receivers can roll over.
+CREATE TABLE webhook_secrets (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    secret TEXT NOT NULL,
+    previous_secret TEXT,
+    previous_expires_at TIMESTAMP,
+    rotated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,27 @@
+-- Per-user receipt counter. Payment inserts bump it in the same statement, so the
+-- row lock serializes concurrent creates and a rolled-back insert never burns a number.
+CREATE TABLE receipt_sequences (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    last_number BIGINT NOT NULL DEFAULT 0
+);
+
+ALTER TABLE transactions ADD COLUMN receipt_number TEXT;
+
+-- Number existing payments in creation order and seed the counters to match
+WITH numbered AS (
+    SELECT id, user_id, ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY created_at, id) AS n
+    FROM transactions
+    WHERE tx_type = 'payment' AND user_id IS NOT NULL
+)
+UPDATE transactions t
+SET receipt_number = 'INV-' || lpad(numbered.n::text, GREATEST(6, length(numbered.n::text)), '0')
+FROM numbered
+WHERE t.id = numbered.id;
+
+INSERT INTO receipt_sequences (user_id, last_number)
+SELECT user_id, COUNT(*) FROM transactions
+WHERE tx_type = 'payment' AND user_id IS NOT NULL
+GROUP BY user_id;
+
+CREATE UNIQUE INDEX idx_transactions_user_receipt_number
+    ON transactions (user_id, receipt_number) WHERE receipt_number IS NOT NULL;
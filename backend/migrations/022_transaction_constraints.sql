@@ -0,0 +1,6 @@
+-- Guard the values handlers already whitelist, so a bug or a manual write can't store
+-- rows the API would misreport. parent_transaction_id already references transactions(id).
+ALTER TABLE transactions
+    ADD CONSTRAINT transactions_status_check CHECK (status IN ('pending', 'settled', 'failed')),
+    ADD CONSTRAINT transactions_tx_type_check CHECK (tx_type IN ('payment', 'refund')),
+    ADD CONSTRAINT transactions_amount_check CHECK (amount >= 0);
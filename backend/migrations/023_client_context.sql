@@ -0,0 +1,4 @@
+-- Where a payment request came from, for fraud review. NULL for rows created
+-- before capture started and for transactions not created through the API.
+ALTER TABLE transactions ADD COLUMN client_ip TEXT;
+ALTER TABLE transactions ADD COLUMN user_agent TEXT;
@@ -0,0 +1,24 @@
+-- Last time anything on the row changed; feeds the ETag on GET /api/transactions/:id.
+-- Maintained by trigger so every writer (handlers, sweeper, auto-settler) bumps it.
+ALTER TABLE transactions ADD COLUMN updated_at TIMESTAMP;
+
+UPDATE transactions
+SET updated_at = COALESCE(GREATEST(created_at, settled_at, archived_at, deleted_at), NOW());
+
+ALTER TABLE transactions
+    ALTER COLUMN updated_at SET DEFAULT NOW(),
+    ALTER COLUMN updated_at SET NOT NULL;
+
+-- clock_timestamp() rather than NOW() so two changes in one database transaction still differ
+CREATE FUNCTION transactions_touch_updated_at() RETURNS trigger AS $$
+BEGIN
+    IF NEW IS DISTINCT FROM OLD THEN
+        NEW.updated_at := clock_timestamp();
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER transactions_touch_updated_at
+    BEFORE UPDATE ON transactions
+    FOR EACH ROW EXECUTE FUNCTION transactions_touch_updated_at();
@@ -0,0 +1,5 @@
+-- How webhook deliveries go out to the merchant's endpoint: one at a time in event
+-- order ('ordered'), or concurrently for throughput with no ordering guarantee ('parallel').
+ALTER TABLE users
+    ADD COLUMN webhook_delivery_mode VARCHAR(20) NOT NULL DEFAULT 'ordered'
+    CHECK (webhook_delivery_mode IN ('ordered', 'parallel'));
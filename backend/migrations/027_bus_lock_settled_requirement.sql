@@ -0,0 +1,39 @@
+-- The BUS lock requirement now tracks settled volume: creating a payment adds 0.1% of its
+-- amount to locked_amount, settling adds it to required_amount, expiry removes it from
+-- locked_amount. Recompute the locks an operator hasn't overridden under that model and
+-- record each change in the history; manually adjusted locks are left as they are.
+WITH adjusted AS (
+    SELECT DISTINCT user_id, currency
+    FROM bus_lock_history
+    WHERE trigger = 'manual_adjustment'
+),
+expected AS (
+    SELECT user_id, currency,
+           SUM(TRUNC(amount * 0.001, 8)) FILTER (WHERE status IN ('pending', 'settled')) AS locked,
+           SUM(TRUNC(amount * 0.001, 8)) FILTER (WHERE status = 'settled') AS required
+    FROM transactions
+    WHERE tx_type = 'payment' AND user_id IS NOT NULL
+    GROUP BY user_id, currency
+),
+recalculated AS (
+    INSERT INTO bus_locks (id, user_id, currency, locked_amount, required_amount, last_calculated_at, created_at, updated_at)
+    SELECT uuid_generate_v4(), COALESCE(b.user_id, e.user_id), COALESCE(b.currency, e.currency),
+           COALESCE(e.locked, 0), COALESCE(e.required, 0), NOW(), NOW(), NOW()
+    FROM bus_locks b
+    FULL OUTER JOIN expected e ON e.user_id = b.user_id AND e.currency = b.currency
+    WHERE NOT EXISTS (
+        SELECT 1 FROM adjusted a
+        WHERE a.user_id = COALESCE(b.user_id, e.user_id)
+          AND a.currency = COALESCE(b.currency, e.currency)
+    )
+    ON CONFLICT (user_id, currency) DO UPDATE
+    SET locked_amount = EXCLUDED.locked_amount,
+        required_amount = EXCLUDED.required_amount,
+        version = bus_locks.version + 1,
+        last_calculated_at = NOW(), updated_at = NOW()
+    RETURNING user_id, currency, locked_amount, required_amount
+)
+INSERT INTO bus_lock_history (user_id, currency, locked_amount, required_amount, trigger, reason)
+SELECT user_id, currency, locked_amount, required_amount, 'recalculation',
+       'requirement now tracks settled volume'
+FROM recalculated;
@@ -0,0 +1,11 @@
+-- Idempotency-Key values seen on POST /api/payments. A repeated key replays the payment
+-- it created instead of creating another; request_hash catches the key being reused
+-- for a different request.
+CREATE TABLE payment_idempotency_keys (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    key VARCHAR(255) NOT NULL,
+    request_hash CHAR(64) NOT NULL,
+    transaction_id UUID NOT NULL REFERENCES transactions(id),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, key)
+);
@@ -0,0 +1,14 @@
+-- Endpoint each merchant's webhook events are POSTed to; NULL leaves them queued.
+ALTER TABLE users ADD COLUMN webhook_url TEXT;
+
+-- Delivery bookkeeping for the outbox. A claimed event's next_attempt_at is pushed
+-- out as a lease so another worker doesn't send it concurrently; a failed attempt
+-- sets it to the backoff time instead.
+ALTER TABLE webhook_events
+    ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN next_attempt_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    ADD COLUMN last_error TEXT;
+
+DROP INDEX idx_webhook_events_undelivered;
+CREATE INDEX idx_webhook_events_undelivered
+    ON webhook_events(user_id, created_at, id) WHERE delivered_at IS NULL;
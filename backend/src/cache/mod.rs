@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Entries kept before new ones are refused; bounds memory if many users page at once.
+const MAX_ENTRIES: usize = 10_000;
+
+type Entries<V> = HashMap<(Uuid, String), (Instant, Arc<V>)>;
+
+/// In-process cache of list responses keyed by user and normalized query string.
+/// Entries live for a short TTL and are dropped early by [`ListCache::invalidate_user`]
+/// whenever one of the user's transactions changes. A `None` TTL disables it entirely.
+pub struct ListCache<V> {
+    entries: Arc<Mutex<Entries<V>>>,
+    ttl: Option<Duration>,
+}
+
+// Not derived: that would needlessly require `V: Clone`, though values sit behind `Arc`
+impl<V> Clone for ListCache<V> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            ttl: self.ttl,
+        }
+    }
+}
+
+impl<V> ListCache<V> {
+    pub fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            ttl,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.ttl.is_some()
+    }
+
+    pub fn get(&self, user_id: Uuid, query: &str) -> Option<Arc<V>> {
+        let ttl = self.ttl?;
+        let mut entries = self.entries.lock().unwrap();
+        let key = (user_id, query.to_string());
+
+        match entries.get(&key) {
+            Some((stored_at, value)) if stored_at.elapsed() < ttl => Some(value.clone()),
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, user_id: Uuid, query: String, value: Arc<V>) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, (stored_at, _)| stored_at.elapsed() < ttl);
+            if entries.len() >= MAX_ENTRIES {
+                return;
+            }
+        }
+        entries.insert((user_id, query), (Instant::now(), value));
+    }
+
+    /// Drops every cached page for `user_id`; call after any write to their transactions.
+    pub fn invalidate_user(&self, user_id: Uuid) {
+        if self.ttl.is_none() {
+            return;
+        }
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|(owner, _), _| *owner != user_id);
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returns_entries_within_the_ttl() {
+        let cache = ListCache::new(Some(Duration::from_secs(60)));
+        let user = Uuid::new_v4();
+        cache.insert(user, "page=1".to_string(), Arc::new(7));
+
+        assert_eq!(cache.get(user, "page=1").as_deref(), Some(&7));
+        assert!(cache.get(user, "page=2").is_none());
+        assert!(cache.get(Uuid::new_v4(), "page=1").is_none());
+    }
+
+    #[test]
+    fn expires_entries_after_the_ttl() {
+        let cache = ListCache::new(Some(Duration::from_millis(20)));
+        let user = Uuid::new_v4();
+        cache.insert(user, "page=1".to_string(), Arc::new(7));
+
+        std::thread::sleep(Duration::from_millis(40));
+
+        assert!(cache.get(user, "page=1").is_none());
+        assert!(cache.entries.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalidate_user_drops_only_that_users_entries() {
+        let cache = ListCache::new(Some(Duration::from_secs(60)));
+        let (user, other) = (Uuid::new_v4(), Uuid::new_v4());
+        cache.insert(user, "page=1".to_string(), Arc::new(1));
+        cache.insert(user, "page=2".to_string(), Arc::new(2));
+        cache.insert(other, "page=1".to_string(), Arc::new(3));
+
+        cache.invalidate_user(user);
+
+        assert!(cache.get(user, "page=1").is_none());
+        assert!(cache.get(user, "page=2").is_none());
+        assert_eq!(cache.get(other, "page=1").as_deref(), Some(&3));
+    }
+
+    #[test]
+    fn clones_share_entries() {
+        let cache = ListCache::new(Some(Duration::from_secs(60)));
+        let user = Uuid::new_v4();
+        cache
+            .clone()
+            .insert(user, "page=1".to_string(), Arc::new(7));
+
+        assert_eq!(cache.get(user, "page=1").as_deref(), Some(&7));
+    }
+
+    #[test]
+    fn without_a_ttl_nothing_is_cached() {
+        let cache = ListCache::new(None);
+        let user = Uuid::new_v4();
+        cache.insert(user, "page=1".to_string(), Arc::new(7));
+
+        assert!(!cache.is_enabled());
+        assert!(cache.get(user, "page=1").is_none());
+    }
+}
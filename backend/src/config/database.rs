@@ -0,0 +1,91 @@
+use std::env;
+use std::time::Duration;
+
+pub struct DatabaseConfig {
+    pub max_connections: u32,
+    /// Idle connections are closed after this long (`DB_IDLE_TIMEOUT_SECS`, 0 disables)
+    pub idle_timeout: Option<Duration>,
+    /// Ping connections before handing them out, catching ones killed by a failover
+    pub test_before_acquire: bool,
+    /// Extra attempts for idempotent reads that hit a transient error
+    pub read_retries: u32,
+    /// First retry delay; doubles on each further attempt
+    pub retry_base_delay: Duration,
+    /// Server-side cap on each statement (`DB_STATEMENT_TIMEOUT_MS`, 0 disables)
+    pub statement_timeout: Option<Duration>,
+    /// How long a request waits for a free pool connection before giving up
+    pub acquire_timeout: Duration,
+    /// Tries at the initial connection before startup fails, for a database still booting
+    pub connect_attempts: u32,
+    /// Wait after the first failed connection attempt; doubles on each further attempt
+    pub connect_retry_delay: Duration,
+}
+
+impl DatabaseConfig {
+    pub fn from_env() -> Self {
+        let max_connections = env::var("DB_MAX_CONNECTIONS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5);
+
+        let idle_timeout = match env::var("DB_IDLE_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => Some(Duration::from_secs(300)),
+        };
+
+        let test_before_acquire = env::var("DB_TEST_BEFORE_ACQUIRE")
+            .map(|v| v != "false" && v != "0")
+            .unwrap_or(true);
+
+        let read_retries = env::var("DB_READ_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2);
+
+        let retry_base_delay_ms = env::var("DB_RETRY_BASE_DELAY_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(50);
+
+        let statement_timeout = match env::var("DB_STATEMENT_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            Some(0) => None,
+            Some(ms) => Some(Duration::from_millis(ms)),
+            None => Some(Duration::from_secs(10)),
+        };
+
+        let acquire_timeout_secs = env::var("DB_ACQUIRE_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5);
+
+        let connect_attempts = env::var("DB_CONNECT_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&attempts: &u32| attempts > 0)
+            .unwrap_or(5);
+
+        let connect_retry_delay_ms = env::var("DB_CONNECT_RETRY_DELAY_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1000);
+
+        Self {
+            max_connections,
+            idle_timeout,
+            test_before_acquire,
+            read_retries,
+            retry_base_delay: Duration::from_millis(retry_base_delay_ms),
+            statement_timeout,
+            acquire_timeout: Duration::from_secs(acquire_timeout_secs),
+            connect_attempts,
+            connect_retry_delay: Duration::from_millis(connect_retry_delay_ms),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use crate::fees::FeeSchedule;
+use std::collections::HashMap;
+use std::env;
+
+/// Per-currency fee schedules, keyed by upper-case currency code (`*` matches any currency).
+#[derive(Clone, Debug, Default)]
+pub struct FeeConfig {
+    schedules: HashMap<String, FeeSchedule>,
+}
+
+impl FeeConfig {
+    /// Reads `FEE_SCHEDULES` as JSON, e.g.
+    /// `{"USD":{"basis":"amount","tiers":[{"up_to":"10000","percent":"2.9"},{"percent":"2.4"}],"fixed":"0.30"}}`.
+    pub fn from_env() -> Self {
+        let schedules: HashMap<String, FeeSchedule> = match env::var("FEE_SCHEDULES") {
+            Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring invalid FEE_SCHEDULES: {}", e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+
+        Self {
+            schedules: schedules
+                .into_iter()
+                .map(|(currency, schedule)| (currency.to_uppercase(), schedule.normalized()))
+                .collect(),
+        }
+    }
+
+    pub fn schedule_for(&self, currency: &str) -> Option<&FeeSchedule> {
+        self.schedules
+            .get(&currency.to_uppercase())
+            .or_else(|| self.schedules.get("*"))
+    }
+}
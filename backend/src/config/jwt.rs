@@ -2,7 +2,11 @@ use std::env;
 
 pub struct JwtConfig {
     pub secret: String,
-    pub expiration_hours: i64,
+    pub expiry_secs: i64,
+    /// Written to and required in the `iss` claim
+    pub issuer: String,
+    /// Written to and required in the `aud` claim
+    pub audience: String,
 }
 
 impl JwtConfig {
@@ -10,7 +14,13 @@ impl JwtConfig {
         Self {
             secret: env::var("JWT_SECRET")
                 .unwrap_or_else(|_| "dev_secret_key_change_in_production".to_string()),
-            expiration_hours: 24,
+            expiry_secs: env::var("JWT_EXPIRY_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&secs: &i64| secs > 0)
+                .unwrap_or(86_400),
+            issuer: env::var("JWT_ISSUER").unwrap_or_else(|_| "bytus".to_string()),
+            audience: env::var("JWT_AUDIENCE").unwrap_or_else(|_| "bytus-api".to_string()),
         }
     }
 }
@@ -0,0 +1,53 @@
+use std::env;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable, coloured output for local development
+    Pretty,
+    /// One JSON object per line for log aggregation
+    Json,
+}
+
+impl LogFormat {
+    /// Reads `LOG_FORMAT` (`json` or `pretty`); when unset, production (`APP_ENV=production`)
+    /// logs JSON and everything else logs pretty.
+    pub fn from_env() -> Self {
+        Self::resolve(
+            env::var("LOG_FORMAT").ok().as_deref(),
+            env::var("APP_ENV").ok().as_deref(),
+        )
+    }
+
+    fn resolve(log_format: Option<&str>, app_env: Option<&str>) -> Self {
+        match log_format.map(|v| v.trim().to_lowercase()) {
+            Some(format) if format == "json" => Self::Json,
+            Some(format) if format == "pretty" => Self::Pretty,
+            _ if app_env.is_some_and(|v| v.eq_ignore_ascii_case("production")) => Self::Json,
+            _ => Self::Pretty,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_format_wins_and_production_defaults_to_json() {
+        assert_eq!(LogFormat::resolve(Some(" JSON "), None), LogFormat::Json);
+        assert_eq!(
+            LogFormat::resolve(Some("pretty"), Some("production")),
+            LogFormat::Pretty
+        );
+        assert_eq!(
+            LogFormat::resolve(None, Some("Production")),
+            LogFormat::Json
+        );
+        assert_eq!(
+            LogFormat::resolve(Some("xml"), Some("production")),
+            LogFormat::Json
+        );
+        assert_eq!(LogFormat::resolve(Some("xml"), None), LogFormat::Pretty);
+        assert_eq!(LogFormat::resolve(None, Some("staging")), LogFormat::Pretty);
+    }
+}
@@ -1,3 +1,21 @@
+pub mod database;
+pub mod fees;
 pub mod jwt;
+pub mod logging;
+pub mod pagination;
+pub mod payments;
+pub mod quota;
+pub mod receipts;
+pub mod server;
+pub mod webhooks;
 
+pub use database::DatabaseConfig;
+pub use fees::FeeConfig;
 pub use jwt::JwtConfig;
+pub use logging::LogFormat;
+pub use pagination::PaginationConfig;
+pub use payments::PaymentConfig;
+pub use quota::QuotaConfig;
+pub use receipts::ReceiptConfig;
+pub use server::ServerConfig;
+pub use webhooks::WebhookConfig;
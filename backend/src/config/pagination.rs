@@ -0,0 +1,93 @@
+use std::env;
+use std::time::Duration;
+
+pub struct PaginationConfig {
+    /// Largest `limit` a list endpoint accepts; larger values are rejected, not clamped
+    pub max_page_size: i32,
+    /// `limit` used when a list endpoint's request omits one; capped at `max_page_size`
+    pub default_page_size: i32,
+    /// How long `GET /api/transactions` pages are cached per user; `None` disables the cache
+    pub list_cache_ttl: Option<Duration>,
+}
+
+impl PaginationConfig {
+    pub fn from_env() -> Self {
+        Self::from_lookup(|key| env::var(key).ok())
+    }
+
+    /// Builds the config from any variable source; `from_env` passes the process environment.
+    fn from_lookup(var: impl Fn(&str) -> Option<String>) -> Self {
+        let max_page_size = var("MAX_PAGE_SIZE")
+            .and_then(|v| v.parse().ok())
+            .filter(|&size: &i32| size > 0)
+            .unwrap_or(100);
+
+        let default_page_size = var("DEFAULT_PAGE_SIZE")
+            .and_then(|v| v.parse().ok())
+            .filter(|&size: &i32| size > 0)
+            .unwrap_or(10)
+            .min(max_page_size);
+
+        let list_cache_ttl_ms = var("TRANSACTION_LIST_CACHE_TTL_MS")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        Self {
+            max_page_size,
+            default_page_size,
+            list_cache_ttl: (list_cache_ttl_ms > 0)
+                .then(|| Duration::from_millis(list_cache_ttl_ms)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> PaginationConfig {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        PaginationConfig::from_lookup(|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn defaults_when_unset() {
+        let config = config(&[]);
+
+        assert_eq!(config.max_page_size, 100);
+        assert_eq!(config.default_page_size, 10);
+        assert_eq!(config.list_cache_ttl, None);
+    }
+
+    #[test]
+    fn invalid_values_fall_back_to_defaults() {
+        let config = config(&[
+            ("MAX_PAGE_SIZE", "0"),
+            ("DEFAULT_PAGE_SIZE", "-5"),
+            ("TRANSACTION_LIST_CACHE_TTL_MS", "soon"),
+        ]);
+
+        assert_eq!(config.max_page_size, 100);
+        assert_eq!(config.default_page_size, 10);
+        assert_eq!(config.list_cache_ttl, None);
+    }
+
+    #[test]
+    fn default_page_size_is_capped_at_max() {
+        let config = config(&[("MAX_PAGE_SIZE", "25"), ("DEFAULT_PAGE_SIZE", "40")]);
+
+        assert_eq!(config.max_page_size, 25);
+        assert_eq!(config.default_page_size, 25);
+    }
+
+    #[test]
+    fn cache_ttl_is_read_in_milliseconds() {
+        let config = config(&[("TRANSACTION_LIST_CACHE_TTL_MS", "1500")]);
+
+        assert_eq!(config.list_cache_ttl, Some(Duration::from_millis(1500)));
+    }
+}
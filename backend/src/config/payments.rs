@@ -0,0 +1,110 @@
+use bigdecimal::BigDecimal;
+use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub struct PaymentConfig {
+    pub max_amount: Option<BigDecimal>,
+    /// How long a payment may stay pending before the sweeper fails it
+    pub pending_ttl: Duration,
+    pub expiry_sweep_interval: Duration,
+    /// Largest serialized `metadata` accepted on a payment
+    pub max_metadata_bytes: usize,
+    /// Deepest nesting of objects and arrays in `metadata`; the top-level object is 1
+    pub max_metadata_depth: usize,
+    /// Most keys `metadata` may hold, counting those of nested objects
+    pub max_metadata_keys: usize,
+    /// Lifetime of the `status_token` handed out for customer status polling
+    pub status_token_ttl: Duration,
+    /// Sandbox only: settle pending payments automatically once they are this old
+    pub auto_settle_after: Option<Duration>,
+    /// Minimum age before a pending payment may settle, unless its currency overrides it
+    pub settlement_hold: Duration,
+    /// Per-currency settlement holds, keyed by upper-case currency code
+    pub settlement_hold_by_currency: HashMap<String, Duration>,
+}
+
+impl PaymentConfig {
+    pub fn from_env() -> Self {
+        let pending_ttl_secs = env::var("PAYMENT_PENDING_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(86_400);
+
+        let sweep_secs = env::var("PAYMENT_EXPIRY_SWEEP_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&secs: &u64| secs > 0)
+            .unwrap_or(60);
+
+        let max_metadata_bytes = env::var("PAYMENT_MAX_METADATA_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(4096);
+
+        let max_metadata_depth = env::var("PAYMENT_MAX_METADATA_DEPTH")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&depth: &usize| depth > 0)
+            .unwrap_or(5);
+
+        let max_metadata_keys = env::var("PAYMENT_MAX_METADATA_KEYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(50);
+
+        let status_token_ttl_secs = env::var("PAYMENT_STATUS_TOKEN_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&secs: &u64| secs > 0)
+            .unwrap_or(3600);
+
+        // Off unless explicitly enabled, so production never settles without a processor
+        let auto_settle = env::var("AUTO_SETTLE").is_ok_and(|v| v.eq_ignore_ascii_case("true"));
+        let auto_settle_delay_secs = env::var("AUTO_SETTLE_DELAY_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+
+        let settlement_hold_secs = env::var("SETTLEMENT_HOLD_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        // JSON object of currency to seconds, e.g. `{"BTC":3600,"ETH":900}`
+        let settlement_hold_by_currency: HashMap<String, u64> =
+            match env::var("SETTLEMENT_HOLD_SECS_BY_CURRENCY") {
+                Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                    tracing::warn!("Ignoring invalid SETTLEMENT_HOLD_SECS_BY_CURRENCY: {}", e);
+                    HashMap::new()
+                }),
+                Err(_) => HashMap::new(),
+            };
+
+        Self {
+            max_amount: env::var("MAX_PAYMENT_AMOUNT")
+                .ok()
+                .and_then(|v| BigDecimal::from_str(v.trim()).ok()),
+            pending_ttl: Duration::from_secs(pending_ttl_secs),
+            expiry_sweep_interval: Duration::from_secs(sweep_secs),
+            max_metadata_bytes,
+            max_metadata_depth,
+            max_metadata_keys,
+            status_token_ttl: Duration::from_secs(status_token_ttl_secs),
+            auto_settle_after: auto_settle.then(|| Duration::from_secs(auto_settle_delay_secs)),
+            settlement_hold: Duration::from_secs(settlement_hold_secs),
+            settlement_hold_by_currency: settlement_hold_by_currency
+                .into_iter()
+                .map(|(currency, secs)| (currency.to_uppercase(), Duration::from_secs(secs)))
+                .collect(),
+        }
+    }
+
+    pub fn settlement_hold_for(&self, currency: &str) -> Duration {
+        self.settlement_hold_by_currency
+            .get(&currency.to_uppercase())
+            .copied()
+            .unwrap_or(self.settlement_hold)
+    }
+}
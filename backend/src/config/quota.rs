@@ -0,0 +1,18 @@
+use std::env;
+
+pub struct QuotaConfig {
+    /// Monthly request quota for users without a plan-specific one
+    /// (`MONTHLY_REQUEST_QUOTA`, unset or 0 means unlimited)
+    pub monthly_requests: Option<i64>,
+}
+
+impl QuotaConfig {
+    pub fn from_env() -> Self {
+        let monthly_requests = env::var("MONTHLY_REQUEST_QUOTA")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&quota: &i64| quota > 0);
+
+        Self { monthly_requests }
+    }
+}
@@ -0,0 +1,28 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::SigningKey;
+use rand::rngs::OsRng;
+use std::env;
+
+pub struct ReceiptConfig {
+    pub signing_key: SigningKey,
+}
+
+impl ReceiptConfig {
+    /// Reads `RECEIPT_SIGNING_KEY` (base64 Ed25519 seed). Without it an ephemeral key is
+    /// generated, so receipts stop verifying after a restart.
+    pub fn from_env() -> Self {
+        let configured = env::var("RECEIPT_SIGNING_KEY").ok().and_then(|raw| {
+            let seed: [u8; 32] = STANDARD.decode(raw.trim()).ok()?.try_into().ok()?;
+            Some(SigningKey::from_bytes(&seed))
+        });
+
+        let signing_key = configured.unwrap_or_else(|| {
+            tracing::warn!(
+                "RECEIPT_SIGNING_KEY missing or invalid; using an ephemeral receipt signing key"
+            );
+            SigningKey::generate(&mut OsRng)
+        });
+
+        Self { signing_key }
+    }
+}
@@ -0,0 +1,226 @@
+use axum::http::HeaderMap;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+pub struct ServerConfig {
+    pub shutdown_timeout: Duration,
+    /// Largest request body accepted by any route
+    pub max_body_bytes: usize,
+    /// External origin clients reach the API at, e.g. `https://api.bytus.io`
+    pub public_base_url: Option<String>,
+    /// Reverse proxies in front of the API that append to `X-Forwarded-For` (1 on
+    /// Railway); 0 ignores the header and uses the socket address
+    pub trusted_proxy_hops: usize,
+}
+
+impl ServerConfig {
+    pub fn from_env() -> Self {
+        let shutdown_timeout_secs = env::var("SHUTDOWN_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+
+        let max_body_bytes = env::var("MAX_BODY_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&bytes: &usize| bytes > 0)
+            .unwrap_or(64 * 1024);
+
+        let public_base_url = env::var("PUBLIC_BASE_URL")
+            .ok()
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty());
+
+        let trusted_proxy_hops = env::var("TRUSTED_PROXY_HOPS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        Self {
+            shutdown_timeout: Duration::from_secs(shutdown_timeout_secs),
+            max_body_bytes,
+            public_base_url,
+            trusted_proxy_hops,
+        }
+    }
+
+    /// Originating client address. Each trusted proxy appends the address it received
+    /// the request from, so the client is `trusted_proxy_hops` entries from the right;
+    /// anything further left was supplied by the client and can't be trusted. Falls back
+    /// to the socket address when the header is absent, too short or malformed.
+    pub fn client_ip(&self, headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<IpAddr> {
+        let forwarded = (self.trusted_proxy_hops > 0)
+            .then(|| {
+                let hops: Vec<&str> = headers
+                    .get_all("x-forwarded-for")
+                    .iter()
+                    .filter_map(|v| v.to_str().ok())
+                    .flat_map(|v| v.split(','))
+                    .map(str::trim)
+                    .collect();
+                hops.len()
+                    .checked_sub(self.trusted_proxy_hops)
+                    .and_then(|index| hops[index].parse().ok())
+            })
+            .flatten();
+
+        forwarded.or(peer.map(|addr| addr.ip()))
+    }
+
+    /// Absolute URL for `path_and_query`. `PUBLIC_BASE_URL` wins when set; otherwise the
+    /// origin is rebuilt from the proxy's `X-Forwarded-Proto`/`X-Forwarded-Host`, falling
+    /// back to `Host`. The forwarded headers are only read behind trusted proxies
+    /// (`trusted_proxy_hops > 0`); without one a client could point links anywhere.
+    /// `None` if no host is known.
+    pub fn absolute_url(&self, headers: &HeaderMap, path_and_query: &str) -> Option<String> {
+        if let Some(base) = &self.public_base_url {
+            return Some(format!("{}{}", base, path_and_query));
+        }
+
+        // Proxies may append to these headers; the first entry is the client-facing one
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+
+        let forwarded = |name: &str| header(name).filter(|_| self.trusted_proxy_hops > 0);
+
+        let host = forwarded("x-forwarded-host").or_else(|| header("host"))?;
+        let proto = forwarded("x-forwarded-proto").unwrap_or("http");
+        Some(format!("{}://{}{}", proto, host, path_and_query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(trusted_proxy_hops: usize) -> ServerConfig {
+        ServerConfig {
+            shutdown_timeout: Duration::from_secs(30),
+            max_body_bytes: 64 * 1024,
+            public_base_url: None,
+            trusted_proxy_hops,
+        }
+    }
+
+    fn forwarded_for(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+        headers
+    }
+
+    fn peer() -> Option<SocketAddr> {
+        Some("10.0.0.9:5000".parse().unwrap())
+    }
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn client_ip_ignores_forwarded_for_without_trusted_proxies() {
+        let headers = forwarded_for("203.0.113.7");
+
+        assert_eq!(config(0).client_ip(&headers, peer()), ip("10.0.0.9"));
+        assert_eq!(config(0).client_ip(&headers, None), None);
+    }
+
+    #[test]
+    fn client_ip_takes_the_entry_added_by_the_outermost_trusted_proxy() {
+        // Client-supplied spoof, real client, then an internal hop
+        let headers = forwarded_for("1.1.1.1, 203.0.113.7, 192.168.0.2");
+
+        assert_eq!(config(1).client_ip(&headers, peer()), ip("192.168.0.2"));
+        assert_eq!(config(2).client_ip(&headers, peer()), ip("203.0.113.7"));
+        assert_eq!(config(3).client_ip(&headers, peer()), ip("1.1.1.1"));
+    }
+
+    #[test]
+    fn client_ip_reads_entries_split_across_headers() {
+        let mut headers = forwarded_for("203.0.113.7");
+        headers.append("x-forwarded-for", "192.168.0.2".parse().unwrap());
+
+        assert_eq!(config(2).client_ip(&headers, peer()), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn client_ip_falls_back_to_the_peer_when_the_header_is_short_or_malformed() {
+        assert_eq!(
+            config(3).client_ip(&forwarded_for("203.0.113.7, 192.168.0.2"), peer()),
+            ip("10.0.0.9")
+        );
+        assert_eq!(
+            config(1).client_ip(&forwarded_for("not-an-ip"), peer()),
+            ip("10.0.0.9")
+        );
+        assert_eq!(
+            config(1).client_ip(&HeaderMap::new(), peer()),
+            ip("10.0.0.9")
+        );
+    }
+
+    fn request_headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn absolute_url_prefers_the_configured_base() {
+        let config = ServerConfig {
+            public_base_url: Some("https://api.bytus.io".to_string()),
+            ..config(1)
+        };
+        let headers =
+            request_headers(&[("host", "internal:8000"), ("x-forwarded-host", "evil.test")]);
+
+        assert_eq!(
+            config.absolute_url(&headers, "/api/payments/1"),
+            Some("https://api.bytus.io/api/payments/1".to_string())
+        );
+    }
+
+    #[test]
+    fn absolute_url_uses_forwarded_headers_behind_a_trusted_proxy() {
+        let headers = request_headers(&[
+            ("host", "internal:8000"),
+            ("x-forwarded-host", "api.bytus.io, internal"),
+            ("x-forwarded-proto", "https"),
+        ]);
+
+        assert_eq!(
+            config(1).absolute_url(&headers, "/api/transactions?page=2"),
+            Some("https://api.bytus.io/api/transactions?page=2".to_string())
+        );
+        assert_eq!(
+            config(1).absolute_url(&request_headers(&[("host", "internal:8000")]), "/x"),
+            Some("http://internal:8000/x".to_string())
+        );
+    }
+
+    #[test]
+    fn absolute_url_ignores_forwarded_headers_without_a_trusted_proxy() {
+        let headers = request_headers(&[
+            ("host", "localhost:8000"),
+            ("x-forwarded-host", "evil.test"),
+            ("x-forwarded-proto", "https"),
+        ]);
+
+        assert_eq!(
+            config(0).absolute_url(&headers, "/x"),
+            Some("http://localhost:8000/x".to_string())
+        );
+        assert_eq!(
+            config(0).absolute_url(&request_headers(&[("x-forwarded-host", "evil.test")]), "/x"),
+            None
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::env;
+use std::time::Duration;
+
+pub struct WebhookConfig {
+    /// How long the previous signing secret stays valid after a rotation
+    pub secret_grace: Duration,
+    /// Cap on concurrent deliveries to an endpoint in parallel mode
+    pub max_parallel_deliveries: usize,
+    /// How often the delivery worker looks for due events
+    pub poll_interval: Duration,
+    /// Per-request timeout for a delivery; slower endpoints count as failed
+    pub delivery_timeout: Duration,
+    /// Attempts before an event is given up on and left undelivered
+    pub max_attempts: i32,
+    /// Wait after the first failed attempt; doubles with each further failure
+    pub retry_base: Duration,
+}
+
+/// Longest wait between two attempts at the same event.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+
+impl WebhookConfig {
+    pub fn from_env() -> Self {
+        let grace_secs = env::var("WEBHOOK_SECRET_GRACE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(86400);
+        let max_parallel_deliveries = env::var("WEBHOOK_MAX_PARALLEL_DELIVERIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&n: &usize| n > 0)
+            .unwrap_or(8);
+        let poll_interval_ms = env::var("WEBHOOK_POLL_INTERVAL_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&ms: &u64| ms > 0)
+            .unwrap_or(1000);
+        let timeout_secs = env::var("WEBHOOK_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&secs: &u64| secs > 0)
+            .unwrap_or(10);
+        let max_attempts = env::var("WEBHOOK_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&n: &i32| n > 0)
+            .unwrap_or(8);
+        let retry_base_secs = env::var("WEBHOOK_RETRY_BASE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+
+        Self {
+            secret_grace: Duration::from_secs(grace_secs),
+            max_parallel_deliveries,
+            poll_interval: Duration::from_millis(poll_interval_ms),
+            delivery_timeout: Duration::from_secs(timeout_secs),
+            max_attempts,
+            retry_base: Duration::from_secs(retry_base_secs),
+        }
+    }
+
+    /// Wait before the next attempt once `attempts` have failed: `retry_base` doubling
+    /// per failure, capped at an hour.
+    pub fn retry_delay(&self, attempts: i32) -> Duration {
+        let doublings = attempts.saturating_sub(1).clamp(0, 16) as u32;
+        self.retry_base
+            .saturating_mul(2u32.pow(doublings))
+            .min(MAX_RETRY_DELAY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_cap() {
+        let config = WebhookConfig {
+            retry_base: Duration::from_secs(30),
+            ..WebhookConfig::from_env()
+        };
+
+        assert_eq!(config.retry_delay(1), Duration::from_secs(30));
+        assert_eq!(config.retry_delay(2), Duration::from_secs(60));
+        assert_eq!(config.retry_delay(4), Duration::from_secs(240));
+        assert_eq!(config.retry_delay(8), MAX_RETRY_DELAY);
+        assert_eq!(config.retry_delay(i32::MAX), MAX_RETRY_DELAY);
+    }
+}
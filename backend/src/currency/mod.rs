@@ -0,0 +1,100 @@
+use crate::fees::minor_units;
+use bigdecimal::{BigDecimal, RoundingMode, Signed};
+
+/// Currencies with known names and rules, as (ISO 4217 or ticker code, name). Payments
+/// accept any well-formed code; ones not listed here use two minor units.
+pub const CURRENCIES: &[(&str, &str)] = &[
+    ("USD", "US Dollar"),
+    ("EUR", "Euro"),
+    ("GBP", "Pound Sterling"),
+    ("JPY", "Japanese Yen"),
+    ("CHF", "Swiss Franc"),
+    ("CAD", "Canadian Dollar"),
+    ("AUD", "Australian Dollar"),
+    ("INR", "Indian Rupee"),
+    ("KRW", "South Korean Won"),
+    ("KWD", "Kuwaiti Dinar"),
+    ("BHD", "Bahraini Dinar"),
+    ("BTC", "Bitcoin"),
+    ("ETH", "Ether"),
+    ("USDC", "USD Coin"),
+    ("USDT", "Tether"),
+    ("DAI", "Dai"),
+    ("SOL", "Solana"),
+    ("LTC", "Litecoin"),
+];
+
+/// Display symbol for common currencies; others are shown by their code.
+fn symbol(currency: &str) -> Option<&'static str> {
+    match currency {
+        "USD" => Some("$"),
+        "EUR" => Some("€"),
+        "GBP" => Some("£"),
+        "JPY" => Some("¥"),
+        "INR" => Some("₹"),
+        "KRW" => Some("₩"),
+        "BTC" => Some("₿"),
+        _ => None,
+    }
+}
+
+/// Human-readable amount, e.g. `$1,234.50` or `¥1,235`: rounded half-up to the
+/// currency's minor units with comma grouping. Unknown currencies get a code suffix.
+pub fn format_display(amount: &BigDecimal, currency: &str) -> String {
+    let currency = currency.to_uppercase();
+    let rounded = amount.with_scale_round(minor_units(&currency), RoundingMode::HalfUp);
+    let plain = rounded.abs().to_plain_string();
+    let (whole, fraction) = match plain.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (plain.as_str(), None),
+    };
+
+    let mut grouped = String::with_capacity(whole.len() + whole.len() / 3);
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        grouped.push('.');
+        grouped.push_str(fraction);
+    }
+
+    let sign = if rounded.is_negative() { "-" } else { "" };
+    match symbol(&currency) {
+        Some(symbol) => format!("{}{}{}", sign, symbol, grouped),
+        None => format!("{}{} {}", sign, grouped, currency),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn display(amount: &str, currency: &str) -> String {
+        format_display(&BigDecimal::from_str(amount).unwrap(), currency)
+    }
+
+    #[test]
+    fn two_decimal_currency_is_grouped_and_rounded_half_up() {
+        assert_eq!(display("1234.5", "USD"), "$1,234.50");
+        assert_eq!(display("1234567.005", "usd"), "$1,234,567.01");
+        assert_eq!(display("999.994", "USD"), "$999.99");
+        assert_eq!(display("0", "USD"), "$0.00");
+        assert_eq!(display("-1234.5", "USD"), "-$1,234.50");
+    }
+
+    #[test]
+    fn zero_decimal_currency_has_no_fraction() {
+        assert_eq!(display("1234.5", "JPY"), "¥1,235");
+        assert_eq!(display("999", "JPY"), "¥999");
+        assert_eq!(display("1000", "JPY"), "¥1,000");
+    }
+
+    #[test]
+    fn currency_without_a_symbol_gets_its_code() {
+        assert_eq!(display("1500", "CHF"), "1,500.00 CHF");
+    }
+}
@@ -0,0 +1,61 @@
+//! Fixtures for tests that need Postgres. They run against `TEST_DATABASE_URL`, a scratch
+//! database with every migration applied, and are skipped when it is unset.
+
+use crate::config::DatabaseConfig;
+use crate::db::RetryPolicy;
+use crate::middleware::auth::{AuthMethod, Principal};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Connects to the scratch database, or returns `None` so the caller can skip.
+pub async fn pool() -> Option<PgPool> {
+    let url = std::env::var("TEST_DATABASE_URL").ok()?;
+    Some(
+        PgPool::connect(&url)
+            .await
+            .expect("TEST_DATABASE_URL is set but unreachable"),
+    )
+}
+
+pub fn retry() -> RetryPolicy {
+    RetryPolicy::from_config(&DatabaseConfig::from_env())
+}
+
+/// Fresh merchant with a unique email, so tests never see each other's rows.
+pub async fn create_user(pool: &PgPool) -> Uuid {
+    sqlx::query_scalar("INSERT INTO users (email, password_hash) VALUES ($1, 'x') RETURNING id")
+        .bind(format!("test-{}@example.test", Uuid::new_v4()))
+        .fetch_one(pool)
+        .await
+        .expect("insert test user")
+}
+
+pub fn principal(user_id: Uuid) -> Principal {
+    Principal {
+        user_id,
+        role: "merchant".to_string(),
+        auth_method: AuthMethod::Jwt,
+    }
+}
+
+/// Inserts a payment row directly, bypassing the handler and its BUS lock bookkeeping.
+pub async fn insert_payment(
+    pool: &PgPool,
+    user_id: Uuid,
+    amount: &str,
+    currency: &str,
+    status: &str,
+) -> Uuid {
+    sqlx::query_scalar(
+        "INSERT INTO transactions (user_id, tx_type, amount, currency, status)
+         VALUES ($1, 'payment', $2::numeric, $3, $4)
+         RETURNING id",
+    )
+    .bind(user_id)
+    .bind(amount)
+    .bind(currency)
+    .bind(status)
+    .fetch_one(pool)
+    .await
+    .expect("insert test payment")
+}
@@ -0,0 +1,151 @@
+use crate::db::ConstraintViolation;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Standard JSON error body: `{ "error": { "code", "message", "details"? } }`.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    details: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody<'a> {
+    error: ErrorDetail<'a>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ErrorDetail<'a> {
+    code: &'a str,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    details: Option<&'a serde_json::Value>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Lets handlers that still map errors to bare status codes return `ApiError`.
+impl From<StatusCode> for ApiError {
+    fn from(status: StatusCode) -> Self {
+        let code = match status {
+            StatusCode::BAD_REQUEST => "bad_request",
+            StatusCode::UNAUTHORIZED => "unauthorized",
+            StatusCode::FORBIDDEN => "forbidden",
+            StatusCode::NOT_FOUND => "not_found",
+            StatusCode::CONFLICT => "conflict",
+            StatusCode::TOO_MANY_REQUESTS => "rate_limited",
+            StatusCode::INTERNAL_SERVER_ERROR => "internal_error",
+            _ => "error",
+        };
+
+        Self::new(status, code, status.canonical_reason().unwrap_or("Error"))
+    }
+}
+
+/// `RowNotFound` and constraint violations are the only sqlx errors a client can cause;
+/// anything else is an outage and must not be reported as a 4xx. Timeouts get their own
+/// statuses so clients and load balancers can tell an overloaded database from a bug.
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        if let Some((kind, constraint)) = crate::db::constraint_violation(&err) {
+            tracing::warn!(error = %err, "database constraint violated");
+            let error = match kind {
+                ConstraintViolation::Unique => Self::new(
+                    StatusCode::CONFLICT,
+                    "duplicate",
+                    "A record with these values already exists",
+                ),
+                ConstraintViolation::ForeignKey => Self::new(
+                    StatusCode::CONFLICT,
+                    "reference_conflict",
+                    "The change refers to a record that doesn't exist or is still in use",
+                ),
+                ConstraintViolation::Check => Self::bad_request(
+                    "constraint_violation",
+                    "A value is outside what the data model allows",
+                ),
+            };
+            return match constraint {
+                Some(name) => error.with_details(serde_json::json!({ "constraint": name })),
+                None => error,
+            };
+        }
+
+        match err {
+            sqlx::Error::RowNotFound => StatusCode::NOT_FOUND.into(),
+            sqlx::Error::PoolTimedOut => {
+                tracing::warn!("timed out waiting for a database connection");
+                Self::new(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "database_unavailable",
+                    "No database connection available, try again shortly",
+                )
+            }
+            err if crate::db::is_statement_timeout(&err) => {
+                tracing::warn!(error = %err, "database statement timed out");
+                Self::new(
+                    StatusCode::GATEWAY_TIMEOUT,
+                    "query_timeout",
+                    "The database query took too long",
+                )
+            }
+            err => {
+                tracing::error!(error = %err, "database error");
+                StatusCode::INTERNAL_SERVER_ERROR.into()
+            }
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        // Emitted inside the request span, so the log line carries the request id
+        if self.status.is_server_error() {
+            tracing::error!(code = self.code, message = %self.message, "request failed");
+        }
+
+        let body = ErrorBody {
+            error: ErrorDetail {
+                code: self.code,
+                message: &self.message,
+                details: self.details.as_ref(),
+            },
+        };
+
+        (self.status, Json(body)).into_response()
+    }
+}
@@ -0,0 +1,67 @@
+use serde::Serialize;
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Events buffered per subscriber before slow consumers start missing them.
+const EVENT_BUFFER: usize = 256;
+
+#[derive(Clone, Copy, Debug, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionEventKind {
+    Created,
+    StatusChanged,
+}
+
+impl TransactionEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "transaction.created",
+            Self::StatusChanged => "transaction.status_changed",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct TransactionEvent {
+    /// Stable id of the recorded `webhook_events` row; the same status change always
+    /// carries the same id, so consumers can dedup redeliveries
+    pub event_id: Uuid,
+    pub kind: TransactionEventKind,
+    /// Owner of the transaction; used for fan-out filtering, never sent to clients
+    #[serde(skip_serializing)]
+    pub user_id: Uuid,
+    pub transaction_id: String,
+    pub status: String,
+    pub amount: String,
+    pub currency: String,
+    pub occurred_at: String,
+}
+
+/// In-process fan-out of transaction changes to live subscribers (SSE).
+#[derive(Clone)]
+pub struct TransactionEvents {
+    sender: broadcast::Sender<TransactionEvent>,
+}
+
+impl TransactionEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self { sender }
+    }
+
+    /// Best-effort: having no subscribers is not an error.
+    pub fn publish(&self, event: TransactionEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TransactionEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for TransactionEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
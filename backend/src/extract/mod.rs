@@ -0,0 +1,148 @@
+use crate::config::{PaymentConfig, ServerConfig};
+use crate::error::ApiError;
+use crate::handlers::payments::check_metadata_size;
+use axum::{
+    async_trait,
+    extract::{
+        path::ErrorKind, rejection::PathRejection, ConnectInfo, FromRef, FromRequest,
+        FromRequestParts, RawPathParams, Request,
+    },
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// `axum::extract::Path` with rejections reported as [`ApiError`] JSON instead of
+/// plain text, so a malformed id gets the same error shape as every other 400.
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let err = match axum::extract::Path::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(value)) => return Ok(Self(value)),
+            Err(PathRejection::FailedToDeserializePathParams(err)) => err,
+            Err(rejection) => {
+                tracing::error!(error = %rejection, "path extraction failed");
+                return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
+            }
+        };
+
+        // Uuid's own parse error doesn't carry the parameter name, so report the raw
+        // segments alongside it; id routes have a single parameter anyway
+        let params: BTreeMap<String, String> = RawPathParams::from_request_parts(parts, state)
+            .await
+            .map(|raw| {
+                raw.iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let name = match err.kind() {
+            ErrorKind::ParseErrorAtKey { key, .. } => Some(key.clone()),
+            _ if params.len() == 1 => params.keys().next().cloned(),
+            _ => None,
+        };
+        let expected = if err.body_text().contains("UUID") {
+            "a valid UUID"
+        } else {
+            "a valid value"
+        };
+        let message = match name {
+            Some(name) => format!("path parameter `{}` must be {}", name, expected),
+            None => format!("path parameters must each be {}", expected),
+        };
+
+        Err(ApiError::bad_request("invalid_path_param", message)
+            .with_details(serde_json::json!({ "params": params })))
+    }
+}
+
+/// Longest `User-Agent` kept, in characters.
+const MAX_USER_AGENT_LEN: usize = 512;
+
+/// Where a request came from: the client address per [`ServerConfig::client_ip`] and
+/// its `User-Agent`. Either may be missing; extraction never fails.
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    Arc<ServerConfig>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let server = Arc::<ServerConfig>::from_ref(state);
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| *addr);
+
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|ua| !ua.is_empty())
+            .map(|ua| ua.chars().take(MAX_USER_AGENT_LEN).collect());
+
+        Ok(Self {
+            ip: server
+                .client_ip(&parts.headers, peer)
+                .map(|ip| ip.to_string()),
+            user_agent,
+        })
+    }
+}
+
+/// Request bodies whose `metadata` values [`MetadataJson`] size-checks.
+pub trait WithMetadata {
+    /// Each `metadata` value present, with the field path used in error messages.
+    fn metadata_fields(&self) -> Vec<(String, &serde_json::Value)>;
+}
+
+/// `axum::Json` that also rejects oversized `metadata` with a 413 `metadata_too_large`
+/// as part of extraction, before the handler runs. The body-size layer still bounds
+/// the whole request; this names the offending field and the configured limit.
+pub struct MetadataJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for MetadataJson<T>
+where
+    T: DeserializeOwned + WithMetadata,
+    Arc<PaymentConfig>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let payment_config = Arc::<PaymentConfig>::from_ref(state);
+        for (field, metadata) in value.metadata_fields() {
+            check_metadata_size(&field, metadata, &payment_config)
+                .map_err(IntoResponse::into_response)?;
+        }
+
+        Ok(Self(value))
+    }
+}
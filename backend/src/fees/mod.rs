@@ -0,0 +1,247 @@
+use crate::config::FeeConfig;
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// What a schedule's tier thresholds are compared against.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TierBasis {
+    /// The payment's own amount
+    #[default]
+    Amount,
+    /// The merchant's settled volume in that currency over the last 30 days
+    RollingVolume,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct FeeTier {
+    /// Inclusive upper bound of the tier; `None` for the open-ended top tier
+    pub up_to: Option<BigDecimal>,
+    /// Percentage of the payment amount, e.g. `2.9`
+    pub percent: BigDecimal,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct FeeSchedule {
+    #[serde(default)]
+    pub basis: TierBasis,
+    #[serde(default)]
+    pub tiers: Vec<FeeTier>,
+    /// Flat amount added to every payment on top of the tier percentage
+    #[serde(default)]
+    pub fixed: BigDecimal,
+    /// Decimal places fees are rounded to; defaults to the currency's minor units
+    pub minor_units: Option<i64>,
+}
+
+/// Decimal places of a currency's smallest unit (ISO 4217 for fiat).
+pub fn minor_units(currency: &str) -> i64 {
+    match currency.to_uppercase().as_str() {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        // Transactions store 8 decimal places, so that's the finest crypto can go
+        "BTC" | "ETH" | "USDC" | "USDT" | "DAI" | "SOL" | "LTC" => 8,
+        _ => 2,
+    }
+}
+
+impl FeeSchedule {
+    /// Orders tiers by ascending bound with the open-ended tier last.
+    pub fn normalized(mut self) -> Self {
+        self.tiers.sort_by(|a, b| match (&a.up_to, &b.up_to) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+        self
+    }
+
+    /// First tier whose bound covers `basis_amount`; the highest tier catches anything above.
+    pub fn tier_for(&self, basis_amount: &BigDecimal) -> Option<&FeeTier> {
+        self.tiers
+            .iter()
+            .find(|tier| {
+                tier.up_to
+                    .as_ref()
+                    .is_none_or(|up_to| basis_amount <= up_to)
+            })
+            .or_else(|| self.tiers.last())
+    }
+
+    /// Fee for `amount`, with the tier selected by `basis_amount`, rounded half-up to
+    /// `scale` decimal places and never more than the amount itself.
+    pub fn fee_for(
+        &self,
+        amount: &BigDecimal,
+        basis_amount: &BigDecimal,
+        scale: i64,
+    ) -> BigDecimal {
+        let variable = match self.tier_for(basis_amount) {
+            Some(tier) => amount * &tier.percent / BigDecimal::from(100),
+            None => BigDecimal::zero(),
+        };
+
+        let fee = (variable + &self.fixed).with_scale_round(scale, RoundingMode::HalfUp);
+        fee.min(amount.clone())
+    }
+}
+
+/// Settled volume for the user in `currency` over the trailing 30 days.
+async fn rolling_volume(
+    pool: &PgPool,
+    user_id: Uuid,
+    currency: &str,
+) -> Result<BigDecimal, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT COALESCE(SUM(amount), 0)
+         FROM transactions
+         WHERE user_id = $1
+         AND currency = $2
+         AND status = 'settled'
+         AND created_at >= NOW() - INTERVAL '30 days'",
+    )
+    .bind(user_id)
+    .bind(currency)
+    .fetch_one(pool)
+    .await
+}
+
+/// Computes the processing fee for a payment; currencies without a schedule are free.
+pub async fn calculate_fee(
+    pool: &PgPool,
+    fees: &FeeConfig,
+    user_id: Uuid,
+    currency: &str,
+    amount: &BigDecimal,
+) -> Result<BigDecimal, sqlx::Error> {
+    let Some(schedule) = fees.schedule_for(currency) else {
+        return Ok(BigDecimal::zero());
+    };
+
+    let basis_amount = match schedule.basis {
+        TierBasis::Amount => amount.clone(),
+        TierBasis::RollingVolume => rolling_volume(pool, user_id, currency).await?,
+    };
+
+    let scale = schedule
+        .minor_units
+        .unwrap_or_else(|| minor_units(currency));
+    Ok(schedule.fee_for(amount, &basis_amount, scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    /// 2.9% up to 100, 2.4% up to 1000, 1.9% above, plus 0.30 fixed.
+    fn schedule() -> FeeSchedule {
+        FeeSchedule {
+            basis: TierBasis::Amount,
+            // Deliberately out of order; `normalized` sorts them
+            tiers: vec![
+                FeeTier {
+                    up_to: None,
+                    percent: dec("1.9"),
+                },
+                FeeTier {
+                    up_to: Some(dec("1000")),
+                    percent: dec("2.4"),
+                },
+                FeeTier {
+                    up_to: Some(dec("100")),
+                    percent: dec("2.9"),
+                },
+            ],
+            fixed: dec("0.30"),
+            minor_units: None,
+        }
+        .normalized()
+    }
+
+    fn percent_at(amount: &str) -> BigDecimal {
+        schedule().tier_for(&dec(amount)).unwrap().percent.clone()
+    }
+
+    #[test]
+    fn tier_for_selects_by_inclusive_upper_bound() {
+        assert_eq!(percent_at("99.99"), dec("2.9"));
+        assert_eq!(percent_at("100"), dec("2.9"));
+        assert_eq!(percent_at("100.01"), dec("2.4"));
+        assert_eq!(percent_at("999.99"), dec("2.4"));
+        assert_eq!(percent_at("1000"), dec("2.4"));
+        assert_eq!(percent_at("1000.01"), dec("1.9"));
+        assert_eq!(percent_at("1000000"), dec("1.9"));
+    }
+
+    #[test]
+    fn tier_for_falls_back_to_highest_tier_without_open_end() {
+        let mut schedule = schedule();
+        schedule.tiers.pop();
+
+        let tier = schedule.tier_for(&dec("5000")).unwrap();
+        assert_eq!(tier.percent, dec("2.4"));
+    }
+
+    #[test]
+    fn fee_for_applies_tier_percent_and_fixed_fee_at_each_boundary() {
+        let schedule = schedule();
+        let fee = |amount: &str| schedule.fee_for(&dec(amount), &dec(amount), 2);
+
+        // 2.9% + 0.30
+        assert_eq!(fee("50"), dec("1.75"));
+        assert_eq!(fee("100"), dec("3.20"));
+        // 2.4% + 0.30
+        assert_eq!(fee("100.01"), dec("2.70"));
+        assert_eq!(fee("1000"), dec("24.30"));
+        // 1.9% + 0.30
+        assert_eq!(fee("1000.01"), dec("19.30"));
+        assert_eq!(fee("2000"), dec("38.30"));
+    }
+
+    #[test]
+    fn fee_for_selects_tier_by_basis_not_amount() {
+        // Rolling volume past the top bound puts even a small payment in the cheapest tier
+        let fee = schedule().fee_for(&dec("50"), &dec("5000"), 2);
+        assert_eq!(fee, dec("1.25"));
+    }
+
+    #[test]
+    fn fee_for_rounds_half_up_and_never_exceeds_amount() {
+        let schedule = schedule();
+        // 2.9% of 10.05 = 0.29145, + 0.30 = 0.59145
+        assert_eq!(
+            schedule.fee_for(&dec("10.05"), &dec("10.05"), 2),
+            dec("0.59")
+        );
+        // 2.9% of 0.25 + 0.30 exceeds the amount
+        assert_eq!(schedule.fee_for(&dec("0.25"), &dec("0.25"), 2), dec("0.25"));
+    }
+
+    #[test]
+    fn fee_for_is_fixed_only_without_tiers() {
+        let schedule = FeeSchedule {
+            basis: TierBasis::Amount,
+            tiers: Vec::new(),
+            fixed: dec("0.30"),
+            minor_units: None,
+        };
+        assert_eq!(schedule.fee_for(&dec("10"), &dec("10"), 2), dec("0.30"));
+    }
+
+    #[test]
+    fn minor_units_follow_iso_4217_and_crypto_precision() {
+        assert_eq!(minor_units("usd"), 2);
+        assert_eq!(minor_units("JPY"), 0);
+        assert_eq!(minor_units("KWD"), 3);
+        assert_eq!(minor_units("BTC"), 8);
+    }
+}
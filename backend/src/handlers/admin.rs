@@ -0,0 +1,602 @@
+use crate::config::PaginationConfig;
+use crate::db::RetryPolicy;
+use crate::error::{ApiError, ErrorBody};
+use crate::extract::Path;
+use crate::handlers::bus_lock::BusLockBalance;
+use crate::handlers::payments::{bus_lock_rate, normalize_currency, BUS_LOCK_SCALE};
+use crate::handlers::transactions::{escape_like, validate_page_params};
+use crate::middleware::auth::Principal;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use bigdecimal::{BigDecimal, Zero};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReconcileQuery {
+    /// Page number (default 1)
+    pub page: Option<i32>,
+    /// Page size, 1 to `MAX_PAGE_SIZE` (default `DEFAULT_PAGE_SIZE`); out-of-range values are rejected
+    pub limit: Option<i32>,
+    /// Smallest absolute difference reported, as a decimal string (default 0.00000001)
+    pub tolerance: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BusLockDrift {
+    pub user_id: String,
+    pub currency: String,
+    /// `required_amount` as stored in `bus_locks` (0 if there is no row)
+    pub stored_required: String,
+    /// The BUS lock share of the user's settled payments in the currency, or the latest
+    /// manual adjustment plus the shares settled since it
+    pub expected_required: String,
+    /// `stored_required - expected_required`
+    pub delta: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BusLockReconcileResponse {
+    /// Largest absolute drift first
+    pub drifts: Vec<BusLockDrift>,
+    pub total: i64,
+    pub page: i32,
+}
+
+/// Reports (user, currency) pairs whose stored BUS lock requirement disagrees with the
+/// one implied by their settled payments and manual adjustments. Read-only.
+#[utoipa::path(
+    get,
+    path = "/api/admin/bus-lock/reconcile",
+    tag = "admin",
+    params(ReconcileQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Locks out of sync with settled payments", body = BusLockReconcileResponse),
+        (status = 400, description = "Invalid page, limit or tolerance", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn reconcile_bus_locks(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    State(pagination): State<Arc<PaginationConfig>>,
+    Query(params): Query<ReconcileQuery>,
+) -> Result<Json<BusLockReconcileResponse>, ApiError> {
+    let (page, limit) = validate_page_params(
+        params.page,
+        params.limit,
+        pagination.default_page_size,
+        &pagination,
+    )?;
+    let offset = i64::from(page - 1) * i64::from(limit);
+
+    let tolerance = match params.tolerance.as_deref() {
+        Some(raw) => BigDecimal::from_str(raw.trim())
+            .ok()
+            .filter(|t| *t >= BigDecimal::zero())
+            .ok_or_else(|| {
+                ApiError::bad_request(
+                    "invalid_tolerance",
+                    "tolerance must be a non-negative decimal",
+                )
+            })?,
+        None => BigDecimal::new(1.into(), 8),
+    };
+
+    // Settling a payment adds its bus_lock_share to the requirement, so it should equal
+    // the shares of settled volume. A manual adjustment resets that baseline: from the
+    // latest one on, the requirement is the adjusted value plus later settlements.
+    let rows = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                WITH adjusted AS (
+                    SELECT DISTINCT ON (user_id, currency)
+                           user_id, currency, required_amount, created_at AS adjusted_at
+                    FROM bus_lock_history
+                    WHERE trigger = 'manual_adjustment'
+                    ORDER BY user_id, currency, created_at DESC, id DESC
+                ),
+                settled AS (
+                    SELECT t.user_id, t.currency, SUM(TRUNC(t.amount * $4, $5)) AS share
+                    FROM transactions t
+                    LEFT JOIN adjusted a ON a.user_id = t.user_id AND a.currency = t.currency
+                    WHERE t.tx_type = 'payment' AND t.status = 'settled'
+                      AND (a.adjusted_at IS NULL OR t.settled_at > a.adjusted_at)
+                    GROUP BY t.user_id, t.currency
+                ),
+                expected AS (
+                    SELECT COALESCE(s.user_id, a.user_id) AS user_id,
+                           COALESCE(s.currency, a.currency) AS currency,
+                           COALESCE(a.required_amount, 0) + COALESCE(s.share, 0) AS required
+                    FROM settled s
+                    FULL OUTER JOIN adjusted a ON a.user_id = s.user_id AND a.currency = s.currency
+                ),
+                compared AS (
+                    SELECT COALESCE(b.user_id, e.user_id) AS user_id,
+                           COALESCE(b.currency, e.currency) AS currency,
+                           COALESCE(b.required_amount, 0) AS stored,
+                           COALESCE(e.required, 0) AS expected
+                    FROM bus_locks b
+                    FULL OUTER JOIN expected e ON e.user_id = b.user_id AND e.currency = b.currency
+                )
+                SELECT user_id AS "user_id!", currency AS "currency!",
+                       stored AS "stored!", expected AS "expected!",
+                       stored - expected AS "delta!",
+                       COUNT(*) OVER () AS "total!"
+                FROM compared
+                WHERE ABS(stored - expected) > $1
+                ORDER BY ABS(stored - expected) DESC, user_id, currency
+                LIMIT $2 OFFSET $3
+                "#,
+                tolerance,
+                i64::from(limit),
+                offset,
+                bus_lock_rate(),
+                BUS_LOCK_SCALE as i32
+            )
+            .fetch_all(&pool)
+        })
+        .await?;
+
+    let total = rows.first().map(|row| row.total).unwrap_or(0);
+    let drifts = rows
+        .into_iter()
+        .map(|row| BusLockDrift {
+            user_id: row.user_id.to_string(),
+            currency: row.currency,
+            stored_required: row.stored.to_string(),
+            expected_required: row.expected.to_string(),
+            delta: row.delta.to_string(),
+        })
+        .collect();
+
+    Ok(Json(BusLockReconcileResponse {
+        drifts,
+        total,
+        page,
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BusLockAdjustRequest {
+    pub currency: String,
+    /// New requirement, as a non-negative decimal string
+    pub required_amount: String,
+    /// Why the override was made; stored in the lock history
+    pub reason: String,
+}
+
+/// Longest adjustment reason accepted, in characters.
+const MAX_REASON_LEN: usize = 500;
+
+/// Overrides a user's BUS lock requirement in one currency, e.g. after a dispute is
+/// resolved. The locked amount is left alone; the history entry records who and why.
+#[utoipa::path(
+    post,
+    path = "/api/admin/bus-lock/{user_id}/adjust",
+    tag = "admin",
+    params(("user_id" = Uuid, Path, description = "User whose lock is adjusted")),
+    request_body = BusLockAdjustRequest,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Updated lock balance", body = BusLockBalance),
+        (status = 400, description = "Invalid currency, amount or reason", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "User not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn adjust_bus_lock(
+    State(pool): State<PgPool>,
+    Extension(principal): Extension<Principal>,
+    Path(user_id): Path<Uuid>,
+    Json(payload): Json<BusLockAdjustRequest>,
+) -> Result<Json<BusLockBalance>, ApiError> {
+    let currency = normalize_currency(&payload.currency)?;
+    let required_amount = BigDecimal::from_str(payload.required_amount.trim())
+        .ok()
+        .filter(|amount| *amount >= BigDecimal::zero())
+        .ok_or_else(|| {
+            ApiError::bad_request(
+                "invalid_amount",
+                "required_amount must be a non-negative decimal",
+            )
+        })?;
+    let reason = payload.reason.trim();
+    if reason.is_empty() || reason.chars().count() > MAX_REASON_LEN {
+        return Err(ApiError::bad_request(
+            "invalid_reason",
+            format!("reason must be 1 to {} characters", MAX_REASON_LEN),
+        ));
+    }
+
+    // The version bump makes concurrent optimistic writers re-read the overridden row
+    let lock = sqlx::query!(
+        r#"
+        WITH target AS (
+            SELECT id FROM users WHERE id = $2
+        ),
+        upserted AS (
+            INSERT INTO bus_locks (id, user_id, currency, locked_amount, required_amount, last_calculated_at, created_at, updated_at)
+            SELECT $1, id, $3, 0, $4, NOW(), NOW(), NOW() FROM target
+            ON CONFLICT (user_id, currency) DO UPDATE
+            SET required_amount = EXCLUDED.required_amount,
+                version = bus_locks.version + 1,
+                last_calculated_at = NOW(), updated_at = NOW()
+            RETURNING user_id, currency, locked_amount, required_amount, last_calculated_at
+        ),
+        history AS (
+            INSERT INTO bus_lock_history (user_id, currency, locked_amount, required_amount, trigger, actor_id, reason)
+            SELECT user_id, currency, locked_amount, required_amount, 'manual_adjustment', $5, $6
+            FROM upserted
+        )
+        SELECT currency AS "currency!", locked_amount AS "locked_amount!",
+               required_amount AS "required_amount!", last_calculated_at
+        FROM upserted
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        currency,
+        required_amount,
+        principal.user_id,
+        reason
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    tracing::info!(
+        actor_id = %principal.user_id,
+        %user_id,
+        currency = %lock.currency,
+        required_amount = %lock.required_amount,
+        reason,
+        "bus_lock manually adjusted"
+    );
+
+    let locked = lock.locked_amount.to_string().parse::<f64>().unwrap_or(0.0);
+    let required = lock
+        .required_amount
+        .to_string()
+        .parse::<f64>()
+        .unwrap_or(0.0);
+
+    Ok(Json(BusLockBalance::new(
+        lock.currency,
+        locked,
+        required,
+        lock.last_calculated_at
+            .map(|t| t.to_string())
+            .unwrap_or_default(),
+    )))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AdminUserQuery {
+    /// Page number (default 1)
+    pub page: Option<i32>,
+    /// Page size, 1 to `MAX_PAGE_SIZE` (default `DEFAULT_PAGE_SIZE`); out-of-range values are rejected
+    pub limit: Option<i32>,
+    /// Case-insensitive substring of the email address
+    pub email: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AdminUser {
+    pub id: String,
+    pub email: String,
+    pub role: String,
+    pub created_at: String,
+    /// Transactions owned by the user, excluding soft-deleted ones
+    pub transaction_count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AdminUserListResponse {
+    pub users: Vec<AdminUser>,
+    pub total: i64,
+    pub page: i32,
+}
+
+/// Lists accounts, newest first, for operators looking up a user to support.
+#[utoipa::path(
+    get,
+    path = "/api/admin/users",
+    tag = "admin",
+    params(AdminUserQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Page of users", body = AdminUserListResponse),
+        (status = 400, description = "Invalid page or limit", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn list_users(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    State(pagination): State<Arc<PaginationConfig>>,
+    Query(params): Query<AdminUserQuery>,
+) -> Result<Json<AdminUserListResponse>, ApiError> {
+    let (page, limit) = validate_page_params(
+        params.page,
+        params.limit,
+        pagination.default_page_size,
+        &pagination,
+    )?;
+    let offset = i64::from(page - 1) * i64::from(limit);
+
+    // Escaped so `%` and `_` in the term match literally
+    let email_pattern = params
+        .email
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(|e| format!("%{}%", escape_like(e)));
+
+    let rows = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT u.id, u.email, u.role, u.created_at,
+                       (SELECT COUNT(*) FROM transactions t
+                        WHERE t.user_id = u.id AND t.deleted_at IS NULL) AS "transaction_count!",
+                       COUNT(*) OVER () AS "total!"
+                FROM users u
+                WHERE $1::text IS NULL OR u.email ILIKE $1
+                ORDER BY u.created_at DESC, u.id DESC
+                LIMIT $2 OFFSET $3
+                "#,
+                email_pattern.as_deref(),
+                i64::from(limit),
+                offset
+            )
+            .fetch_all(&pool)
+        })
+        .await?;
+
+    let total = rows.first().map(|row| row.total).unwrap_or(0);
+    let users = rows
+        .into_iter()
+        .map(|row| AdminUser {
+            id: row.id.to_string(),
+            email: row.email,
+            role: row.role,
+            created_at: row.created_at.map(|t| t.to_string()).unwrap_or_default(),
+            transaction_count: row.transaction_count,
+        })
+        .collect();
+
+    Ok(Json(AdminUserListResponse { users, total, page }))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditEntry {
+    pub id: String,
+    /// `created`, `status_changed`, `metadata_updated`, `archived` or `deleted`
+    pub action: String,
+    /// Who made the change; null for system changes such as expiry
+    pub actor_user_id: Option<String>,
+    pub old_status: Option<String>,
+    pub new_status: Option<String>,
+    /// For metadata edits, `{"key": {"old": ..., "new": ...}}` per changed key
+    #[schema(value_type = Option<Object>)]
+    pub metadata_diff: Option<serde_json::Value>,
+    pub created_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TransactionAuditResponse {
+    pub transaction_id: String,
+    /// Oldest first
+    pub entries: Vec<AuditEntry>,
+}
+
+/// Full change history of any user's transaction, soft-deleted ones included.
+#[utoipa::path(
+    get,
+    path = "/api/transactions/{id}/audit",
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Transaction id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Audit trail", body = TransactionAuditResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Caller is not an admin"),
+        (status = 404, description = "Transaction not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_transaction_audit(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<TransactionAuditResponse>, ApiError> {
+    let exists = retry
+        .run(|| {
+            sqlx::query_scalar!(
+                r#"SELECT EXISTS (SELECT 1 FROM transactions WHERE id = $1) AS "exists!""#,
+                id
+            )
+            .fetch_one(&pool)
+        })
+        .await?;
+    if !exists {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+
+    let rows = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT id, action, actor_user_id, old_status, new_status, metadata_diff, created_at
+                FROM audit_log
+                WHERE transaction_id = $1
+                ORDER BY created_at, id
+                "#,
+                id
+            )
+            .fetch_all(&pool)
+        })
+        .await?;
+
+    let entries = rows
+        .into_iter()
+        .map(|row| AuditEntry {
+            id: row.id.to_string(),
+            action: row.action,
+            actor_user_id: row.actor_user_id.map(|id| id.to_string()),
+            old_status: row.old_status,
+            new_status: row.new_status,
+            metadata_diff: row.metadata_diff,
+            created_at: row.created_at.to_string(),
+        })
+        .collect();
+
+    Ok(Json(TransactionAuditResponse {
+        transaction_id: id.to_string(),
+        entries,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support;
+
+    async fn set_lock(pool: &PgPool, user_id: Uuid, currency: &str, required: &str) {
+        sqlx::query(
+            "INSERT INTO bus_locks (id, user_id, currency, locked_amount, required_amount, last_calculated_at, created_at, updated_at)
+             VALUES ($1, $2, $3, $4::numeric, $4::numeric, NOW(), NOW(), NOW())",
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(currency)
+        .bind(required)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    /// Every drift the report lists, across all pages.
+    async fn all_drifts(pool: &PgPool) -> Vec<BusLockDrift> {
+        let pagination = Arc::new(PaginationConfig::from_env());
+        let mut drifts = Vec::new();
+        for page in 1.. {
+            let Json(report) = reconcile_bus_locks(
+                State(pool.clone()),
+                State(test_support::retry()),
+                State(pagination.clone()),
+                Query(ReconcileQuery {
+                    page: Some(page),
+                    limit: Some(pagination.max_page_size),
+                    tolerance: None,
+                }),
+            )
+            .await
+            .unwrap();
+            if report.drifts.is_empty() {
+                break;
+            }
+            drifts.extend(report.drifts);
+        }
+        drifts
+    }
+
+    #[tokio::test]
+    async fn reconcile_reports_lock_drifted_from_settled_volume() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let drifted = test_support::create_user(&pool).await;
+        let in_sync = test_support::create_user(&pool).await;
+        for user_id in [drifted, in_sync] {
+            test_support::insert_payment(&pool, user_id, "1000.00", "USD", "settled").await;
+            test_support::insert_payment(&pool, user_id, "500.00", "USD", "settled").await;
+            // Pending volume is locked but not yet required
+            test_support::insert_payment(&pool, user_id, "9000.00", "USD", "pending").await;
+        }
+        set_lock(&pool, drifted, "USD", "2.00000000").await;
+        set_lock(&pool, in_sync, "USD", "1.50000000").await;
+
+        let drifts = all_drifts(&pool).await;
+
+        let reported: Vec<_> = drifts
+            .iter()
+            .filter(|d| d.user_id == drifted.to_string())
+            .collect();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].currency, "USD");
+        let decimal = |s: &str| BigDecimal::from_str(s).unwrap();
+        assert_eq!(decimal(&reported[0].stored_required), decimal("2"));
+        assert_eq!(decimal(&reported[0].expected_required), decimal("1.5"));
+        assert_eq!(decimal(&reported[0].delta), decimal("0.5"));
+        assert!(!drifts.iter().any(|d| d.user_id == in_sync.to_string()));
+    }
+
+    #[tokio::test]
+    async fn reconcile_measures_from_the_latest_manual_adjustment() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let admin = test_support::create_user(&pool).await;
+        let user_id = test_support::create_user(&pool).await;
+        test_support::insert_payment(&pool, user_id, "1000.00", "USD", "settled").await;
+        set_lock(&pool, user_id, "USD", "1.00000000").await;
+
+        let Json(adjusted) = adjust_bus_lock(
+            State(pool.clone()),
+            Extension(test_support::principal(admin)),
+            Path(user_id),
+            Json(BusLockAdjustRequest {
+                currency: "USD".into(),
+                required_amount: "0.25".into(),
+                reason: "dispute resolved in the merchant's favour".into(),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(adjusted.required_amount, 0.25);
+        assert!(!all_drifts(&pool)
+            .await
+            .iter()
+            .any(|d| d.user_id == user_id.to_string()));
+
+        // A payment settled after the override adds its share on top of it
+        let settled =
+            test_support::insert_payment(&pool, user_id, "2000.00", "USD", "settled").await;
+        sqlx::query(
+            "UPDATE transactions SET settled_at = NOW() + INTERVAL '1 second' WHERE id = $1",
+        )
+        .bind(settled)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let drifts = all_drifts(&pool).await;
+        let reported: Vec<_> = drifts
+            .iter()
+            .filter(|d| d.user_id == user_id.to_string())
+            .collect();
+        assert_eq!(reported.len(), 1);
+        let decimal = |s: &str| BigDecimal::from_str(s).unwrap();
+        assert_eq!(decimal(&reported[0].stored_required), decimal("0.25"));
+        assert_eq!(decimal(&reported[0].expected_required), decimal("2.25"));
+        assert_eq!(decimal(&reported[0].delta), decimal("-2"));
+    }
+}
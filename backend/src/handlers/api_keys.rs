@@ -1,15 +1,14 @@
-use crate::handlers::auth::Claims;
-use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    Extension, Json,
-};
+use crate::db::RetryPolicy;
+use crate::extract::Path;
+use crate::middleware::auth::{AuthMethod, Principal};
+use axum::{extract::State, http::StatusCode, Extension, Json};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use sqlx::PgPool;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ApiKey {
     pub id: String,
     pub name: String,
@@ -20,13 +19,13 @@ pub struct ApiKey {
     pub revoked: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreateApiKeyRequest {
     pub name: String,
     pub permissions: Option<Vec<String>>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct CreateApiKeyResponse {
     pub id: String,
     pub secret_key: String,
@@ -34,7 +33,7 @@ pub struct CreateApiKeyResponse {
     pub created_at: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct DeleteApiKeyResponse {
     pub message: String,
 }
@@ -52,24 +51,39 @@ fn generate_api_key() -> (String, String) {
     (key, hash)
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/keys",
+    tag = "api_keys",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Active API keys", body = Vec<ApiKey>),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error")
+    )
+)]
 pub async fn list_keys(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
 ) -> Result<Json<Vec<ApiKey>>, StatusCode> {
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
-
-    let rows = sqlx::query!(
-        r#"
-        SELECT id, name, key_hash, permissions, last_used_at, created_at, revoked_at
-        FROM api_keys
-        WHERE user_id = $1 AND revoked_at IS NULL
-        ORDER BY created_at DESC
-        "#,
-        user_id
-    )
-    .fetch_all(&pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let user_id = principal.user_id;
+
+    let rows = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT id, name, key_hash, permissions, last_used_at, created_at, revoked_at
+                FROM api_keys
+                WHERE user_id = $1 AND revoked_at IS NULL
+                ORDER BY created_at DESC
+                "#,
+                user_id
+            )
+            .fetch_all(&pool)
+        })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let keys: Vec<ApiKey> = rows
         .into_iter()
@@ -90,12 +104,24 @@ pub async fn list_keys(
     Ok(Json(keys))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/keys",
+    tag = "api_keys",
+    request_body = CreateApiKeyRequest,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "API key created; the secret is only returned once", body = CreateApiKeyResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error")
+    )
+)]
 pub async fn create_key(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
+    Extension(principal): Extension<Principal>,
     Json(payload): Json<CreateApiKeyRequest>,
 ) -> Result<Json<CreateApiKeyResponse>, StatusCode> {
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let user_id = principal.user_id;
     let (secret_key, key_hash) = generate_api_key();
     let id = Uuid::new_v4();
     let permissions = payload
@@ -125,12 +151,25 @@ pub async fn create_key(
     }))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/keys/{id}",
+    tag = "api_keys",
+    params(("id" = Uuid, Path, description = "API key id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "API key revoked", body = DeleteApiKeyResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "API key not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
 pub async fn delete_key(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
+    Extension(principal): Extension<Principal>,
     Path(key_id): Path<Uuid>,
 ) -> Result<Json<DeleteApiKeyResponse>, StatusCode> {
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let user_id = principal.user_id;
 
     let result = sqlx::query!(
         r#"
@@ -154,21 +193,31 @@ pub async fn delete_key(
     }))
 }
 
-pub async fn validate_api_key(pool: &PgPool, api_key: &str) -> Result<Uuid, StatusCode> {
+/// Resolves an API key to the owning user's [`Principal`] and records its use.
+pub async fn validate_api_key(
+    pool: &PgPool,
+    retry: RetryPolicy,
+    api_key: &str,
+) -> Result<Principal, StatusCode> {
     let mut hasher = Sha256::new();
     hasher.update(api_key);
     let key_hash = format!("{:x}", hasher.finalize());
 
-    let result = sqlx::query!(
-        r#"
-        SELECT user_id FROM api_keys
-        WHERE key_hash = $1 AND revoked_at IS NULL
-        "#,
-        key_hash
-    )
-    .fetch_one(pool)
-    .await
-    .map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let result = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT u.id, u.role FROM api_keys k
+                JOIN users u ON u.id = k.user_id
+                WHERE k.key_hash = $1 AND k.revoked_at IS NULL
+                "#,
+                key_hash
+            )
+            .fetch_optional(pool)
+        })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
 
     sqlx::query!(
         r#"
@@ -180,5 +229,9 @@ pub async fn validate_api_key(pool: &PgPool, api_key: &str) -> Result<Uuid, Stat
     .await
     .ok();
 
-    Ok(result.user_id.ok_or(StatusCode::UNAUTHORIZED)?)
+    Ok(Principal {
+        user_id: result.id,
+        role: result.role,
+        auth_method: AuthMethod::ApiKey,
+    })
 }
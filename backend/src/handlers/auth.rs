@@ -1,12 +1,16 @@
+use crate::config::JwtConfig;
 use argon2::password_hash::{rand_core::OsRng, SaltString};
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use axum::{extract::State, http::StatusCode, Json};
-use chrono::{Duration, Utc};
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{encode, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SignupRequest {
     pub email: String,
     pub password: String,
@@ -14,26 +18,31 @@ pub struct SignupRequest {
     pub business_type: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct AuthResponse {
     pub user_id: String,
     pub email: String,
     pub message: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct LoginResponse {
     pub token: String,
     pub user: UserInfo,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+pub struct LogoutResponse {
+    pub message: String,
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct UserInfo {
     pub id: String,
     pub email: String,
@@ -45,8 +54,23 @@ pub struct Claims {
     pub sub: String,
     pub email: String,
     pub exp: i64,
+    pub iss: String,
+    pub aud: String,
+    /// Unique token id; logout puts it on the denylist until `exp`
+    pub jti: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/auth/signup",
+    tag = "auth",
+    request_body = SignupRequest,
+    responses(
+        (status = 200, description = "User created", body = AuthResponse),
+        (status = 409, description = "Email already registered"),
+        (status = 500, description = "Internal server error")
+    )
+)]
 pub async fn signup(
     State(pool): State<PgPool>,
     Json(payload): Json<SignupRequest>,
@@ -84,8 +108,20 @@ pub async fn signup(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Authenticated; returns a bearer token", body = LoginResponse),
+        (status = 401, description = "Invalid credentials"),
+        (status = 500, description = "Internal server error")
+    )
+)]
 pub async fn login(
     State(pool): State<PgPool>,
+    State(jwt): State<Arc<JwtConfig>>,
     Json(payload): Json<LoginRequest>,
 ) -> Result<Json<LoginResponse>, StatusCode> {
     let user = sqlx::query!(
@@ -105,7 +141,7 @@ pub async fn login(
     verify_password(&payload.password, &user.password_hash)
         .map_err(|_| StatusCode::UNAUTHORIZED)?;
 
-    let token = generate_jwt(&user.id.to_string(), &user.email)
+    let token = generate_jwt(&jwt, &user.id.to_string(), &user.email)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(Json(LoginResponse {
@@ -118,6 +154,57 @@ pub async fn login(
     }))
 }
 
+/// Revokes the presented JWT; it is rejected with 401 from then until it expires.
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    tag = "auth",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Token revoked", body = LogoutResponse),
+        (status = 400, description = "Request was not authenticated with a JWT"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn logout(
+    State(pool): State<PgPool>,
+    claims: Option<Extension<Claims>>,
+) -> Result<Json<LogoutResponse>, StatusCode> {
+    // API keys are revoked through /api/keys, not here
+    let Extension(claims) = claims.ok_or(StatusCode::BAD_REQUEST)?;
+
+    let jti = Uuid::parse_str(&claims.jti).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let expires_at = DateTime::from_timestamp(claims.exp, 0)
+        .ok_or(StatusCode::UNAUTHORIZED)?
+        .naive_utc();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO revoked_tokens (jti, user_id, expires_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (jti) DO NOTHING
+        "#,
+        jti,
+        user_id,
+        expires_at
+    )
+    .execute(&pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Entries are only needed until the token would have expired anyway
+    sqlx::query!(r#"DELETE FROM revoked_tokens WHERE expires_at < NOW()"#)
+        .execute(&pool)
+        .await
+        .ok();
+
+    Ok(Json(LogoutResponse {
+        message: "Logged out".to_string(),
+    }))
+}
+
 fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
@@ -130,21 +217,138 @@ fn verify_password(password: &str, hash: &str) -> Result<(), argon2::password_ha
     Argon2::default().verify_password(password.as_bytes(), &parsed_hash)
 }
 
-fn generate_jwt(user_id: &str, email: &str) -> Result<String, jsonwebtoken::errors::Error> {
-    let expiration = Utc::now() + Duration::hours(24);
+pub(crate) fn generate_jwt(
+    config: &JwtConfig,
+    user_id: &str,
+    email: &str,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let expiration = Utc::now() + Duration::seconds(config.expiry_secs);
 
     let claims = Claims {
         sub: user_id.to_string(),
         email: email.to_string(),
         exp: expiration.timestamp(),
+        iss: config.issuer.clone(),
+        aud: config.audience.clone(),
+        jti: Uuid::new_v4().to_string(),
     };
 
-    let secret = std::env::var("JWT_SECRET")
-        .unwrap_or_else(|_| "dev_secret_key_change_in_production".to_string());
-
     encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
+        &EncodingKey::from_secret(config.secret.as_bytes()),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::db::test_support;
+    use crate::handlers::api_keys;
+    use crate::routes::create_router;
+    use crate::state::AppState;
+    use axum::{body::Body, http::Request, Router};
+    use tower::ServiceExt;
+
+    async fn send(app: &Router, method: &str, path: &str, credential: (&str, &str)) -> StatusCode {
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(credential.0, credential.1)
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn logout_revokes_only_the_presented_token() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let state = AppState::new(pool);
+        let jwt = state.jwt.clone();
+        let app = create_router(state, &ServerConfig::from_env());
+        // Each token gets its own jti
+        let token = || generate_jwt(&jwt, &user_id.to_string(), "owner@example.test").unwrap();
+        let (first, second) = (token(), token());
+        let bearer = |token: &str| format!("Bearer {}", token);
+
+        assert_eq!(
+            send(
+                &app,
+                "GET",
+                "/api/usage",
+                ("authorization", &bearer(&first))
+            )
+            .await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(
+                &app,
+                "POST",
+                "/api/auth/logout",
+                ("authorization", &bearer(&first))
+            )
+            .await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(
+                &app,
+                "GET",
+                "/api/usage",
+                ("authorization", &bearer(&first))
+            )
+            .await,
+            StatusCode::UNAUTHORIZED
+        );
+        // Other sessions of the same user are unaffected
+        assert_eq!(
+            send(
+                &app,
+                "GET",
+                "/api/usage",
+                ("authorization", &bearer(&second))
+            )
+            .await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn logout_with_an_api_key_is_rejected() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let Json(key) = api_keys::create_key(
+            State(pool.clone()),
+            Extension(test_support::principal(user_id)),
+            Json(api_keys::CreateApiKeyRequest {
+                name: "ci".to_string(),
+                permissions: None,
+            }),
+        )
+        .await
+        .unwrap();
+        let app = create_router(AppState::new(pool), &ServerConfig::from_env());
+
+        assert_eq!(
+            send(
+                &app,
+                "POST",
+                "/api/auth/logout",
+                ("x-api-key", &key.secret_key)
+            )
+            .await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            send(&app, "GET", "/api/usage", ("x-api-key", &key.secret_key)).await,
+            StatusCode::OK
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::db::RetryPolicy;
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::auth::Principal;
+use axum::{extract::State, Extension, Json};
+use serde::Serialize;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+/// Ledger position in a single currency.
+#[derive(Serialize, ToSchema)]
+pub struct CurrencyBalance {
+    pub currency: String,
+    /// Settled payments less refunds that haven't failed
+    pub available: String,
+    /// Pending payments that haven't expired
+    pub pending: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BalanceResponse {
+    pub balances: Vec<CurrencyBalance>,
+}
+
+/// Per-currency balance derived from the transaction ledger; soft-deleted rows are ignored.
+#[utoipa::path(
+    get,
+    path = "/api/balance",
+    tag = "balance",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Balance per currency", body = BalanceResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_balance(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<BalanceResponse>, ApiError> {
+    let user_id = principal.user_id;
+
+    let rows = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT currency,
+                       COALESCE(SUM(amount) FILTER (WHERE tx_type = 'payment' AND status = 'settled'), 0)
+                         - COALESCE(SUM(amount) FILTER (WHERE tx_type = 'refund' AND status <> 'failed'), 0)
+                         AS "available!",
+                       COALESCE(SUM(amount) FILTER (
+                           WHERE tx_type = 'payment' AND status = 'pending'
+                           AND (expires_at IS NULL OR expires_at > NOW())
+                       ), 0) AS "pending!"
+                FROM transactions
+                WHERE user_id = $1 AND deleted_at IS NULL
+                GROUP BY currency
+                ORDER BY currency
+                "#,
+                user_id
+            )
+            .fetch_all(&pool)
+        })
+        .await?;
+
+    let balances = rows
+        .into_iter()
+        .map(|row| CurrencyBalance {
+            currency: row.currency,
+            available: row.available.to_string(),
+            pending: row.pending.to_string(),
+        })
+        .collect();
+
+    Ok(Json(BalanceResponse { balances }))
+}
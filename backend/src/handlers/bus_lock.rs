@@ -1,61 +1,695 @@
-use crate::handlers::auth::Claims;
-use axum::{extract::State, http::StatusCode, Extension, Json};
-use serde::Serialize;
-use sqlx::PgPool;
+use crate::config::PaginationConfig;
+use crate::db::RetryPolicy;
+use crate::error::{ApiError, ErrorBody};
+use crate::handlers::payments::{bus_lock_rate, normalize_currency, BUS_LOCK_SCALE};
+use crate::handlers::transactions::{encode_cursor, parse_cursor, validate_page_params};
+use crate::middleware::auth::Principal;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-#[derive(Serialize)]
+/// Lock position in a single currency.
+#[derive(Serialize, ToSchema)]
 pub struct BusLockBalance {
-    pub user_id: String,
+    pub currency: String,
     pub locked_amount: f64,
     pub required_amount: f64,
     pub deficit: f64,
+    /// Whether the locked amount covers the requirement
+    pub is_sufficient: bool,
+    /// Amount to lock to become sufficient; equal to `deficit`
+    pub top_up_required: f64,
+    /// Locked as a percentage of required, to two decimals; null when nothing is required
+    pub utilization_pct: Option<f64>,
     pub last_calculated_at: String,
 }
 
+impl BusLockBalance {
+    pub(crate) fn new(
+        currency: String,
+        locked_amount: f64,
+        required_amount: f64,
+        last_calculated_at: String,
+    ) -> Self {
+        let deficit = (required_amount - locked_amount).max(0.0);
+        let utilization_pct = (required_amount > 0.0)
+            .then(|| (locked_amount / required_amount * 10_000.0).round() / 100.0);
+
+        Self {
+            currency,
+            locked_amount,
+            required_amount,
+            deficit,
+            is_sufficient: deficit == 0.0,
+            top_up_required: deficit,
+            utilization_pct,
+            last_calculated_at,
+        }
+    }
+}
+
+/// Per-currency lock balances. There is no rolled-up total: amounts in different
+/// currencies can't be summed without a reporting currency and FX rates.
+#[derive(Serialize, ToSchema)]
+pub struct BusLockBalances {
+    pub user_id: String,
+    pub balances: Vec<BusLockBalance>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/bus-lock/balance",
+    tag = "bus_lock",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Current BUS lock balance per currency", body = BusLockBalances),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error")
+    )
+)]
 pub async fn get_bus_lock_balance(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
-) -> Result<Json<BusLockBalance>, StatusCode> {
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
-
-    let lock = sqlx::query!(
-        r#"
-        SELECT locked_amount, required_amount, last_calculated_at FROM bus_locks WHERE user_id = $1
-        "#,
-        user_id
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<BusLockBalances>, ApiError> {
+    let user_id = principal.user_id;
+
+    let locks = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT currency, locked_amount, required_amount, last_calculated_at
+                FROM bus_locks
+                WHERE user_id = $1
+                ORDER BY currency
+                "#,
+                user_id
+            )
+            .fetch_all(&pool)
+        })
+        .await?;
+
+    let balances = locks
+        .into_iter()
+        .map(|lock_data| {
+            let locked = lock_data
+                .locked_amount
+                .to_string()
+                .parse::<f64>()
+                .unwrap_or(0.0);
+            let required = lock_data
+                .required_amount
+                .to_string()
+                .parse::<f64>()
+                .unwrap_or(0.0);
+
+            BusLockBalance::new(
+                lock_data.currency,
+                locked,
+                required,
+                lock_data
+                    .last_calculated_at
+                    .map(|t| t.to_string())
+                    .unwrap_or_default(),
+            )
+        })
+        .collect();
+
+    Ok(Json(BusLockBalances {
+        user_id: user_id.to_string(),
+        balances,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BusLockHistoryQuery {
+    /// Page number for offset pagination (default 1)
+    pub page: Option<i32>,
+    /// Page size, 1 to `MAX_PAGE_SIZE` (default `DEFAULT_PAGE_SIZE`); out-of-range values are rejected
+    pub limit: Option<i32>,
+    /// Opaque cursor from `next_cursor`; pass empty to start cursor pagination
+    pub cursor: Option<String>,
+}
+
+#[derive(FromRow)]
+struct BusLockHistoryRow {
+    id: Uuid,
+    currency: String,
+    locked_amount: BigDecimal,
+    required_amount: BigDecimal,
+    trigger: String,
+    actor_id: Option<Uuid>,
+    reason: Option<String>,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BusLockHistoryEntry {
+    pub id: String,
+    pub currency: String,
+    pub locked_amount: String,
+    pub required_amount: String,
+    /// What caused the recalculation: `payment`, `batch_payment`, `settlement`, `expiry`,
+    /// `manual_adjustment` or `recalculation` (a one-off migration)
+    pub trigger: String,
+    /// Admin who made a manual adjustment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor_id: Option<String>,
+    /// Reason given for a manual adjustment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BusLockHistoryResponse {
+    pub entries: Vec<BusLockHistoryEntry>,
+    pub total: i32,
+    pub page: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Lists BUS lock recalculations, newest first; paginates like `GET /api/transactions`.
+#[utoipa::path(
+    get,
+    path = "/api/bus-lock/history",
+    tag = "bus_lock",
+    params(BusLockHistoryQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Page of BUS lock recalculations", body = BusLockHistoryResponse),
+        (status = 400, description = "Invalid page, limit or cursor", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error")
     )
-    .fetch_optional(&pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    if let Some(lock_data) = lock {
-        let locked = lock_data
-            .locked_amount
-            .to_string()
-            .parse::<f64>()
-            .unwrap_or(0.0);
-        let required = lock_data
-            .required_amount
-            .to_string()
-            .parse::<f64>()
-            .unwrap_or(0.0);
-        let deficit = (required - locked).max(0.0);
-
-        Ok(Json(BusLockBalance {
-            user_id: user_id.to_string(),
-            locked_amount: locked,
-            required_amount: required,
-            deficit,
-            last_calculated_at: lock_data.last_calculated_at.unwrap().to_string(),
-        }))
+)]
+pub async fn get_bus_lock_history(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    State(pagination): State<Arc<PaginationConfig>>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<BusLockHistoryQuery>,
+) -> Result<Json<BusLockHistoryResponse>, ApiError> {
+    let user_id = principal.user_id;
+
+    let (page, limit) = validate_page_params(
+        params.page,
+        params.limit,
+        pagination.default_page_size,
+        &pagination,
+    )?;
+    // Widened first: a huge `page` would overflow i32 and panic or wrap negative
+    let offset = i64::from(page - 1) * i64::from(limit);
+
+    // Keyset pagination: any `cursor` param (empty for the first page) replaces OFFSET paging
+    let cursor_mode = params.cursor.is_some();
+    let cursor_position = match params.cursor.as_deref() {
+        Some(cursor) if !cursor.is_empty() => Some(parse_cursor(cursor)?),
+        _ => None,
+    };
+
+    // Rebuilt per attempt since a built QueryBuilder can't be executed twice
+    let page_query = || {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT id, currency, locked_amount, required_amount, trigger, actor_id, reason, created_at
+             FROM bus_lock_history WHERE user_id = ",
+        );
+        query.push_bind(user_id);
+
+        if cursor_mode {
+            if let Some((created_at, id)) = cursor_position {
+                query
+                    .push(" AND (created_at, id) < (")
+                    .push_bind(created_at)
+                    .push(", ")
+                    .push_bind(id)
+                    .push(")");
+            }
+            // Fetch one extra row to know whether another page exists
+            query
+                .push(" ORDER BY created_at DESC, id DESC LIMIT ")
+                .push_bind(limit + 1);
+        } else {
+            query
+                .push(" ORDER BY created_at DESC, id DESC LIMIT ")
+                .push_bind(limit)
+                .push(" OFFSET ")
+                .push_bind(offset);
+        }
+
+        query
+    };
+
+    let mut rows: Vec<BusLockHistoryRow> = retry
+        .run(|| async { page_query().build_query_as().fetch_all(&pool).await })
+        .await?;
+
+    let next_cursor = if cursor_mode && rows.len() > limit as usize {
+        rows.truncate(limit as usize);
+        rows.last().map(|row| encode_cursor(row.created_at, row.id))
     } else {
-        Ok(Json(BusLockBalance {
-            user_id: user_id.to_string(),
-            locked_amount: 0.0,
-            required_amount: 0.0,
-            deficit: 0.0,
-            last_calculated_at: chrono::Utc::now().to_string(),
-        }))
+        None
+    };
+
+    let total = retry
+        .run(|| {
+            sqlx::query_scalar!(
+                r#"SELECT COUNT(*) AS "count!" FROM bus_lock_history WHERE user_id = $1"#,
+                user_id
+            )
+            .fetch_one(&pool)
+        })
+        .await?;
+
+    let entries = rows
+        .into_iter()
+        .map(|row| BusLockHistoryEntry {
+            id: row.id.to_string(),
+            currency: row.currency,
+            locked_amount: row.locked_amount.to_string(),
+            required_amount: row.required_amount.to_string(),
+            trigger: row.trigger,
+            actor_id: row.actor_id.map(|id| id.to_string()),
+            reason: row.reason,
+            created_at: row.created_at.to_string(),
+        })
+        .collect();
+
+    Ok(Json(BusLockHistoryResponse {
+        entries,
+        total: i32::try_from(total).unwrap_or(i32::MAX),
+        page,
+        next_cursor,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BusLockContributorsQuery {
+    /// Currency of the lock to break down
+    pub currency: String,
+    /// How many contributors to return, 1 to `MAX_PAGE_SIZE` (default `DEFAULT_PAGE_SIZE`)
+    pub limit: Option<i32>,
+    /// Whose lock to inspect; admins only, defaults to the caller
+    pub user_id: Option<Uuid>,
+}
+
+/// A settled payment and the part of the lock requirement it accounts for.
+#[derive(Serialize, ToSchema)]
+pub struct BusLockContributor {
+    pub transaction_id: String,
+    pub amount: String,
+    /// Lock required for this payment (0.1% of its amount)
+    pub required_amount: String,
+    /// Percentage of the currency's total requirement, to two decimals
+    pub share_pct: f64,
+    pub created_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BusLockContributorsResponse {
+    pub user_id: String,
+    pub currency: String,
+    /// Requirement implied by all settled payments, as `/api/admin/bus-lock/reconcile` computes it
+    pub required_amount: String,
+    /// Settled payments contributing, including those beyond `limit`
+    pub contributor_count: i64,
+    pub contributors: Vec<BusLockContributor>,
+}
+
+/// Largest settled payments behind a currency's BUS lock requirement. Only settled
+/// payments count: pending ones are covered by the locked amount, not the requirement.
+#[utoipa::path(
+    get,
+    path = "/api/bus-lock/contributors",
+    tag = "bus_lock",
+    params(BusLockContributorsQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Settled payments ordered by amount, largest first", body = BusLockContributorsResponse),
+        (status = 400, description = "Invalid currency or limit", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "user_id given by a non-admin"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_bus_lock_contributors(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    State(pagination): State<Arc<PaginationConfig>>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<BusLockContributorsQuery>,
+) -> Result<Json<BusLockContributorsResponse>, ApiError> {
+    let user_id = match params.user_id {
+        Some(other) if other != principal.user_id && !principal.is_admin() => {
+            return Err(StatusCode::FORBIDDEN.into());
+        }
+        Some(other) => other,
+        None => principal.user_id,
+    };
+    let currency = normalize_currency(&params.currency)?;
+    let (_, limit) = validate_page_params(
+        None,
+        params.limit,
+        pagination.default_page_size,
+        &pagination,
+    )?;
+
+    let rows = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                WITH settled AS (
+                    SELECT id, amount, created_at,
+                           TRUNC(amount * $4, $5) AS required
+                    FROM transactions
+                    WHERE user_id = $1 AND currency = $2
+                      AND tx_type = 'payment' AND status = 'settled'
+                ), totals AS (
+                    SELECT id, amount, created_at, required,
+                           SUM(required) OVER () AS total,
+                           COUNT(*) OVER () AS count
+                    FROM settled
+                )
+                SELECT id, amount, created_at,
+                       required AS "required!", total AS "total!", count AS "count!",
+                       ROUND(required * 100 / NULLIF(total, 0), 2)::float8 AS share_pct
+                FROM totals
+                ORDER BY amount DESC, created_at, id
+                LIMIT $3
+                "#,
+                user_id,
+                currency,
+                limit as i64,
+                bus_lock_rate(),
+                BUS_LOCK_SCALE as i32
+            )
+            .fetch_all(&pool)
+        })
+        .await?;
+
+    let (total, contributor_count) = rows
+        .first()
+        .map(|row| (row.total.clone(), row.count))
+        .unwrap_or_default();
+
+    let contributors = rows
+        .into_iter()
+        .map(|row| BusLockContributor {
+            transaction_id: row.id.to_string(),
+            required_amount: row.required.with_scale(8).to_string(),
+            amount: row.amount.to_string(),
+            share_pct: row.share_pct.unwrap_or(0.0),
+            created_at: row.created_at.map(|t| t.to_string()).unwrap_or_default(),
+        })
+        .collect();
+
+    Ok(Json(BusLockContributorsResponse {
+        user_id: user_id.to_string(),
+        currency,
+        required_amount: total.with_scale(8).to_string(),
+        contributor_count,
+        contributors,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support;
+
+    fn balance(locked: f64, required: f64) -> BusLockBalance {
+        BusLockBalance::new("USD".to_string(), locked, required, String::new())
+    }
+
+    #[test]
+    fn balance_covering_the_requirement_has_no_deficit() {
+        let covered = balance(1.5, 1.2);
+        assert_eq!(covered.deficit, 0.0);
+        assert_eq!(covered.top_up_required, 0.0);
+        assert!(covered.is_sufficient);
+        assert_eq!(covered.utilization_pct, Some(125.0));
+
+        let exact = balance(2.0, 2.0);
+        assert!(exact.is_sufficient);
+        assert_eq!(exact.utilization_pct, Some(100.0));
+    }
+
+    #[test]
+    fn balance_short_of_the_requirement_reports_the_top_up() {
+        let short = balance(0.5, 2.0);
+        assert_eq!(short.deficit, 1.5);
+        assert_eq!(short.top_up_required, 1.5);
+        assert!(!short.is_sufficient);
+        assert_eq!(short.utilization_pct, Some(25.0));
+
+        let unlocked = balance(0.0, 1.0);
+        assert_eq!(unlocked.deficit, 1.0);
+        assert_eq!(unlocked.utilization_pct, Some(0.0));
+    }
+
+    #[test]
+    fn balance_with_nothing_required_is_sufficient_without_a_utilization() {
+        let empty = balance(0.0, 0.0);
+        assert_eq!(empty.deficit, 0.0);
+        assert!(empty.is_sufficient);
+        assert_eq!(empty.utilization_pct, None);
+
+        assert_eq!(balance(3.0, 0.0).utilization_pct, None);
+    }
+
+    async fn contributors(
+        pool: &PgPool,
+        principal: Principal,
+        user_id: Option<Uuid>,
+        limit: Option<i32>,
+    ) -> Result<BusLockContributorsResponse, ApiError> {
+        get_bus_lock_contributors(
+            State(pool.clone()),
+            State(test_support::retry()),
+            State(Arc::new(PaginationConfig {
+                max_page_size: 100,
+                default_page_size: 10,
+                list_cache_ttl: None,
+            })),
+            Extension(principal),
+            Query(BusLockContributorsQuery {
+                currency: "usd".to_string(),
+                limit,
+                user_id,
+            }),
+        )
+        .await
+        .map(|Json(body)| body)
+    }
+
+    async fn insert_lock(
+        pool: &PgPool,
+        user_id: Uuid,
+        currency: &str,
+        locked: &str,
+        required: &str,
+    ) {
+        sqlx::query(
+            "INSERT INTO bus_locks (id, user_id, currency, locked_amount, required_amount, last_calculated_at, created_at, updated_at)
+             VALUES ($1, $2, $3, $4::numeric, $5::numeric, NOW(), NOW(), NOW())",
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(currency)
+        .bind(locked)
+        .bind(required)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn balance_reports_each_currency_independently() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let other = test_support::create_user(&pool).await;
+        insert_lock(&pool, user_id, "USD", "5", "3").await;
+        insert_lock(&pool, user_id, "EUR", "1", "4").await;
+        insert_lock(&pool, other, "USD", "0", "100").await;
+
+        let Json(body) = get_bus_lock_balance(
+            State(pool.clone()),
+            State(test_support::retry()),
+            Extension(test_support::principal(user_id)),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(body.user_id, user_id.to_string());
+        let balances: Vec<_> = body
+            .balances
+            .iter()
+            .map(|b| {
+                (
+                    b.currency.as_str(),
+                    b.locked_amount,
+                    b.required_amount,
+                    b.deficit,
+                )
+            })
+            .collect();
+        assert_eq!(balances, [("EUR", 1.0, 4.0, 3.0), ("USD", 5.0, 3.0, 0.0)]);
+    }
+
+    async fn history(
+        pool: &PgPool,
+        user_id: Uuid,
+        page: Option<i32>,
+        cursor: Option<&str>,
+    ) -> Result<BusLockHistoryResponse, ApiError> {
+        get_bus_lock_history(
+            State(pool.clone()),
+            State(test_support::retry()),
+            State(Arc::new(PaginationConfig {
+                max_page_size: 100,
+                default_page_size: 10,
+                list_cache_ttl: None,
+            })),
+            Extension(test_support::principal(user_id)),
+            Query(BusLockHistoryQuery {
+                page,
+                limit: Some(100),
+                cursor: cursor.map(str::to_string),
+            }),
+        )
+        .await
+        .map(|Json(body)| body)
+    }
+
+    #[tokio::test]
+    async fn history_page_far_past_the_end_is_empty_not_an_overflow() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        sqlx::query(
+            "INSERT INTO bus_lock_history (user_id, currency, locked_amount, required_amount, trigger)
+             VALUES ($1, 'USD', 1, 1, 'payment')",
+        )
+        .bind(user_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let body = history(&pool, user_id, Some(i32::MAX), None).await.unwrap();
+
+        assert!(body.entries.is_empty());
+        assert_eq!(body.page, i32::MAX);
+        assert_eq!(body.total, 1);
+    }
+
+    #[tokio::test]
+    async fn history_rejects_a_malformed_cursor_with_a_message() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+
+        let err = history(&pool, user_id, None, Some("garbage"))
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(err.code(), "invalid_cursor");
+        assert!(!err.message().is_empty());
+    }
+
+    #[tokio::test]
+    async fn contributors_rank_settled_payments_by_amount() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let mut settled = Vec::new();
+        for amount in ["250.00", "1000.00", "37.50", "712.25"] {
+            settled
+                .push(test_support::insert_payment(&pool, user_id, amount, "USD", "settled").await);
+        }
+        test_support::insert_payment(&pool, user_id, "5000.00", "USD", "pending").await;
+        test_support::insert_payment(&pool, user_id, "900.00", "EUR", "settled").await;
+
+        let body = contributors(&pool, test_support::principal(user_id), None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(body.currency, "USD");
+        assert_eq!(body.contributor_count, 4);
+        let expected = [settled[1], settled[3], settled[0], settled[2]];
+        let ids: Vec<_> = body
+            .contributors
+            .iter()
+            .map(|c| c.transaction_id.clone())
+            .collect();
+        assert_eq!(ids, expected.map(|id| id.to_string()));
+        assert_eq!(body.contributors[0].required_amount, "1.00000000");
+        assert_eq!(body.required_amount, "1.99975000");
+
+        let total: f64 = body.contributors.iter().map(|c| c.share_pct).sum();
+        assert!((total - 100.0).abs() < 0.05, "shares summed to {total}");
+    }
+
+    #[tokio::test]
+    async fn contributors_limit_keeps_the_full_count_and_requirement() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        for amount in ["300.00", "100.00", "600.00"] {
+            test_support::insert_payment(&pool, user_id, amount, "USD", "settled").await;
+        }
+
+        let body = contributors(&pool, test_support::principal(user_id), None, Some(1))
+            .await
+            .unwrap();
+
+        assert_eq!(body.contributor_count, 3);
+        assert_eq!(body.contributors.len(), 1);
+        assert_eq!(
+            body.contributors[0].amount.parse::<BigDecimal>().unwrap(),
+            "600".parse::<BigDecimal>().unwrap()
+        );
+        assert_eq!(body.contributors[0].share_pct, 60.0);
+        assert_eq!(body.required_amount, "1.00000000");
+    }
+
+    #[tokio::test]
+    async fn contributors_of_another_user_are_admin_only() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let owner = test_support::create_user(&pool).await;
+        let other = test_support::create_user(&pool).await;
+        test_support::insert_payment(&pool, owner, "10.00", "USD", "settled").await;
+
+        let err = contributors(&pool, test_support::principal(other), Some(owner), None)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), "forbidden");
+
+        let mut admin = test_support::principal(other);
+        admin.role = crate::middleware::auth::ADMIN_ROLE.to_string();
+        let body = contributors(&pool, admin, Some(owner), None).await.unwrap();
+        assert_eq!(body.user_id, owner.to_string());
+        assert_eq!(body.contributor_count, 1);
     }
 }
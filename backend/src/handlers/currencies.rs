@@ -0,0 +1,42 @@
+use crate::currency::{self, CURRENCIES};
+use crate::fees::minor_units;
+use axum::{http::header, response::IntoResponse, Json};
+use bigdecimal::BigDecimal;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct CurrencyInfo {
+    pub code: String,
+    pub name: String,
+    /// Decimal places allowed in amounts
+    pub minor_units: i64,
+    /// 1234.5678 formatted the way `format=display` would show it
+    pub example: String,
+}
+
+/// Lists the currencies with known rules. The list only changes with a deploy, so
+/// clients and proxies may cache it for a day.
+#[utoipa::path(
+    get,
+    path = "/api/currencies",
+    tag = "currencies",
+    responses((status = 200, description = "Supported currencies and their precision", body = Vec<CurrencyInfo>))
+)]
+pub async fn list_currencies() -> impl IntoResponse {
+    let sample = BigDecimal::new(12345678.into(), 4);
+    let currencies: Vec<CurrencyInfo> = CURRENCIES
+        .iter()
+        .map(|&(code, name)| CurrencyInfo {
+            code: code.to_string(),
+            name: name.to_string(),
+            minor_units: minor_units(code),
+            example: currency::format_display(&sample, code),
+        })
+        .collect();
+
+    (
+        [(header::CACHE_CONTROL, "public, max-age=86400")],
+        Json(currencies),
+    )
+}
@@ -1,73 +1,124 @@
-use crate::handlers::auth::Claims;
+use crate::db::RetryPolicy;
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::auth::Principal;
 use axum::{extract::State, Extension, Json};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct DashboardOverview {
     pub monthly_volume: f64,
     pub transaction_count: i64,
     pub pending_settlement: f64,
     pub bus_locked: f64,
     pub bus_required: f64,
+    pub by_currency: Vec<CurrencyStats>,
 }
 
+/// Current-month figures for a single currency.
+#[derive(Serialize, Deserialize, ToSchema, sqlx::FromRow)]
+pub struct CurrencyStats {
+    pub currency: String,
+    pub transaction_count: i64,
+    pub settled_volume: f64,
+    pub pending_volume: f64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/dashboard/overview",
+    tag = "dashboard",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Current month overview", body = DashboardOverview),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
 pub async fn get_dashboard_overview(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
-) -> Result<Json<DashboardOverview>, (axum::http::StatusCode, String)> {
-    let user_id = uuid::Uuid::parse_str(&claims.sub)
-        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e.to_string()))?;
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<DashboardOverview>, ApiError> {
+    let user_id = principal.user_id;
 
     // Calculate monthly volume (current month, settled transactions)
-    let monthly_volume: Option<f64> = sqlx::query_scalar(
-        "SELECT CAST(COALESCE(SUM(amount), 0) AS DOUBLE PRECISION)
-         FROM transactions 
-         WHERE user_id = $1 
-         AND status = 'settled' 
-         AND created_at >= date_trunc('month', CURRENT_DATE)",
-    )
-    .bind(user_id)
-    .fetch_one(&pool)
-    .await
-    .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let monthly_volume: Option<f64> = retry
+        .run(|| {
+            sqlx::query_scalar(
+                "SELECT CAST(COALESCE(SUM(amount), 0) AS DOUBLE PRECISION)
+                 FROM transactions 
+                 WHERE user_id = $1 
+                 AND status = 'settled' 
+                 AND created_at >= date_trunc('month', CURRENT_DATE)",
+            )
+            .bind(user_id)
+            .fetch_one(&pool)
+        })
+        .await?;
 
     // Count transactions this month
-    let transaction_count: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) 
-         FROM transactions 
-         WHERE user_id = $1 
-         AND created_at >= date_trunc('month', CURRENT_DATE)",
-    )
-    .bind(user_id)
-    .fetch_one(&pool)
-    .await
-    .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let transaction_count: i64 = retry
+        .run(|| {
+            sqlx::query_scalar(
+                "SELECT COUNT(*) 
+                 FROM transactions 
+                 WHERE user_id = $1 
+                 AND created_at >= date_trunc('month', CURRENT_DATE)",
+            )
+            .bind(user_id)
+            .fetch_one(&pool)
+        })
+        .await?;
 
     // Calculate pending settlement
-    let pending_settlement: Option<f64> = sqlx::query_scalar(
-        "SELECT CAST(COALESCE(SUM(amount), 0) AS DOUBLE PRECISION)
-         FROM transactions 
-         WHERE user_id = $1 
-         AND status = 'pending'",
-    )
-    .bind(user_id)
-    .fetch_one(&pool)
-    .await
-    .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let pending_settlement: Option<f64> = retry
+        .run(|| {
+            sqlx::query_scalar(
+                "SELECT CAST(COALESCE(SUM(amount), 0) AS DOUBLE PRECISION)
+                 FROM transactions 
+                 WHERE user_id = $1 
+                 AND status = 'pending'",
+            )
+            .bind(user_id)
+            .fetch_one(&pool)
+        })
+        .await?;
 
-    // Get BUS lock amounts
-    let bus_lock: Option<(f64, f64)> = sqlx::query_as(
-        "SELECT CAST(locked_amount AS DOUBLE PRECISION), CAST(required_amount AS DOUBLE PRECISION)
-         FROM bus_locks 
-         WHERE user_id = $1",
-    )
-    .bind(user_id)
-    .fetch_optional(&pool)
-    .await
-    .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // Get BUS lock amounts, summed across currencies like monthly_volume;
+    // per-currency balances are served by /api/bus-lock/balance
+    let (bus_locked, bus_required): (f64, f64) = retry
+        .run(|| {
+            sqlx::query_as(
+                "SELECT CAST(COALESCE(SUM(locked_amount), 0) AS DOUBLE PRECISION),
+                        CAST(COALESCE(SUM(required_amount), 0) AS DOUBLE PRECISION)
+                 FROM bus_locks
+                 WHERE user_id = $1",
+            )
+            .bind(user_id)
+            .fetch_one(&pool)
+        })
+        .await?;
 
-    let (bus_locked, bus_required) = bus_lock.unwrap_or((0.0, 0.0));
+    // Per-currency breakdown for the current month
+    let by_currency: Vec<CurrencyStats> = retry
+        .run(|| {
+            sqlx::query_as(
+                "SELECT currency,
+                        COUNT(*) AS transaction_count,
+                        CAST(COALESCE(SUM(amount) FILTER (WHERE status = 'settled'), 0) AS DOUBLE PRECISION) AS settled_volume,
+                        CAST(COALESCE(SUM(amount) FILTER (WHERE status = 'pending'), 0) AS DOUBLE PRECISION) AS pending_volume
+                 FROM transactions
+                 WHERE user_id = $1
+                 AND created_at >= date_trunc('month', CURRENT_DATE)
+                 GROUP BY currency
+                 ORDER BY currency",
+            )
+            .bind(user_id)
+            .fetch_all(&pool)
+        })
+        .await?;
 
     Ok(Json(DashboardOverview {
         monthly_volume: monthly_volume.unwrap_or(0.0),
@@ -75,5 +126,61 @@ pub async fn get_dashboard_overview(
         pending_settlement: pending_settlement.unwrap_or(0.0),
         bus_locked,
         bus_required,
+        by_currency,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support;
+
+    #[tokio::test]
+    async fn by_currency_groups_mixed_currencies() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        for (amount, currency, status) in [
+            ("100.00", "USD", "settled"),
+            ("50.00", "USD", "pending"),
+            ("25.50", "USD", "settled"),
+            ("0.25", "BTC", "pending"),
+            ("40.00", "EUR", "settled"),
+            ("10.00", "EUR", "failed"),
+        ] {
+            test_support::insert_payment(&pool, user_id, amount, currency, status).await;
+        }
+
+        let Json(overview) = get_dashboard_overview(
+            State(pool),
+            State(test_support::retry()),
+            Extension(test_support::principal(user_id)),
+        )
+        .await
+        .unwrap();
+
+        let rows: Vec<_> = overview
+            .by_currency
+            .iter()
+            .map(|c| {
+                (
+                    c.currency.as_str(),
+                    c.transaction_count,
+                    c.settled_volume,
+                    c.pending_volume,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("BTC", 1, 0.0, 0.25),
+                ("EUR", 2, 40.0, 0.0),
+                ("USD", 3, 125.5, 50.0),
+            ]
+        );
+        assert_eq!(overview.transaction_count, 6);
+        assert_eq!(overview.monthly_volume, 165.5);
+    }
+}
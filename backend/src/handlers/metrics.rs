@@ -0,0 +1,19 @@
+use crate::middleware::metrics::Metrics;
+use axum::{extract::State, http::header, response::IntoResponse};
+use sqlx::PgPool;
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "operations",
+    responses((status = 200, description = "Prometheus text exposition format", body = String))
+)]
+pub async fn get_metrics(
+    State(metrics): State<Metrics>,
+    State(pool): State<PgPool>,
+) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(&pool),
+    )
+}
@@ -1,8 +1,17 @@
+pub mod admin;
 pub mod api_keys;
 pub mod auth;
+pub mod balance;
 pub mod bus_lock;
+pub mod currencies;
 pub mod dashboard;
+pub mod metrics;
 pub mod payments;
+pub mod receipts;
 pub mod settings;
+#[cfg(feature = "test-endpoints")]
+pub mod testing;
 pub mod transactions;
 pub mod treasury;
+pub mod usage;
+pub mod webhooks;
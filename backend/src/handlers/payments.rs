@@ -1,23 +1,43 @@
-use crate::handlers::auth::Claims;
+use crate::cache::ListCache;
+use crate::config::{FeeConfig, JwtConfig, PaymentConfig, ServerConfig};
+use crate::db::RetryPolicy;
+use crate::error::{ApiError, ErrorBody};
+use crate::events::{TransactionEvent, TransactionEventKind, TransactionEvents};
+use crate::extract::{ClientInfo, MetadataJson, Path, WithMetadata};
+use crate::fees;
+use crate::handlers::transactions::TransactionPage;
+use crate::middleware::auth::Principal;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     Extension, Json,
 };
-use bigdecimal::{BigDecimal, FromPrimitive};
+use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive, Zero};
+use chrono::{NaiveDateTime, SubsecRound, Utc};
+use email_address::{EmailAddress, Options};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreatePaymentRequest {
     pub amount: f64,
     pub currency: String,
     pub customer_email: String,
+    #[schema(value_type = Option<Object>)]
     pub metadata: Option<serde_json::Value>,
+    /// Free-form labels for filtering; up to 20, each 1-50 characters
+    pub tags: Option<Vec<String>>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct PaymentResponse {
     pub id: Uuid,
     pub amount: f64,
@@ -26,138 +46,2365 @@ pub struct PaymentResponse {
     pub customer_email: String,
     pub created_at: String,
     pub bus_lock_required: f64,
+    pub fee_amount: String,
+    /// Amount less the processing fee
+    pub net_amount: String,
+    /// Short-lived token a customer can use with `GET /api/payments/{id}/status`
+    pub status_token: String,
+    /// Sequential per-merchant number for display, e.g. `INV-000123`; null on a dry run
+    /// and on payments created before numbering existed
+    pub receipt_number: Option<String>,
+    /// Set on a `dry_run` preview; nothing was stored and `id` is the nil UUID
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
 }
 
-async fn calculate_and_update_bus_lock(
-    pool: &PgPool,
-    user_id: Uuid,
-    payment_amount: f64,
-) -> Result<f64, StatusCode> {
-    // Fixed: 0.001 = 0.1% (was 0.01 = 1%)
-    let lock_amount = payment_amount * 0.001;
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CreatePaymentQuery {
+    /// Validate and price the payment without storing it (default false)
+    pub dry_run: Option<bool>,
+}
 
-    let existing = sqlx::query!(
-        r#"SELECT locked_amount FROM bus_locks WHERE user_id = $1"#,
-        user_id
-    )
-    .fetch_optional(pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BatchPaymentRequest {
+    pub payments: Vec<CreatePaymentRequest>,
+}
 
-    if let Some(lock) = existing {
-        let current_locked = lock.locked_amount.to_string().parse::<f64>().unwrap_or(0.0);
-        let new_locked = current_locked + lock_amount;
-        let new_locked_decimal =
-            BigDecimal::from_f64(new_locked).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+impl WithMetadata for CreatePaymentRequest {
+    fn metadata_fields(&self) -> Vec<(String, &serde_json::Value)> {
+        self.metadata
+            .iter()
+            .map(|metadata| ("metadata".to_string(), metadata))
+            .collect()
+    }
+}
 
-        sqlx::query!(
-            r#"
-            UPDATE bus_locks
-            SET locked_amount = $1, required_amount = $1, last_calculated_at = NOW(), updated_at = NOW()
-            WHERE user_id = $2
-            "#,
-            new_locked_decimal,
-            user_id
+impl WithMetadata for BatchPaymentRequest {
+    fn metadata_fields(&self) -> Vec<(String, &serde_json::Value)> {
+        self.payments
+            .iter()
+            .enumerate()
+            .filter_map(|(i, payment)| {
+                let metadata = payment.metadata.as_ref()?;
+                Some((format!("payments[{}].metadata", i), metadata))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchPaymentResponse {
+    /// Created payments, in request order
+    pub payments: Vec<PaymentResponse>,
+}
+
+/// Upper bound on payments accepted by one batch request.
+const MAX_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FeePreviewRequest {
+    pub amount: f64,
+    pub currency: String,
+    /// Payment method; schedules are per-currency today, so it does not change the fee
+    pub method: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeePreviewResponse {
+    pub amount: String,
+    pub currency: String,
+    pub method: Option<String>,
+    pub fee_amount: String,
+    pub net_amount: String,
+}
+
+/// Converts and range-checks a requested payment amount, rejecting more decimal
+/// places than `currency` has minor units.
+fn validate_amount(
+    amount: f64,
+    currency: &str,
+    payment_config: &PaymentConfig,
+) -> Result<BigDecimal, ApiError> {
+    let amount_decimal = BigDecimal::from_f64(amount)
+        .ok_or_else(|| ApiError::bad_request("invalid_amount", "amount must be a finite number"))?;
+
+    if amount_decimal <= BigDecimal::zero() {
+        return Err(ApiError::bad_request(
+            "invalid_amount",
+            "amount must be greater than zero",
+        ));
+    }
+
+    // The f64's shortest decimal form is what the client actually wrote
+    let scale = fees::minor_units(currency);
+    let written = BigDecimal::from_str(&amount.to_string()).unwrap_or(amount_decimal.clone());
+    if written.with_scale(scale) != written {
+        return Err(ApiError::bad_request(
+            "invalid_amount_precision",
+            format!("amount must have at most {} decimal places", scale),
+        ));
+    }
+
+    if let Some(max_amount) = &payment_config.max_amount {
+        if &amount_decimal > max_amount {
+            return Err(ApiError::bad_request(
+                "amount_too_large",
+                format!("amount must not exceed {}", max_amount),
+            ));
+        }
+    }
+
+    Ok(amount_decimal)
+}
+
+/// Trims and uppercases a currency code, which must be 3 to 5 ASCII letters.
+pub(crate) fn normalize_currency(currency: &str) -> Result<String, ApiError> {
+    let currency = currency.trim().to_uppercase();
+    if !(3..=5).contains(&currency.len()) || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(ApiError::bad_request(
+            "invalid_currency",
+            "currency must be a 3 to 5 letter currency code",
+        ));
+    }
+
+    Ok(currency)
+}
+
+/// Validates a customer email and lowercases it so search matches regardless of case.
+fn normalize_email(email: &str) -> Result<String, ApiError> {
+    let email = email.trim().to_lowercase();
+
+    // Bare addresses on a real domain only: no display names or domain literals
+    let options = Options::default()
+        .with_required_tld()
+        .without_display_text()
+        .without_domain_literal();
+    if EmailAddress::parse_with_options(&email, options).is_err() {
+        return Err(ApiError::bad_request(
+            "invalid_email",
+            "customer_email must be a valid email address",
+        ));
+    }
+
+    Ok(email)
+}
+
+/// Nesting depth of a JSON value (scalars are 0) and the number of object keys in it.
+fn metadata_shape(value: &serde_json::Value) -> (usize, usize) {
+    let children: Box<dyn Iterator<Item = &serde_json::Value>> = match value {
+        serde_json::Value::Object(map) => Box::new(map.values()),
+        serde_json::Value::Array(items) => Box::new(items.iter()),
+        _ => return (0, 0),
+    };
+    let own_keys = value.as_object().map_or(0, |map| map.len());
+
+    children.fold((1, own_keys), |(depth, keys), child| {
+        let (child_depth, child_keys) = metadata_shape(child);
+        (depth.max(child_depth + 1), keys + child_keys)
+    })
+}
+
+/// Requires metadata to be a JSON object within the configured depth, key count and
+/// serialized size, so it stays filterable and bounded in storage.
+pub(crate) fn validate_metadata(
+    metadata: Option<&serde_json::Value>,
+    payment_config: &PaymentConfig,
+) -> Result<(), ApiError> {
+    let Some(metadata) = metadata else {
+        return Ok(());
+    };
+
+    if !metadata.is_object() {
+        return Err(ApiError::bad_request(
+            "invalid_metadata",
+            "metadata must be a JSON object",
+        ));
+    }
+
+    let (depth, keys) = metadata_shape(metadata);
+    if depth > payment_config.max_metadata_depth {
+        return Err(ApiError::bad_request(
+            "metadata_too_deep",
+            format!(
+                "metadata must not nest deeper than {} levels",
+                payment_config.max_metadata_depth
+            ),
+        ));
+    }
+    if keys > payment_config.max_metadata_keys {
+        return Err(ApiError::bad_request(
+            "metadata_too_many_keys",
+            format!(
+                "metadata must not have more than {} keys in total",
+                payment_config.max_metadata_keys
+            ),
+        ));
+    }
+
+    check_metadata_size("metadata", metadata, payment_config)
+}
+
+/// 413 `metadata_too_large` if `metadata` serializes to more than `PAYMENT_MAX_METADATA_BYTES`.
+/// `field` names it in the message, e.g. `payments[2].metadata` in a batch.
+pub(crate) fn check_metadata_size(
+    field: &str,
+    metadata: &serde_json::Value,
+    payment_config: &PaymentConfig,
+) -> Result<(), ApiError> {
+    let size = serde_json::to_vec(metadata)
+        .map(|v| v.len())
+        .unwrap_or(usize::MAX);
+    if size > payment_config.max_metadata_bytes {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "metadata_too_large",
+            format!(
+                "{} must not exceed {} bytes when serialized",
+                field, payment_config.max_metadata_bytes
+            ),
         )
-        .execute(pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    } else {
-        let lock_decimal =
-            BigDecimal::from_f64(lock_amount).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+        .with_details(serde_json::json!({
+            "field": field,
+            "size_bytes": size,
+            "limit_bytes": payment_config.max_metadata_bytes,
+        })));
+    }
 
-        sqlx::query!(
-            r#"
-            INSERT INTO bus_locks (id, user_id, locked_amount, required_amount, last_calculated_at, created_at, updated_at)
-            VALUES ($1, $2, $3, $3, NOW(), NOW(), NOW())
-            "#,
-            Uuid::new_v4(),
+    Ok(())
+}
+
+/// Most tags a single payment may carry.
+const MAX_TAGS: usize = 20;
+/// Longest tag accepted, in characters.
+const MAX_TAG_LEN: usize = 50;
+
+/// Trims tags, drops duplicates and enforces the count and length limits.
+fn normalize_tags(tags: Option<&[String]>) -> Result<Vec<String>, ApiError> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.unwrap_or_default() {
+        let tag = tag.trim();
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_LEN {
+            return Err(ApiError::bad_request(
+                "invalid_tag",
+                format!("tags must be 1 to {} characters", MAX_TAG_LEN),
+            ));
+        }
+        if !normalized.iter().any(|t| t == tag) {
+            normalized.push(tag.to_string());
+        }
+    }
+
+    if normalized.len() > MAX_TAGS {
+        return Err(ApiError::bad_request(
+            "too_many_tags",
+            format!("a payment may have at most {} tags", MAX_TAGS),
+        ));
+    }
+
+    Ok(normalized)
+}
+
+/// A payment request whose fields all passed validation, normalized for storage.
+struct ValidatedPayment {
+    amount: BigDecimal,
+    currency: String,
+    customer_email: String,
+    tags: Vec<String>,
+}
+
+/// Runs every field check on `request` and reports all failures keyed by field
+/// name, so a client can fix a bad request in one round trip.
+fn validate_payment_request(
+    request: &CreatePaymentRequest,
+    payment_config: &PaymentConfig,
+) -> Result<ValidatedPayment, BTreeMap<&'static str, ApiError>> {
+    let mut errors = BTreeMap::new();
+    let priced = validate_priced_amount(
+        request.amount,
+        &request.currency,
+        payment_config,
+        &mut errors,
+    );
+    let mut check = |field: &'static str, result: Result<(), ApiError>| {
+        if let Err(e) = result {
+            errors.insert(field, e);
+        }
+    };
+
+    let mut customer_email = None;
+    check(
+        "customer_email",
+        normalize_email(&request.customer_email).map(|e| customer_email = Some(e)),
+    );
+    check(
+        "metadata",
+        validate_metadata(request.metadata.as_ref(), payment_config),
+    );
+    let mut tags = None;
+    check(
+        "tags",
+        normalize_tags(request.tags.as_deref()).map(|t| tags = Some(t)),
+    );
+
+    match (priced, customer_email, tags) {
+        (Some((amount, currency)), Some(customer_email), Some(tags)) if errors.is_empty() => {
+            Ok(ValidatedPayment {
+                amount,
+                currency,
+                customer_email,
+                tags,
+            })
+        }
+        _ => Err(errors),
+    }
+}
+
+/// Currency and amount checks shared by payment creation and the fee preview, so a
+/// fee is only ever quoted for an amount `create_payment` would accept.
+fn validate_priced_amount(
+    amount: f64,
+    currency: &str,
+    payment_config: &PaymentConfig,
+    errors: &mut BTreeMap<&'static str, ApiError>,
+) -> Option<(BigDecimal, String)> {
+    let normalized = normalize_currency(currency)
+        .map_err(|e| errors.insert("currency", e))
+        .ok();
+    let amount = validate_amount(amount, currency, payment_config)
+        .map_err(|e| errors.insert("amount", e))
+        .ok();
+
+    amount.zip(normalized)
+}
+
+fn validate_fee_preview(
+    request: &FeePreviewRequest,
+    payment_config: &PaymentConfig,
+) -> Result<(BigDecimal, String), BTreeMap<&'static str, ApiError>> {
+    let mut errors = BTreeMap::new();
+    validate_priced_amount(
+        request.amount,
+        &request.currency,
+        payment_config,
+        &mut errors,
+    )
+    .ok_or(errors)
+}
+
+/// Folds per-field failures into one 422 with `details.errors` mapping field to message.
+fn validation_failed(errors: BTreeMap<&'static str, ApiError>) -> ApiError {
+    let fields: serde_json::Map<String, serde_json::Value> = errors
+        .iter()
+        .map(|(field, e)| (field.to_string(), e.message().into()))
+        .collect();
+
+    ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "validation_failed",
+        "one or more fields are invalid",
+    )
+    .with_details(serde_json::json!({ "errors": fields }))
+}
+
+/// `aud` of payment status tokens; keeps them from being accepted as API credentials.
+const STATUS_TOKEN_AUDIENCE: &str = "payment-status";
+
+/// Claims of a payment status token; `sub` is the payment id it is bound to.
+#[derive(Serialize, Deserialize)]
+struct StatusTokenClaims {
+    sub: String,
+    exp: i64,
+    iss: String,
+    aud: String,
+}
+
+fn issue_status_token(
+    config: &JwtConfig,
+    payment_id: Uuid,
+    ttl: Duration,
+) -> Result<String, ApiError> {
+    let claims = StatusTokenClaims {
+        sub: payment_id.to_string(),
+        exp: Utc::now().timestamp() + ttl.as_secs() as i64,
+        iss: config.issuer.clone(),
+        aud: STATUS_TOKEN_AUDIENCE.to_string(),
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.secret.as_bytes()),
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into())
+}
+
+/// True if `token` is an unexpired status token issued for `payment_id`.
+fn verify_status_token(config: &JwtConfig, token: &str, payment_id: Uuid) -> bool {
+    let mut validation = Validation::default();
+    validation.set_issuer(&[&config.issuer]);
+    validation.set_audience(&[STATUS_TOKEN_AUDIENCE]);
+    validation.leeway = 0;
+
+    decode::<StatusTokenClaims>(
+        token,
+        &DecodingKey::from_secret(config.secret.as_bytes()),
+        &validation,
+    )
+    .is_ok_and(|data| data.claims.sub == payment_id.to_string())
+}
+
+/// Read-modify-write attempts before giving up on a contended `bus_locks` row.
+const BUS_LOCK_MAX_ATTEMPTS: usize = 5;
+
+/// BUS collateral for one payment: 0.1% of its amount, truncated to the 8 places
+/// `bus_locks` stores. Every lock and requirement change uses it, so they can't drift.
+///
+/// Creating a payment adds its share to `locked_amount`; settling adds it to
+/// `required_amount`, which therefore tracks settled volume; expiry takes it back off
+/// `locked_amount`.
+pub(crate) fn bus_lock_share(amount: &BigDecimal) -> BigDecimal {
+    (amount * bus_lock_rate()).with_scale(BUS_LOCK_SCALE)
+}
+
+/// Fraction of a payment held as BUS collateral; SQL that computes shares in bulk binds
+/// this and [`BUS_LOCK_SCALE`] as `TRUNC(amount * rate, scale)` to match [`bus_lock_share`].
+pub(crate) fn bus_lock_rate() -> BigDecimal {
+    BigDecimal::new(1.into(), 3)
+}
+
+/// Decimal places BUS lock amounts are truncated to.
+pub(crate) const BUS_LOCK_SCALE: i64 = 8;
+
+/// Adds `share` to the user's locked amount for `currency` inside the caller's transaction.
+/// Gives up with a 409 after `BUS_LOCK_MAX_ATTEMPTS` lost version races; the caller's
+/// transaction is then dropped, so nothing it wrote commits and a retry can't duplicate.
+async fn calculate_and_update_bus_lock(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    currency: &str,
+    share: &BigDecimal,
+) -> Result<(), ApiError> {
+    // Optimistic concurrency: each write is conditional on the version that was read,
+    // so a concurrent recalculation forces a re-read instead of being overwritten.
+    for attempt in 0..BUS_LOCK_MAX_ATTEMPTS {
+        if attempt > 0 {
+            // Jittered backoff so contending writers don't retry in lockstep
+            let delay_ms = rand::random::<u64>() % (10 * attempt as u64) + 1;
+            tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
+        }
+
+        let existing = sqlx::query!(
+            r#"SELECT locked_amount, version FROM bus_locks WHERE user_id = $1 AND currency = $2"#,
             user_id,
-            lock_decimal
+            currency
         )
-        .execute(pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        let written = if let Some(lock) = existing {
+            sqlx::query!(
+                r#"
+                WITH updated AS (
+                    UPDATE bus_locks
+                    SET locked_amount = $1, version = version + 1,
+                        last_calculated_at = NOW(), updated_at = NOW()
+                    WHERE user_id = $2 AND currency = $4 AND version = $3
+                    RETURNING user_id, currency, locked_amount, required_amount, version
+                ),
+                history AS (
+                    INSERT INTO bus_lock_history (user_id, currency, locked_amount, required_amount, trigger)
+                    SELECT user_id, currency, locked_amount, required_amount, 'payment' FROM updated
+                )
+                SELECT version FROM updated
+                "#,
+                lock.locked_amount + share,
+                user_id,
+                lock.version,
+                currency
+            )
+            .fetch_optional(&mut *conn)
+            .await?
+            .is_some()
+        } else {
+            sqlx::query!(
+                r#"
+                WITH inserted AS (
+                    INSERT INTO bus_locks (id, user_id, currency, locked_amount, required_amount, last_calculated_at, created_at, updated_at)
+                    VALUES ($1, $2, $4, $3, 0, NOW(), NOW(), NOW())
+                    ON CONFLICT (user_id, currency) DO NOTHING
+                    RETURNING user_id, currency, locked_amount, required_amount, version
+                ),
+                history AS (
+                    INSERT INTO bus_lock_history (user_id, currency, locked_amount, required_amount, trigger)
+                    SELECT user_id, currency, locked_amount, required_amount, 'payment' FROM inserted
+                )
+                SELECT version FROM inserted
+                "#,
+                Uuid::new_v4(),
+                user_id,
+                share,
+                currency
+            )
+            .fetch_optional(&mut *conn)
+            .await?
+            .is_some()
+        };
+
+        if written {
+            return Ok(());
+        }
     }
 
-    Ok(lock_amount)
+    tracing::warn!(%user_id, currency, "bus_lock update lost the version race on every attempt");
+    Err(ApiError::new(
+        StatusCode::CONFLICT,
+        "bus_lock_contention",
+        "the BUS lock is being updated concurrently; the payment was not created, retry the request",
+    ))
+}
+
+/// Header a client sets so retrying a create can't make a second payment.
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Longest `Idempotency-Key` accepted, in bytes.
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Reads `Idempotency-Key`; it must be 1 to 255 visible ASCII characters when present.
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>, ApiError> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+
+    value
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN)
+        .map(|key| Some(key.to_string()))
+        .ok_or_else(|| {
+            ApiError::bad_request(
+                "invalid_idempotency_key",
+                format!(
+                    "Idempotency-Key must be 1 to {} visible ASCII characters",
+                    MAX_IDEMPOTENCY_KEY_LEN
+                ),
+            )
+        })
+}
+
+/// Hex SHA-256 of the request fields, to tell a genuine retry from a reused key.
+/// `serde_json` maps are sorted, so the hash doesn't depend on the client's key order.
+fn request_hash(request: &CreatePaymentRequest) -> String {
+    let canonical = serde_json::json!({
+        "amount": request.amount,
+        "currency": request.currency,
+        "customer_email": request.customer_email,
+        "metadata": request.metadata,
+        "tags": request.tags,
+    });
+    format!("{:x}", Sha256::digest(canonical.to_string()))
 }
 
+/// The payment an earlier request with the same `Idempotency-Key` created, if any.
+/// The same key with a different request body is rejected rather than replayed.
+async fn replay_payment(
+    pool: &PgPool,
+    retry: &RetryPolicy,
+    jwt: &JwtConfig,
+    payment_config: &PaymentConfig,
+    user_id: Uuid,
+    key: &str,
+    hash: &str,
+) -> Result<Option<PaymentResponse>, ApiError> {
+    let Some(record) = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT request_hash, transaction_id
+                FROM payment_idempotency_keys
+                WHERE user_id = $1 AND key = $2
+                "#,
+                user_id,
+                key
+            )
+            .fetch_optional(pool)
+        })
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    if record.request_hash != hash {
+        return Err(ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "idempotency_key_reused",
+            "this Idempotency-Key was already used with a different request",
+        ));
+    }
+
+    load_payment(
+        pool,
+        retry,
+        jwt,
+        payment_config,
+        user_id,
+        record.transaction_id,
+    )
+    .await
+    .map(Some)
+}
+
+/// `Location` pointing at `GET /api/payments/{id}`: absolute when the public origin is
+/// known, otherwise relative (RFC 9110 allows both).
+fn payment_location(server: &ServerConfig, request_headers: &HeaderMap, id: Uuid) -> HeaderMap {
+    let path = format!("/api/payments/{}", id);
+    let location = server.absolute_url(request_headers, &path).unwrap_or(path);
+    let mut response_headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&location) {
+        response_headers.insert(header::LOCATION, value);
+    }
+    response_headers
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/payments",
+    tag = "payments",
+    params(
+        CreatePaymentQuery,
+        ("Idempotency-Key" = Option<String>, Header, description = "Client-chosen key, 1 to 255 characters; repeating it replays the payment it created instead of creating another")
+    ),
+    request_body = CreatePaymentRequest,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 201, description = "Payment created; `Location` points at `GET /api/payments/{id}`", body = PaymentResponse),
+        (status = 200, description = "Nothing was created: a preview when `dry_run` is set, or the payment an earlier request with this `Idempotency-Key` created", body = PaymentResponse),
+        (status = 400, description = "`invalid_idempotency_key`", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 409, description = "Concurrent BUS lock update, or a write conflicted with a unique or foreign key constraint (`details.constraint`)", body = ErrorBody),
+        (status = 413, description = "Request body too large, or `metadata_too_large` when metadata exceeds PAYMENT_MAX_METADATA_BYTES", body = ErrorBody),
+        (status = 422, description = "Invalid fields (`details.errors` maps each field to its problem), or `idempotency_key_reused` with a different request", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+#[allow(clippy::too_many_arguments)]
 pub async fn create_payment(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
-    Json(payload): Json<CreatePaymentRequest>,
-) -> Result<Json<PaymentResponse>, StatusCode> {
-    // Extract authenticated user_id from JWT claims
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
-    
+    State(retry): State<RetryPolicy>,
+    State(fee_config): State<Arc<FeeConfig>>,
+    State(payment_config): State<Arc<PaymentConfig>>,
+    State(jwt): State<Arc<JwtConfig>>,
+    State(events): State<TransactionEvents>,
+    State(server): State<Arc<ServerConfig>>,
+    State(list_cache): State<ListCache<TransactionPage>>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<CreatePaymentQuery>,
+    client: ClientInfo,
+    headers: HeaderMap,
+    MetadataJson(payload): MetadataJson<CreatePaymentRequest>,
+) -> Result<(StatusCode, HeaderMap, Json<PaymentResponse>), ApiError> {
+    // Extract authenticated user_id from the request principal
+    let user_id = principal.user_id;
+
     let id = Uuid::new_v4();
-    let amount_decimal = BigDecimal::from_f64(payload.amount).ok_or(StatusCode::BAD_REQUEST)?;
+    let ValidatedPayment {
+        amount: amount_decimal,
+        currency,
+        customer_email,
+        tags,
+    } = validate_payment_request(&payload, &payment_config).map_err(validation_failed)?;
+    let idempotency = idempotency_key(&headers)?.map(|key| (key, request_hash(&payload)));
+
+    let fee_amount =
+        fees::calculate_fee(&pool, &fee_config, user_id, &currency, &amount_decimal).await?;
+    let net_amount = &amount_decimal - &fee_amount;
+
+    // Everything above only reads, so a dry run can stop here with what would be created
+    // 200 rather than 201 since nothing is created
+    if params.dry_run.unwrap_or(false) {
+        let preview = PaymentResponse {
+            id: Uuid::nil(),
+            amount: payload.amount,
+            currency,
+            status: "pending".to_string(),
+            customer_email,
+            // Microseconds, like the timestamps Postgres returns
+            created_at: Utc::now().naive_utc().trunc_subsecs(6).to_string(),
+            bus_lock_required: bus_lock_share(&amount_decimal).to_f64().unwrap_or_default(),
+            fee_amount: fee_amount.to_string(),
+            net_amount: net_amount.to_string(),
+            status_token: String::new(),
+            receipt_number: None,
+            dry_run: true,
+        };
+        return Ok((StatusCode::OK, HeaderMap::new(), Json(preview)));
+    }
+
+    // A retry of a request that already created a payment gets that payment back
+    if let Some((key, hash)) = &idempotency {
+        let replayed =
+            replay_payment(&pool, &retry, &jwt, &payment_config, user_id, key, hash).await?;
+        if let Some(payment) = replayed {
+            let location = payment_location(&server, &headers, payment.id);
+            return Ok((StatusCode::OK, location, Json(payment)));
+        }
+    }
+
+    // The payment, its event and audit rows, and the lock increase commit together
+    let mut tx = pool.begin().await?;
 
     // Fixed: Insert actual user_id (was NULL)
     let result = sqlx::query!(
         r#"
-        INSERT INTO transactions (id, user_id, tx_type, amount, currency, status, customer_email, metadata, created_at)
-        VALUES ($1, $2, 'payment', $3, $4, 'pending', $5, $6, NOW())
-        RETURNING id, amount, currency, status, customer_email, created_at
+        WITH seq AS (
+            INSERT INTO receipt_sequences (user_id, last_number) VALUES ($2, 1)
+            ON CONFLICT (user_id) DO UPDATE SET last_number = receipt_sequences.last_number + 1
+            RETURNING last_number
+        ),
+        inserted AS (
+            INSERT INTO transactions (id, user_id, tx_type, amount, currency, status, customer_email, metadata, fee_amount, net_amount, tags, created_at, expires_at, receipt_number, client_ip, user_agent)
+            SELECT $1, $2, 'payment', $3, $4, 'pending', $5, $6, $7, $8, $9, NOW(), NOW() + make_interval(secs => $10),
+                   'INV-' || lpad(last_number::text, GREATEST(6, length(last_number::text)), '0'), $12, $13
+            FROM seq
+            RETURNING id, user_id, amount, currency, status, customer_email, fee_amount, net_amount, created_at, receipt_number
+        ),
+        event AS (
+            INSERT INTO webhook_events (user_id, transaction_id, event_type, status)
+            SELECT user_id, id, $11, status FROM inserted
+            RETURNING id
+        ),
+        audit AS (
+            INSERT INTO audit_log (transaction_id, actor_user_id, action, new_status)
+            SELECT id, user_id, 'created', status FROM inserted
+        )
+        SELECT i.id AS "id!", i.amount AS "amount!", i.currency AS "currency!", i.status AS "status!",
+               i.customer_email, i.fee_amount, i.net_amount, i.created_at, i.receipt_number, e.id AS "event_id!"
+        FROM inserted i, event e
         "#,
         id,
         user_id,
         amount_decimal,
-        payload.currency,
-        payload.customer_email,
-        payload.metadata
+        currency,
+        customer_email,
+        payload.metadata,
+        fee_amount,
+        net_amount,
+        &tags,
+        payment_config.pending_ttl.as_secs_f64(),
+        TransactionEventKind::Created.as_str(),
+        client.ip.as_deref(),
+        client.user_agent.as_deref()
     )
-    .fetch_one(&pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .fetch_one(&mut *tx)
+    .await?;
 
     // Fixed: Use actual user_id (was Uuid::nil())
-    let bus_lock = calculate_and_update_bus_lock(&pool, user_id, payload.amount).await?;
+    let bus_lock = bus_lock_share(&result.amount);
+    calculate_and_update_bus_lock(&mut tx, user_id, &result.currency, &bus_lock).await?;
+
+    if let Some((key, hash)) = &idempotency {
+        // Waits on a concurrent request holding the same key until it commits
+        let claimed = sqlx::query!(
+            r#"
+            INSERT INTO payment_idempotency_keys (user_id, key, request_hash, transaction_id)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, key) DO NOTHING
+            RETURNING key
+            "#,
+            user_id,
+            key,
+            hash,
+            result.id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .is_some();
+
+        if !claimed {
+            // The other request won; roll ours back and return what it created
+            tx.rollback().await?;
+            let payment = replay_payment(&pool, &retry, &jwt, &payment_config, user_id, key, hash)
+                .await?
+                .ok_or(StatusCode::CONFLICT)?;
+            let location = payment_location(&server, &headers, payment.id);
+            return Ok((StatusCode::OK, location, Json(payment)));
+        }
+    }
 
-    Ok(Json(PaymentResponse {
+    tx.commit().await?;
+
+    let created_at = result.created_at.unwrap().to_string();
+    list_cache.invalidate_user(user_id);
+    events.publish(TransactionEvent {
+        event_id: result.event_id,
+        kind: TransactionEventKind::Created,
+        user_id,
+        transaction_id: result.id.to_string(),
+        status: result.status.clone(),
+        amount: result.amount.to_string(),
+        currency: result.currency.clone(),
+        occurred_at: created_at.clone(),
+    });
+
+    let response_headers = payment_location(&server, &headers, result.id);
+
+    let payment = PaymentResponse {
         id: result.id,
         amount: payload.amount,
         currency: result.currency,
         status: result.status,
         customer_email: result.customer_email.unwrap_or_default(),
-        created_at: result.created_at.unwrap().to_string(),
-        bus_lock_required: bus_lock,
+        created_at,
+        bus_lock_required: bus_lock.to_f64().unwrap_or_default(),
+        fee_amount: result.fee_amount.unwrap_or_default().to_string(),
+        net_amount: result.net_amount.unwrap_or_default().to_string(),
+        status_token: issue_status_token(&jwt, result.id, payment_config.status_token_ttl)?,
+        receipt_number: result.receipt_number,
+        dry_run: false,
+    };
+
+    Ok((StatusCode::CREATED, response_headers, Json(payment)))
+}
+
+/// Creates up to 500 payments atomically: every item is validated first, then all rows
+/// and the combined BUS lock increase are written in one database transaction.
+#[utoipa::path(
+    post,
+    path = "/api/payments/batch",
+    tag = "payments",
+    request_body = BatchPaymentRequest,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "All payments created", body = BatchPaymentResponse),
+        (status = 400, description = "Empty or oversized batch, or invalid items; `details.errors` lists each offending index and field", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 413, description = "`metadata_too_large`: an item's metadata exceeds PAYMENT_MAX_METADATA_BYTES", body = ErrorBody),
+        (status = 500, description = "Internal server error; nothing was created", body = ErrorBody)
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_payment_batch(
+    State(pool): State<PgPool>,
+    State(fee_config): State<Arc<FeeConfig>>,
+    State(payment_config): State<Arc<PaymentConfig>>,
+    State(jwt): State<Arc<JwtConfig>>,
+    State(events): State<TransactionEvents>,
+    State(list_cache): State<ListCache<TransactionPage>>,
+    Extension(principal): Extension<Principal>,
+    client: ClientInfo,
+    MetadataJson(payload): MetadataJson<BatchPaymentRequest>,
+) -> Result<Json<BatchPaymentResponse>, ApiError> {
+    let user_id = principal.user_id;
+
+    if payload.payments.is_empty() || payload.payments.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::bad_request(
+            "invalid_batch_size",
+            format!("batch must contain 1 to {} payments", MAX_BATCH_SIZE),
+        ));
+    }
+
+    // Validate everything up front so a bad item never opens a transaction
+    let mut validated = Vec::with_capacity(payload.payments.len());
+    let mut errors = Vec::new();
+    for (index, item) in payload.payments.iter().enumerate() {
+        match validate_payment_request(item, &payment_config) {
+            Ok(payment) => validated.push(payment),
+            Err(failures) => {
+                for (field, e) in failures {
+                    errors.push(serde_json::json!({
+                        "index": index,
+                        "field": field,
+                        "code": e.code(),
+                        "message": e.message(),
+                    }));
+                }
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(
+            ApiError::bad_request("invalid_batch", "one or more payments are invalid")
+                .with_details(serde_json::json!({ "errors": errors })),
+        );
+    }
+
+    let mut fee_amounts = Vec::with_capacity(validated.len());
+    for payment in &validated {
+        let fee = fees::calculate_fee(
+            &pool,
+            &fee_config,
+            user_id,
+            &payment.currency,
+            &payment.amount,
+        )
+        .await?;
+        fee_amounts.push(fee);
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let mut event_ids = Vec::with_capacity(validated.len());
+    let mut lock_totals: BTreeMap<String, BigDecimal> = BTreeMap::new();
+    let mut created = Vec::with_capacity(validated.len());
+    let rows = payload.payments.into_iter().zip(validated).zip(fee_amounts);
+    for ((item, payment), fee_amount) in rows {
+        let ValidatedPayment {
+            amount,
+            currency,
+            customer_email,
+            tags,
+        } = payment;
+        let net_amount = &amount - &fee_amount;
+        let result = sqlx::query!(
+            r#"
+            WITH seq AS (
+                INSERT INTO receipt_sequences (user_id, last_number) VALUES ($2, 1)
+                ON CONFLICT (user_id) DO UPDATE SET last_number = receipt_sequences.last_number + 1
+                RETURNING last_number
+            ),
+            inserted AS (
+                INSERT INTO transactions (id, user_id, tx_type, amount, currency, status, customer_email, metadata, fee_amount, net_amount, tags, created_at, expires_at, receipt_number, client_ip, user_agent)
+                SELECT $1, $2, 'payment', $3, $4, 'pending', $5, $6, $7, $8, $9, NOW(), NOW() + make_interval(secs => $10),
+                       'INV-' || lpad(last_number::text, GREATEST(6, length(last_number::text)), '0'), $12, $13
+                FROM seq
+                RETURNING id, user_id, amount, currency, status, customer_email, fee_amount, net_amount, created_at, receipt_number
+            ),
+            event AS (
+                INSERT INTO webhook_events (user_id, transaction_id, event_type, status)
+                SELECT user_id, id, $11, status FROM inserted
+                RETURNING id
+            ),
+            audit AS (
+                INSERT INTO audit_log (transaction_id, actor_user_id, action, new_status)
+                SELECT id, user_id, 'created', status FROM inserted
+            )
+            SELECT i.id AS "id!", i.amount AS "amount!", i.currency AS "currency!", i.status AS "status!",
+                   i.customer_email, i.fee_amount, i.net_amount, i.created_at, i.receipt_number, e.id AS "event_id!"
+            FROM inserted i, event e
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            amount,
+            currency,
+            customer_email,
+            item.metadata,
+            fee_amount,
+            net_amount,
+            &tags,
+            payment_config.pending_ttl.as_secs_f64(),
+            TransactionEventKind::Created.as_str(),
+            client.ip.as_deref(),
+            client.user_agent.as_deref()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        event_ids.push(result.event_id);
+        *lock_totals
+            .entry(result.currency.clone())
+            .or_insert_with(BigDecimal::zero) += bus_lock_share(&result.amount);
+        created.push(PaymentResponse {
+            id: result.id,
+            amount: item.amount,
+            currency: result.currency,
+            status: result.status,
+            customer_email: result.customer_email.unwrap_or_default(),
+            created_at: result.created_at.unwrap().to_string(),
+            // Same 0.1% share create_payment locks per payment
+            bus_lock_required: bus_lock_share(&result.amount).to_f64().unwrap_or_default(),
+            fee_amount: result.fee_amount.unwrap_or_default().to_string(),
+            net_amount: result.net_amount.unwrap_or_default().to_string(),
+            status_token: issue_status_token(&jwt, result.id, payment_config.status_token_ttl)?,
+            receipt_number: result.receipt_number,
+            dry_run: false,
+        });
+    }
+
+    // One locked-amount increase per currency for the whole batch; the row lock taken here
+    // serializes with other writers, and the version bump makes optimistic writers re-read.
+    for (currency, lock_total) in lock_totals {
+        sqlx::query!(
+            r#"
+            WITH upserted AS (
+                INSERT INTO bus_locks (id, user_id, currency, locked_amount, required_amount, last_calculated_at, created_at, updated_at)
+                VALUES ($1, $2, $4, $3, 0, NOW(), NOW(), NOW())
+                ON CONFLICT (user_id, currency) DO UPDATE
+                SET locked_amount = bus_locks.locked_amount + $3,
+                    version = bus_locks.version + 1,
+                    last_calculated_at = NOW(), updated_at = NOW()
+                RETURNING user_id, currency, locked_amount, required_amount
+            )
+            INSERT INTO bus_lock_history (user_id, currency, locked_amount, required_amount, trigger)
+            SELECT user_id, currency, locked_amount, required_amount, 'batch_payment' FROM upserted
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            lock_total,
+            currency
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    list_cache.invalidate_user(user_id);
+
+    for (payment, event_id) in created.iter().zip(event_ids) {
+        events.publish(TransactionEvent {
+            event_id,
+            kind: TransactionEventKind::Created,
+            user_id,
+            transaction_id: payment.id.to_string(),
+            status: payment.status.clone(),
+            amount: payment.amount.to_string(),
+            currency: payment.currency.clone(),
+            occurred_at: payment.created_at.clone(),
+        });
+    }
+
+    Ok(Json(BatchPaymentResponse { payments: created }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/payments/fee-preview",
+    tag = "payments",
+    request_body = FeePreviewRequest,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Fee that create_payment would charge", body = FeePreviewResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 422, description = "Invalid amount or currency, exactly as create_payment reports them", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn preview_fee(
+    State(pool): State<PgPool>,
+    State(fee_config): State<Arc<FeeConfig>>,
+    State(payment_config): State<Arc<PaymentConfig>>,
+    Extension(principal): Extension<Principal>,
+    Json(payload): Json<FeePreviewRequest>,
+) -> Result<Json<FeePreviewResponse>, ApiError> {
+    let user_id = principal.user_id;
+    let (amount_decimal, currency) =
+        validate_fee_preview(&payload, &payment_config).map_err(validation_failed)?;
+
+    let fee_amount =
+        fees::calculate_fee(&pool, &fee_config, user_id, &currency, &amount_decimal).await?;
+    let net_amount = &amount_decimal - &fee_amount;
+
+    Ok(Json(FeePreviewResponse {
+        amount: amount_decimal.to_string(),
+        currency,
+        method: payload.method,
+        fee_amount: fee_amount.to_string(),
+        net_amount: net_amount.to_string(),
     }))
 }
 
-pub async fn get_payment(
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SettlePaymentResponse {
+    pub id: Uuid,
+    pub status: String,
+    /// 0.1% of the amount, added to the BUS lock requirement now that the payment is settled
+    pub bus_lock_required_added: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/payments/{id}/settle",
+    tag = "payments",
+    params(("id" = Uuid, Path, description = "Payment id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Payment settled and its share added to the BUS lock requirement", body = SettlePaymentResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "No pending payment with this id"),
+        (status = 409, description = "Payment is still inside its currency's settlement hold; details carry earliest_settlement_at", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn settle_payment(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
+    State(events): State<TransactionEvents>,
+    State(payment_config): State<Arc<PaymentConfig>>,
+    State(list_cache): State<ListCache<TransactionPage>>,
+    Extension(principal): Extension<Principal>,
     Path(payment_id): Path<Uuid>,
-) -> Result<Json<PaymentResponse>, StatusCode> {
-    // Extract authenticated user_id
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+) -> Result<Json<SettlePaymentResponse>, ApiError> {
+    let user_id = principal.user_id;
 
-    // Fixed: Validate user ownership (was missing user_id check)
-    let result = sqlx::query!(
+    let settlement =
+        settle_pending_payment(&pool, &payment_config, user_id, payment_id, Some(user_id)).await?;
+
+    let settled = match settlement {
+        Settlement::Settled(settled) => settled,
+        Settlement::Held {
+            earliest_settlement_at,
+        } => {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                "settlement_hold",
+                format!(
+                    "payment is inside its settlement hold and can settle from {}",
+                    earliest_settlement_at
+                ),
+            )
+            .with_details(serde_json::json!({
+                "earliest_settlement_at": earliest_settlement_at.to_string(),
+            })))
+        }
+        Settlement::NotPending => return Err(StatusCode::NOT_FOUND.into()),
+    };
+
+    list_cache.invalidate_user(user_id);
+    events.publish(settled.event(user_id));
+
+    Ok(Json(SettlePaymentResponse {
+        id: settled.id,
+        status: settled.status,
+        bus_lock_required_added: settled.required_added.to_string(),
+    }))
+}
+
+/// A payment moved from pending to settled by [`settle_pending_payment`].
+pub(crate) struct SettledPayment {
+    pub id: Uuid,
+    pub amount: BigDecimal,
+    pub currency: String,
+    pub status: String,
+    /// BUS lock share added to the requirement
+    pub required_added: BigDecimal,
+    /// Recorded `webhook_events` row for the status change
+    pub event_id: Uuid,
+}
+
+impl SettledPayment {
+    pub fn event(&self, user_id: Uuid) -> TransactionEvent {
+        TransactionEvent {
+            event_id: self.event_id,
+            kind: TransactionEventKind::StatusChanged,
+            user_id,
+            transaction_id: self.id.to_string(),
+            status: self.status.clone(),
+            amount: self.amount.to_string(),
+            currency: self.currency.clone(),
+            occurred_at: chrono::Utc::now().naive_utc().to_string(),
+        }
+    }
+}
+
+/// Outcome of [`settle_pending_payment`].
+pub(crate) enum Settlement {
+    Settled(SettledPayment),
+    /// Still inside its currency's settlement hold; nothing was changed
+    Held {
+        earliest_settlement_at: NaiveDateTime,
+    },
+    /// The payment doesn't exist, isn't the user's, was soft-deleted, is no longer
+    /// pending, or has expired and is only waiting for the sweeper
+    NotPending,
+}
+
+/// Settles a pending payment and adds its share to the BUS lock requirement in one database transaction,
+/// provided it is older than the settlement hold for its currency.
+/// `actor_id` is who requested the settlement, or `None` when the system settled it.
+pub(crate) async fn settle_pending_payment(
+    pool: &PgPool,
+    payment_config: &PaymentConfig,
+    user_id: Uuid,
+    payment_id: Uuid,
+    actor_id: Option<Uuid>,
+) -> Result<Settlement, sqlx::Error> {
+    // Status change and requirement increase commit together; dropping `tx` on any
+    // early return rolls both back so the ledger and bus_locks can't drift.
+    let mut tx = pool.begin().await?;
+
+    let Some(pending) = sqlx::query!(
         r#"
-        SELECT id, amount, currency, status, customer_email, created_at
+        SELECT currency, created_at, NOW()::timestamp AS "now!"
         FROM transactions
-        WHERE id = $1 AND user_id = $2
+        WHERE id = $1 AND user_id = $2 AND status = 'pending' AND deleted_at IS NULL
+          AND (expires_at IS NULL OR expires_at > NOW())
+        FOR UPDATE
         "#,
         payment_id,
         user_id
     )
-    .fetch_one(&pool)
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(Settlement::NotPending);
+    };
+
+    let hold = payment_config.settlement_hold_for(&pending.currency);
+    if let Some(created_at) = pending.created_at.filter(|_| !hold.is_zero()) {
+        // An absurdly long configured hold saturates rather than overflowing
+        let earliest_settlement_at = chrono::Duration::from_std(hold)
+            .ok()
+            .and_then(|hold| created_at.checked_add_signed(hold))
+            .unwrap_or(NaiveDateTime::MAX);
+        if earliest_settlement_at > pending.now {
+            return Ok(Settlement::Held {
+                earliest_settlement_at,
+            });
+        }
+    }
+
+    let settled = sqlx::query!(
+        r#"
+        UPDATE transactions
+        SET status = 'settled', settled_at = NOW()
+        WHERE id = $1
+        RETURNING id, amount, currency, status
+        "#,
+        payment_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log (transaction_id, actor_user_id, action, old_status, new_status)
+        VALUES ($1, $2, 'status_changed', 'pending', $3)
+        "#,
+        settled.id,
+        actor_id,
+        settled.status
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // The share create_payment locked now counts towards the requirement too
+    let required_added = bus_lock_share(&settled.amount);
+
+    sqlx::query!(
+        r#"
+        WITH upserted AS (
+            INSERT INTO bus_locks (id, user_id, currency, locked_amount, required_amount, last_calculated_at, created_at, updated_at)
+            VALUES ($1, $2, $4, 0, $3, NOW(), NOW(), NOW())
+            ON CONFLICT (user_id, currency) DO UPDATE
+            SET required_amount = bus_locks.required_amount + $3,
+                version = bus_locks.version + 1,
+                last_calculated_at = NOW(), updated_at = NOW()
+            RETURNING user_id, currency, locked_amount, required_amount
+        )
+        INSERT INTO bus_lock_history (user_id, currency, locked_amount, required_amount, trigger)
+        SELECT user_id, currency, locked_amount, required_amount, 'settlement' FROM upserted
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        required_added,
+        settled.currency
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // Keyed on (transaction_id, status): a replayed settlement reuses the existing event
+    let event_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO webhook_events (user_id, transaction_id, event_type, status)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (transaction_id, status) DO UPDATE SET event_type = EXCLUDED.event_type
+        RETURNING id
+        "#,
+        user_id,
+        settled.id,
+        TransactionEventKind::StatusChanged.as_str(),
+        settled.status
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Settlement::Settled(SettledPayment {
+        id: settled.id,
+        amount: settled.amount,
+        currency: settled.currency,
+        status: settled.status,
+        required_added,
+        event_id,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/payments/{id}",
+    tag = "payments",
+    params(("id" = Uuid, Path, description = "Payment id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Payment details", body = PaymentResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Payment not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_payment(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    State(payment_config): State<Arc<PaymentConfig>>,
+    State(jwt): State<Arc<JwtConfig>>,
+    Extension(principal): Extension<Principal>,
+    Path(payment_id): Path<Uuid>,
+) -> Result<Json<PaymentResponse>, ApiError> {
+    load_payment(
+        &pool,
+        &retry,
+        &jwt,
+        &payment_config,
+        principal.user_id,
+        payment_id,
+    )
     .await
-    .map_err(|_| StatusCode::NOT_FOUND)?;
+    .map(Json)
+}
+
+/// Builds the response for one of `user_id`'s payments, with a fresh status token.
+async fn load_payment(
+    pool: &PgPool,
+    retry: &RetryPolicy,
+    jwt: &JwtConfig,
+    payment_config: &PaymentConfig,
+    user_id: Uuid,
+    payment_id: Uuid,
+) -> Result<PaymentResponse, ApiError> {
+    // Fixed: Validate user ownership (was missing user_id check)
+    let result = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT id, amount, currency, customer_email, fee_amount, net_amount, created_at, receipt_number,
+                       CASE WHEN status = 'pending' AND expires_at <= NOW() THEN 'failed'
+                            ELSE status END AS "status!"
+                FROM transactions
+                WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL
+                "#,
+                payment_id,
+                user_id
+            )
+            .fetch_one(pool)
+        })
+        .await?;
 
     let amount: f64 = result.amount.to_string().parse().unwrap_or(0.0);
 
-    // Fixed: Use 0.001 = 0.1% (was 0.01 = 1%)
-    Ok(Json(PaymentResponse {
+    Ok(PaymentResponse {
         id: result.id,
         amount,
         currency: result.currency,
         status: result.status,
         customer_email: result.customer_email.unwrap_or_default(),
         created_at: result.created_at.unwrap().to_string(),
-        bus_lock_required: amount * 0.001,
+        bus_lock_required: bus_lock_share(&result.amount).to_f64().unwrap_or_default(),
+        fee_amount: result.fee_amount.unwrap_or_default().to_string(),
+        net_amount: result.net_amount.unwrap_or_default().to_string(),
+        status_token: issue_status_token(jwt, result.id, payment_config.status_token_ttl)?,
+        receipt_number: result.receipt_number,
+        dry_run: false,
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PaymentStatusQuery {
+    /// `status_token` returned when the payment was created or fetched
+    pub token: Option<String>,
+}
+
+/// What a customer may see about a payment; no email, metadata or fees.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PaymentStatusView {
+    pub id: Uuid,
+    pub status: String,
+    pub amount: String,
+    pub currency: String,
+}
+
+/// Unauthenticated status check for customers, authorized by the payment's `status_token`.
+#[utoipa::path(
+    get,
+    path = "/api/payments/{id}/status",
+    tag = "payments",
+    params(("id" = Uuid, Path, description = "Payment id"), PaymentStatusQuery),
+    responses(
+        (status = 200, description = "Payment status", body = PaymentStatusView),
+        (status = 401, description = "Missing, invalid or expired token, or a token for another payment"),
+        (status = 404, description = "Payment not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_payment_status(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    State(jwt): State<Arc<JwtConfig>>,
+    Path(payment_id): Path<Uuid>,
+    Query(params): Query<PaymentStatusQuery>,
+) -> Result<Json<PaymentStatusView>, ApiError> {
+    let token = params.token.ok_or(StatusCode::UNAUTHORIZED)?;
+    if !verify_status_token(&jwt, &token, payment_id) {
+        return Err(StatusCode::UNAUTHORIZED.into());
+    }
+
+    let result = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT id, amount, currency,
+                       CASE WHEN status = 'pending' AND expires_at <= NOW() THEN 'failed'
+                            ELSE status END AS "status!"
+                FROM transactions
+                WHERE id = $1 AND tx_type = 'payment' AND deleted_at IS NULL
+                "#,
+                payment_id
+            )
+            .fetch_one(&pool)
+        })
+        .await?;
+
+    Ok(Json(PaymentStatusView {
+        id: result.id,
+        status: result.status,
+        amount: result.amount.to_string(),
+        currency: result.currency,
     }))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support;
+    use axum::response::IntoResponse;
+
+    fn field_errors(errors: &BTreeMap<&'static str, ApiError>) -> Vec<(&'static str, String)> {
+        errors
+            .iter()
+            .map(|(field, e)| (*field, e.message().to_string()))
+            .collect()
+    }
+
+    fn metadata_limits() -> PaymentConfig {
+        PaymentConfig {
+            max_metadata_bytes: 64,
+            max_metadata_depth: 3,
+            max_metadata_keys: 4,
+            ..PaymentConfig::from_env()
+        }
+    }
+
+    fn check_metadata(metadata: serde_json::Value) -> Result<(), (StatusCode, String)> {
+        validate_metadata(Some(&metadata), &metadata_limits()).map_err(|e| {
+            let code = e.code().to_string();
+            (e.into_response().status(), code)
+        })
+    }
+
+    #[test]
+    fn metadata_shape_counts_depth_and_nested_keys() {
+        use serde_json::json;
+        assert_eq!(metadata_shape(&json!("scalar")), (0, 0));
+        assert_eq!(metadata_shape(&json!({})), (1, 0));
+        assert_eq!(metadata_shape(&json!({"a": 1, "b": [1, 2]})), (2, 2));
+        assert_eq!(
+            metadata_shape(&json!({"a": {"b": {"c": 1}}, "d": 1})),
+            (3, 4)
+        );
+        assert_eq!(metadata_shape(&json!([[{"a": 1}]])), (3, 1));
+    }
+
+    #[test]
+    fn metadata_depth_is_allowed_up_to_the_limit() {
+        use serde_json::json;
+        assert_eq!(check_metadata(json!({"a": {"b": {"c": 1}}})), Ok(()));
+        assert_eq!(check_metadata(json!({"a": [[1]]})), Ok(()));
+        assert_eq!(
+            check_metadata(json!({"a": {"b": {"c": {}}}})),
+            Err((StatusCode::BAD_REQUEST, "metadata_too_deep".to_string()))
+        );
+        assert_eq!(
+            check_metadata(json!({"a": [[[1]]]})),
+            Err((StatusCode::BAD_REQUEST, "metadata_too_deep".to_string()))
+        );
+    }
+
+    #[test]
+    fn metadata_keys_are_counted_across_nesting_up_to_the_limit() {
+        use serde_json::json;
+        assert_eq!(
+            check_metadata(json!({"a": 1, "b": 2, "c": 3, "d": 4})),
+            Ok(())
+        );
+        assert_eq!(
+            check_metadata(json!({"a": {"b": 1, "c": 2}, "d": 3})),
+            Ok(())
+        );
+        assert_eq!(
+            check_metadata(json!({"a": 1, "b": 2, "c": 3, "d": 4, "e": 5})),
+            Err((
+                StatusCode::BAD_REQUEST,
+                "metadata_too_many_keys".to_string()
+            ))
+        );
+        assert_eq!(
+            check_metadata(json!({"a": {"b": 1, "c": 2}, "d": {"e": 3}})),
+            Err((
+                StatusCode::BAD_REQUEST,
+                "metadata_too_many_keys".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn metadata_size_is_allowed_up_to_the_limit_then_413() {
+        // `{"k":""}` is 8 bytes, so the value fills the rest of the 64
+        let at_limit = serde_json::json!({ "k": "x".repeat(56) });
+        assert_eq!(serde_json::to_vec(&at_limit).unwrap().len(), 64);
+        assert_eq!(check_metadata(at_limit), Ok(()));
+
+        let past_limit = serde_json::json!({ "k": "x".repeat(57) });
+        assert_eq!(
+            check_metadata(past_limit),
+            Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "metadata_too_large".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn metadata_must_be_an_object() {
+        assert_eq!(validate_metadata(None, &metadata_limits()).ok(), Some(()));
+        assert_eq!(
+            check_metadata(serde_json::json!(["a"])),
+            Err((StatusCode::BAD_REQUEST, "invalid_metadata".to_string()))
+        );
+    }
+
+    #[test]
+    fn fee_preview_validates_like_create_payment() {
+        let config = PaymentConfig::from_env();
+        let cases = [
+            (12.5, "usd"),
+            (12.5, " USD "),
+            (0.00012345, "btc"),
+            (-1.0, "USD"),
+            (0.0, "USD"),
+            (1.001, "USD"),
+            (f64::NAN, "USD"),
+            (10.0, "US"),
+            (10.0, "U5D"),
+            (1.5, "usdollars"),
+            (-3.0, "x"),
+        ];
+
+        for (amount, currency) in cases {
+            let create = validate_payment_request(
+                &CreatePaymentRequest {
+                    amount,
+                    currency: currency.to_string(),
+                    customer_email: "buyer@example.com".to_string(),
+                    metadata: None,
+                    tags: None,
+                },
+                &config,
+            );
+            let preview = validate_fee_preview(
+                &FeePreviewRequest {
+                    amount,
+                    currency: currency.to_string(),
+                    method: None,
+                },
+                &config,
+            );
+
+            match (create, preview) {
+                (Ok(payment), Ok((preview_amount, preview_currency))) => {
+                    assert_eq!(payment.amount, preview_amount, "{amount} {currency}");
+                    assert_eq!(payment.currency, preview_currency, "{amount} {currency}");
+                }
+                (Err(create), Err(preview)) => {
+                    assert_eq!(
+                        field_errors(&create),
+                        field_errors(&preview),
+                        "{amount} {currency}"
+                    );
+                }
+                (create, preview) => panic!(
+                    "{amount} {currency}: create ok={} but preview ok={}",
+                    create.is_ok(),
+                    preview.is_ok()
+                ),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn preview_fee_normalizes_currency_and_rejects_invalid_with_422() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let preview = |amount: f64, currency: &str| {
+            preview_fee(
+                State(pool.clone()),
+                State(Arc::new(FeeConfig::from_env())),
+                State(Arc::new(PaymentConfig::from_env())),
+                Extension(test_support::principal(user_id)),
+                Json(FeePreviewRequest {
+                    amount,
+                    currency: currency.to_string(),
+                    method: None,
+                }),
+            )
+        };
+
+        let Json(ok) = preview(100.0, " usd ").await.unwrap();
+        assert_eq!(ok.currency, "USD");
+
+        let rejected = preview(-5.0, "dollars").await.unwrap_err().into_response();
+        assert_eq!(rejected.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn concurrent_creates_keep_bus_lock_in_step_with_committed_payments() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let fee_config = Arc::new(FeeConfig::from_env());
+        let payment_config = Arc::new(PaymentConfig::from_env());
+        let server = Arc::new(ServerConfig::from_env());
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for i in 1..=8 {
+            let create = create_payment(
+                State(pool.clone()),
+                State(test_support::retry()),
+                State(fee_config.clone()),
+                State(payment_config.clone()),
+                State(Arc::new(JwtConfig::from_env())),
+                State(TransactionEvents::new()),
+                State(server.clone()),
+                State(ListCache::new(None)),
+                Extension(test_support::principal(user_id)),
+                Query(CreatePaymentQuery { dry_run: None }),
+                ClientInfo {
+                    ip: None,
+                    user_agent: None,
+                },
+                HeaderMap::new(),
+                MetadataJson(CreatePaymentRequest {
+                    amount: 100.37 * i as f64,
+                    currency: "USD".to_string(),
+                    customer_email: "buyer@example.com".to_string(),
+                    metadata: None,
+                    tags: None,
+                }),
+            );
+            tasks.spawn(async move { create.await.map(|(status, _, _)| status) });
+        }
+
+        let mut created = 0;
+        while let Some(outcome) = tasks.join_next().await {
+            match outcome.unwrap() {
+                Ok(status) => {
+                    assert_eq!(status, StatusCode::CREATED);
+                    created += 1;
+                }
+                // Lost every version race: must have left nothing behind
+                Err(e) => assert_eq!(e.code(), "bus_lock_contention"),
+            }
+        }
+        assert!(created > 0);
+
+        let payments: Vec<BigDecimal> =
+            sqlx::query_scalar("SELECT amount FROM transactions WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(payments.len(), created);
+        let expected: BigDecimal = payments.iter().map(bus_lock_share).sum();
+
+        let (locked, required): (BigDecimal, BigDecimal) = sqlx::query_as(
+            "SELECT locked_amount, required_amount FROM bus_locks WHERE user_id = $1 AND currency = 'USD'",
+        )
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(locked, expected);
+        // Nothing is settled yet, so nothing is required
+        assert_eq!(required, BigDecimal::zero());
+
+        let history: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM bus_lock_history WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(history, created as i64);
+    }
+
+    #[tokio::test]
+    async fn settlement_moves_the_locked_share_into_the_requirement() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let payment_config = PaymentConfig::from_env();
+        let id = test_support::insert_payment(&pool, user_id, "250.00", "EUR", "pending").await;
+        let share = bus_lock_share(&BigDecimal::from(250));
+
+        let mut tx = pool.begin().await.unwrap();
+        calculate_and_update_bus_lock(&mut tx, user_id, "EUR", &share)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+
+        let Settlement::Settled(settled) =
+            settle_pending_payment(&pool, &payment_config, user_id, id, Some(user_id))
+                .await
+                .unwrap()
+        else {
+            panic!("payment should settle");
+        };
+        assert_eq!(settled.required_added, share);
+
+        let (locked, required): (BigDecimal, BigDecimal) = sqlx::query_as(
+            "SELECT locked_amount, required_amount FROM bus_locks WHERE user_id = $1 AND currency = 'EUR'",
+        )
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(locked, share);
+        assert_eq!(required, share);
+    }
+
+    fn payment_request(amount: f64) -> CreatePaymentRequest {
+        CreatePaymentRequest {
+            amount,
+            currency: "USD".to_string(),
+            customer_email: "buyer@example.com".to_string(),
+            metadata: Some(serde_json::json!({ "order": "A-1" })),
+            tags: None,
+        }
+    }
+
+    async fn create(
+        pool: &PgPool,
+        user_id: Uuid,
+        idempotency_key: Option<&str>,
+        request: CreatePaymentRequest,
+    ) -> Result<(StatusCode, HeaderMap, Json<PaymentResponse>), ApiError> {
+        let mut headers = HeaderMap::new();
+        if let Some(key) = idempotency_key {
+            headers.insert(IDEMPOTENCY_KEY_HEADER, HeaderValue::from_str(key).unwrap());
+        }
+        create_payment(
+            State(pool.clone()),
+            State(test_support::retry()),
+            State(Arc::new(FeeConfig::from_env())),
+            State(Arc::new(PaymentConfig::from_env())),
+            State(Arc::new(JwtConfig::from_env())),
+            State(TransactionEvents::new()),
+            State(Arc::new(ServerConfig::from_env())),
+            State(ListCache::new(None)),
+            Extension(test_support::principal(user_id)),
+            Query(CreatePaymentQuery { dry_run: None }),
+            ClientInfo {
+                ip: None,
+                user_agent: None,
+            },
+            headers,
+            MetadataJson(request),
+        )
+        .await
+    }
+
+    async fn payment_count(pool: &PgPool, user_id: Uuid) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    fn location(headers: &HeaderMap) -> &str {
+        headers.get(header::LOCATION).unwrap().to_str().unwrap()
+    }
+
+    #[test]
+    fn idempotency_key_must_be_short_visible_ascii() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                IDEMPOTENCY_KEY_HEADER,
+                HeaderValue::from_str(value).unwrap(),
+            );
+            headers
+        };
+
+        assert_eq!(idempotency_key(&HeaderMap::new()).unwrap(), None);
+        assert_eq!(
+            idempotency_key(&headers(" order-17 ")).unwrap().as_deref(),
+            Some("order-17")
+        );
+        assert_eq!(
+            idempotency_key(&headers(&"k".repeat(255)))
+                .unwrap()
+                .map(|k| k.len()),
+            Some(255)
+        );
+        assert!(idempotency_key(&headers(&"k".repeat(256))).is_err());
+        assert!(idempotency_key(&headers("   ")).is_err());
+    }
+
+    #[test]
+    fn request_hash_ignores_metadata_key_order() {
+        let mut reordered = payment_request(10.0);
+        reordered.metadata = Some(serde_json::from_str(r#"{"b":1,"a":2}"#).unwrap());
+        let mut original = payment_request(10.0);
+        original.metadata = Some(serde_json::from_str(r#"{"a":2,"b":1}"#).unwrap());
+
+        assert_eq!(request_hash(&original), request_hash(&reordered));
+        assert_ne!(
+            request_hash(&original),
+            request_hash(&payment_request(11.0))
+        );
+    }
+
+    #[tokio::test]
+    async fn concurrent_creates_get_distinct_gapless_receipt_numbers() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for i in 1..=10 {
+            let pool = pool.clone();
+            tasks.spawn(async move {
+                create(&pool, user_id, None, payment_request(10.0 + i as f64))
+                    .await
+                    .map(|(_, _, Json(payment))| payment.receipt_number.unwrap())
+            });
+        }
+        let mut numbers = Vec::new();
+        while let Some(outcome) = tasks.join_next().await {
+            match outcome.unwrap() {
+                Ok(number) => numbers.push(number),
+                // A create that lost its lock races rolled its number back too
+                Err(e) => assert_eq!(e.code(), "bus_lock_contention"),
+            }
+        }
+        assert!(!numbers.is_empty());
+
+        numbers.sort();
+        let expected: Vec<_> = (1..=numbers.len())
+            .map(|n| format!("INV-{:06}", n))
+            .collect();
+        assert_eq!(numbers, expected);
+    }
+
+    #[tokio::test]
+    async fn create_returns_201_with_location() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+
+        let (status, headers, Json(payment)) = create(&pool, user_id, None, payment_request(10.0))
+            .await
+            .unwrap();
+
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(location(&headers).ends_with(&format!("/api/payments/{}", payment.id)));
+    }
+
+    #[tokio::test]
+    async fn idempotent_replay_returns_200_with_the_original_payment() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+
+        let (status, headers, Json(created)) =
+            create(&pool, user_id, Some("order-17"), payment_request(10.0))
+                .await
+                .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, replay_headers, Json(replayed)) =
+            create(&pool, user_id, Some("order-17"), payment_request(10.0))
+                .await
+                .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(replayed.id, created.id);
+        assert_eq!(replayed.receipt_number, created.receipt_number);
+        assert_eq!(location(&replay_headers), location(&headers));
+        assert_eq!(payment_count(&pool, user_id).await, 1);
+
+        let reused = create(&pool, user_id, Some("order-17"), payment_request(99.0))
+            .await
+            .unwrap_err();
+        assert_eq!(reused.code(), "idempotency_key_reused");
+        assert_eq!(payment_count(&pool, user_id).await, 1);
+
+        // Keys are per merchant
+        let other = test_support::create_user(&pool).await;
+        let (status, _, _) = create(&pool, other, Some("order-17"), payment_request(10.0))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_with_one_key_create_one_payment() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..4 {
+            let pool = pool.clone();
+            tasks.spawn(async move {
+                let (status, _, Json(payment)) =
+                    create(&pool, user_id, Some("same-key"), payment_request(10.0))
+                        .await
+                        .unwrap();
+                (status, payment.id)
+            });
+        }
+
+        let mut outcomes = Vec::new();
+        while let Some(outcome) = tasks.join_next().await {
+            outcomes.push(outcome.unwrap());
+        }
+
+        let created = outcomes
+            .iter()
+            .filter(|(status, _)| *status == StatusCode::CREATED)
+            .count();
+        assert_eq!(created, 1);
+        assert!(outcomes.iter().all(|(_, id)| *id == outcomes[0].1));
+        assert_eq!(payment_count(&pool, user_id).await, 1);
+    }
+
+    #[tokio::test]
+    async fn settle_reports_an_exhausted_pool_as_503() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let id = test_support::insert_payment(&pool, user_id, "10.00", "USD", "pending").await;
+
+        let url = std::env::var("TEST_DATABASE_URL").unwrap();
+        let tiny = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(1)
+            .acquire_timeout(Duration::from_millis(100))
+            .connect(&url)
+            .await
+            .unwrap();
+        let _held = tiny.acquire().await.unwrap();
+
+        let err = settle_payment(
+            State(tiny.clone()),
+            State(TransactionEvents::new()),
+            State(Arc::new(PaymentConfig::from_env())),
+            State(ListCache::new(None)),
+            Extension(test_support::principal(user_id)),
+            Path(id),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.code(), "database_unavailable");
+        assert_eq!(
+            err.into_response().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    /// Makes inserts into `table` raise for rows matching `condition` (a PL/pgSQL
+    /// expression over `NEW`); returns the SQL that undoes it.
+    async fn fail_inserts_into(pool: &PgPool, table: &str, condition: &str) -> String {
+        let name = format!("fail_{}", Uuid::new_v4().simple());
+        sqlx::raw_sql(&format!(
+            "CREATE FUNCTION {name}() RETURNS trigger AS $$
+             BEGIN
+                 IF {condition} THEN
+                     RAISE EXCEPTION 'injected failure';
+                 END IF;
+                 RETURN NEW;
+             END $$ LANGUAGE plpgsql;
+             CREATE TRIGGER {name} BEFORE INSERT ON {table}
+                 FOR EACH ROW EXECUTE FUNCTION {name}();"
+        ))
+        .execute(pool)
+        .await
+        .unwrap();
+        format!("DROP TRIGGER {name} ON {table}; DROP FUNCTION {name}();")
+    }
+
+    #[tokio::test]
+    async fn failed_settlement_rolls_back_every_write() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+
+        // First and last writes after the status change
+        for table in ["audit_log", "webhook_events"] {
+            let user_id = test_support::create_user(&pool).await;
+            let id = test_support::insert_payment(&pool, user_id, "80.00", "USD", "pending").await;
+            let cleanup =
+                fail_inserts_into(&pool, table, &format!("NEW.transaction_id = '{id}'")).await;
+
+            let result = settle_payment(
+                State(pool.clone()),
+                State(TransactionEvents::new()),
+                State(Arc::new(PaymentConfig::from_env())),
+                State(ListCache::new(None)),
+                Extension(test_support::principal(user_id)),
+                Path(id),
+            )
+            .await;
+            sqlx::raw_sql(&cleanup).execute(&pool).await.unwrap();
+
+            let Err(err) = result else {
+                panic!("settlement should fail when {table} rejects the insert");
+            };
+            assert_eq!(
+                err.into_response().status(),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "{table}"
+            );
+
+            let (status, settled_at): (String, Option<NaiveDateTime>) =
+                sqlx::query_as("SELECT status, settled_at FROM transactions WHERE id = $1")
+                    .bind(id)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+            assert_eq!(status, "pending", "{table}");
+            assert_eq!(settled_at, None, "{table}");
+
+            let written: (i64, i64, i64, i64) = sqlx::query_as(
+                "SELECT (SELECT COUNT(*) FROM bus_locks WHERE user_id = $1),
+                        (SELECT COUNT(*) FROM bus_lock_history WHERE user_id = $1),
+                        (SELECT COUNT(*) FROM audit_log WHERE transaction_id = $2),
+                        (SELECT COUNT(*) FROM webhook_events WHERE transaction_id = $2)",
+            )
+            .bind(user_id)
+            .bind(id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+            assert_eq!(written, (0, 0, 0, 0), "{table}");
+        }
+    }
+
+    async fn create_batch(
+        pool: &PgPool,
+        user_id: Uuid,
+        payments: Vec<CreatePaymentRequest>,
+    ) -> Result<Json<BatchPaymentResponse>, ApiError> {
+        create_payment_batch(
+            State(pool.clone()),
+            State(Arc::new(FeeConfig::from_env())),
+            State(Arc::new(PaymentConfig::from_env())),
+            State(Arc::new(JwtConfig::from_env())),
+            State(TransactionEvents::new()),
+            State(ListCache::new(None)),
+            Extension(test_support::principal(user_id)),
+            ClientInfo {
+                ip: None,
+                user_agent: None,
+            },
+            MetadataJson(BatchPaymentRequest { payments }),
+        )
+        .await
+    }
+
+    async fn locked_amount(pool: &PgPool, user_id: Uuid) -> Option<BigDecimal> {
+        sqlx::query_scalar("SELECT locked_amount FROM bus_locks WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn batch_creates_every_payment_in_request_order() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let amounts = [30.0, 10.0, 20.0];
+
+        let Json(batch) = create_batch(
+            &pool,
+            user_id,
+            amounts.into_iter().map(payment_request).collect(),
+        )
+        .await
+        .unwrap();
+
+        let returned: Vec<f64> = batch.payments.iter().map(|p| p.amount).collect();
+        assert_eq!(returned, amounts);
+        assert!(batch.payments.iter().all(|p| p.status == "pending"));
+        assert_eq!(payment_count(&pool, user_id).await, 3);
+        assert_eq!(
+            locked_amount(&pool, user_id).await,
+            Some(bus_lock_share(&BigDecimal::from(60)))
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_with_an_invalid_item_creates_nothing() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let mut payments = vec![payment_request(10.0), payment_request(-5.0)];
+        payments.push(CreatePaymentRequest {
+            currency: "DOLLARS".to_string(),
+            ..payment_request(20.0)
+        });
+
+        let err = create_batch(&pool, user_id, payments).await.unwrap_err();
+
+        assert_eq!(err.code(), "invalid_batch");
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let offending: Vec<(u64, &str)> = body["error"]["details"]["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["index"].as_u64().unwrap(), e["field"].as_str().unwrap()))
+            .collect();
+        assert_eq!(offending, [(1, "amount"), (2, "currency")]);
+        assert_eq!(payment_count(&pool, user_id).await, 0);
+    }
+
+    #[tokio::test]
+    async fn batch_failing_midway_rolls_back_earlier_items() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let cleanup = fail_inserts_into(
+            &pool,
+            "transactions",
+            &format!("NEW.user_id = '{user_id}' AND NEW.amount = 13.13"),
+        )
+        .await;
+
+        let result = create_batch(
+            &pool,
+            user_id,
+            vec![payment_request(10.0), payment_request(13.13)],
+        )
+        .await;
+        sqlx::raw_sql(&cleanup).execute(&pool).await.unwrap();
+
+        let Err(err) = result else {
+            panic!("batch should fail on the second item");
+        };
+        assert_eq!(
+            err.into_response().status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(payment_count(&pool, user_id).await, 0);
+        assert_eq!(locked_amount(&pool, user_id).await, None);
+    }
+
+    #[test]
+    fn email_is_trimmed_and_lowercased() {
+        assert_eq!(
+            normalize_email("buyer@example.com").unwrap(),
+            "buyer@example.com"
+        );
+        assert_eq!(normalize_email("Foo@Bar.COM").unwrap(), "foo@bar.com");
+        assert_eq!(
+            normalize_email("  Mixed.Case+tag@Shop.IO ").unwrap(),
+            "mixed.case+tag@shop.io"
+        );
+    }
+
+    #[test]
+    fn email_rejects_anything_but_a_bare_address() {
+        for invalid in [
+            "",
+            "not-an-email",
+            "@example.com",
+            "buyer@",
+            "buyer@localhost",
+            "Buyer <buyer@example.com>",
+            "buyer@[127.0.0.1]",
+            "two@@example.com",
+        ] {
+            assert_eq!(
+                normalize_email(invalid).unwrap_err().code(),
+                "invalid_email",
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn create_stores_the_normalized_email() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+
+        let (_, _, Json(payment)) = create(
+            &pool,
+            user_id,
+            None,
+            CreatePaymentRequest {
+                customer_email: " Foo@Bar.COM ".to_string(),
+                ..payment_request(10.0)
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(payment.customer_email, "foo@bar.com");
+        let stored: String =
+            sqlx::query_scalar("SELECT customer_email FROM transactions WHERE id = $1")
+                .bind(payment.id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(stored, "foo@bar.com");
+
+        let err = create(
+            &pool,
+            user_id,
+            None,
+            CreatePaymentRequest {
+                customer_email: "not-an-email".to_string(),
+                ..payment_request(10.0)
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.code(), "validation_failed");
+        assert_eq!(payment_count(&pool, user_id).await, 1);
+    }
+
+    #[test]
+    fn status_token_is_bound_to_its_payment_and_expires() {
+        let jwt = JwtConfig::from_env();
+        let payment_id = Uuid::new_v4();
+        let token = issue_status_token(&jwt, payment_id, Duration::from_secs(60)).unwrap();
+
+        assert!(verify_status_token(&jwt, &token, payment_id));
+        assert!(!verify_status_token(&jwt, &token, Uuid::new_v4()));
+
+        let expired = encode(
+            &Header::default(),
+            &StatusTokenClaims {
+                sub: payment_id.to_string(),
+                exp: Utc::now().timestamp() - 1,
+                iss: jwt.issuer.clone(),
+                aud: STATUS_TOKEN_AUDIENCE.to_string(),
+            },
+            &EncodingKey::from_secret(jwt.secret.as_bytes()),
+        )
+        .unwrap();
+        assert!(!verify_status_token(&jwt, &expired, payment_id));
+
+        // A session token for the same subject is for a different audience
+        let session =
+            crate::handlers::auth::generate_jwt(&jwt, &payment_id.to_string(), "buyer@example.com")
+                .unwrap();
+        assert!(!verify_status_token(&jwt, &session, payment_id));
+    }
+
+    #[tokio::test]
+    async fn status_endpoint_needs_the_payments_own_token() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let (_, _, Json(first)) = create(&pool, user_id, None, payment_request(10.0))
+            .await
+            .unwrap();
+        let (_, _, Json(second)) = create(&pool, user_id, None, payment_request(20.0))
+            .await
+            .unwrap();
+        let status = |id: Uuid, token: Option<&str>| {
+            get_payment_status(
+                State(pool.clone()),
+                State(test_support::retry()),
+                State(Arc::new(JwtConfig::from_env())),
+                Path(id),
+                Query(PaymentStatusQuery {
+                    token: token.map(str::to_string),
+                }),
+            )
+        };
+
+        let Json(view) = status(first.id, Some(&first.status_token)).await.unwrap();
+        assert_eq!(
+            serde_json::to_value(&view).unwrap(),
+            serde_json::json!({
+                "id": first.id,
+                "status": "pending",
+                "amount": "10",
+                "currency": "USD",
+            })
+        );
+
+        for token in [Some(second.status_token.as_str()), Some("garbage"), None] {
+            let err = status(first.id, token).await.unwrap_err();
+            assert_eq!(err.code(), "unauthorized", "{:?}", token);
+        }
+    }
+
+    #[tokio::test]
+    async fn create_reports_every_invalid_field_in_one_422() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let request = CreatePaymentRequest {
+            amount: -5.0,
+            currency: "US".to_string(),
+            customer_email: "not-an-email".to_string(),
+            metadata: None,
+            tags: None,
+        };
+
+        let response = create(&pool, user_id, None, request)
+            .await
+            .unwrap_err()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["error"]["code"], "validation_failed");
+        let errors = body["error"]["details"]["errors"].as_object().unwrap();
+        let mut fields: Vec<&str> = errors.keys().map(String::as_str).collect();
+        fields.sort();
+        assert_eq!(fields, ["amount", "currency", "customer_email"]);
+        assert!(errors
+            .values()
+            .all(|message| message.as_str().is_some_and(|m| !m.is_empty())));
+        assert_eq!(payment_count(&pool, user_id).await, 0);
+    }
+}
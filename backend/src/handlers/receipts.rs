@@ -0,0 +1,153 @@
+use crate::config::ReceiptConfig;
+use crate::db::RetryPolicy;
+use crate::error::{ApiError, ErrorBody};
+use crate::extract::Path;
+use crate::middleware::auth::Principal;
+use crate::receipts;
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Proof-of-payment fields covered by the signature.
+#[derive(Serialize, ToSchema)]
+pub struct Receipt {
+    pub transaction_id: String,
+    pub merchant_id: String,
+    pub tx_type: String,
+    pub amount: String,
+    pub currency: String,
+    pub status: String,
+    pub customer_email: Option<String>,
+    /// RFC 3339 UTC, like `issued_at`
+    pub created_at: String,
+    pub issued_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SignedReceipt {
+    #[schema(value_type = Receipt)]
+    pub receipt: serde_json::Value,
+    /// Base64 Ed25519 signature over the canonical (sorted-key, compact) JSON of `receipt`
+    pub signature: String,
+    pub algorithm: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReceiptPublicKey {
+    pub algorithm: String,
+    /// Base64-encoded Ed25519 public key
+    pub public_key: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyReceiptRequest {
+    #[schema(value_type = Object)]
+    pub receipt: serde_json::Value,
+    pub signature: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VerifyReceiptResponse {
+    pub valid: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/transactions/{id}/receipt.json",
+    tag = "receipts",
+    params(("id" = Uuid, Path, description = "Transaction id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Signed receipt", body = SignedReceipt),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Transaction not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_transaction_receipt(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    State(receipt_config): State<Arc<ReceiptConfig>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<SignedReceipt>, ApiError> {
+    let user_id = principal.user_id;
+
+    let result = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT id, tx_type, amount, currency, status, customer_email, created_at
+                FROM transactions
+                WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL
+                "#,
+                id,
+                user_id
+            )
+            .fetch_one(&pool)
+        })
+        .await?;
+
+    let receipt = Receipt {
+        transaction_id: result.id.to_string(),
+        merchant_id: user_id.to_string(),
+        tx_type: result.tx_type,
+        amount: result.amount.to_string(),
+        currency: result.currency,
+        status: result.status,
+        customer_email: result.customer_email,
+        created_at: result
+            .created_at
+            .unwrap_or_default()
+            .and_utc()
+            .to_rfc3339_opts(SecondsFormat::Micros, true),
+        issued_at: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+    };
+    let receipt = serde_json::to_value(receipt).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let signature = receipts::sign(&receipt_config.signing_key, &receipt);
+
+    Ok(Json(SignedReceipt {
+        receipt,
+        signature,
+        algorithm: receipts::ALGORITHM.to_string(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/receipts/public-key",
+    tag = "receipts",
+    responses((status = 200, description = "Key for verifying receipt signatures", body = ReceiptPublicKey))
+)]
+pub async fn get_public_key(
+    State(receipt_config): State<Arc<ReceiptConfig>>,
+) -> Json<ReceiptPublicKey> {
+    Json(ReceiptPublicKey {
+        algorithm: receipts::ALGORITHM.to_string(),
+        public_key: receipts::encode_public_key(&receipt_config.signing_key.verifying_key()),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/receipts/verify",
+    tag = "receipts",
+    request_body = VerifyReceiptRequest,
+    responses((status = 200, description = "Whether the signature matches the receipt", body = VerifyReceiptResponse))
+)]
+pub async fn verify_receipt(
+    State(receipt_config): State<Arc<ReceiptConfig>>,
+    Json(payload): Json<VerifyReceiptRequest>,
+) -> Json<VerifyReceiptResponse> {
+    let valid = receipts::verify(
+        &receipt_config.signing_key.verifying_key(),
+        &payload.receipt,
+        &payload.signature,
+    );
+
+    Json(VerifyReceiptResponse { valid })
+}
@@ -1,10 +1,11 @@
-use crate::handlers::auth::Claims;
+use crate::db::RetryPolicy;
+use crate::middleware::auth::Principal;
 use axum::{extract::State, http::StatusCode, Extension, Json};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use uuid::Uuid;
+use utoipa::ToSchema;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct UserSettings {
     pub company_name: String,
     pub email: String,
@@ -13,36 +14,51 @@ pub struct UserSettings {
     pub kyc_status: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct UpdateSettingsRequest {
     pub company_name: String,
     pub email: String,
     pub website: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct UpdateSettingsResponse {
     pub message: String,
     pub updated_fields: Vec<String>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/user/settings",
+    tag = "settings",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Account settings", body = UserSettings),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error")
+    )
+)]
 pub async fn get_settings(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
 ) -> Result<Json<UserSettings>, StatusCode> {
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let user_id = principal.user_id;
 
-    let user = sqlx::query!(
-        r#"
-        SELECT email, company_name, website, registration_number, kyc_status
-        FROM users
-        WHERE id = $1
-        "#,
-        user_id
-    )
-    .fetch_one(&pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let user = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT email, company_name, website, registration_number, kyc_status
+                FROM users
+                WHERE id = $1
+                "#,
+                user_id
+            )
+            .fetch_one(&pool)
+        })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(Json(UserSettings {
         company_name: user.company_name.unwrap_or_default(),
@@ -53,12 +69,24 @@ pub async fn get_settings(
     }))
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/user/settings",
+    tag = "settings",
+    request_body = UpdateSettingsRequest,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Settings updated", body = UpdateSettingsResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error")
+    )
+)]
 pub async fn update_settings(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
+    Extension(principal): Extension<Principal>,
     Json(payload): Json<UpdateSettingsRequest>,
 ) -> Result<Json<UpdateSettingsResponse>, StatusCode> {
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let user_id = principal.user_id;
 
     sqlx::query!(
         r#"
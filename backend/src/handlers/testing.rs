@@ -0,0 +1,139 @@
+//! Database seeding and reset for integration tests. Only compiled with the
+//! `test-endpoints` feature, which `main.rs` refuses to build in release mode.
+
+use crate::cache::ListCache;
+use crate::error::ApiError;
+use crate::handlers::transactions::TransactionPage;
+use crate::state::AppState;
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use bigdecimal::BigDecimal;
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+/// Largest seed request; keeps the single INSERT well under Postgres' bind limit.
+const MAX_SEED_TRANSACTIONS: usize = 1000;
+
+/// Every application table, truncated together so foreign keys never block the reset.
+const APP_TABLES: &[&str] = &[
+    "api_keys",
+    "audit_log",
+    "bus_lock_history",
+    "bus_locks",
+    "bus_price_cache",
+    "disputes",
+    "receipt_sequences",
+    "revoked_tokens",
+    "transactions",
+    "treasury_positions",
+    "usage_counters",
+    "users",
+    "webhook_events",
+    "webhook_secrets",
+];
+
+/// Mounts `/test/*`. Panics when `APP_ENV=production` so a debug build with the
+/// feature still can't serve these routes from a production deployment.
+pub fn routes() -> Router<AppState> {
+    assert!(
+        !std::env::var("APP_ENV").is_ok_and(|v| v.eq_ignore_ascii_case("production")),
+        "the test-endpoints feature must not be enabled with APP_ENV=production"
+    );
+    tracing::warn!(
+        "test endpoints enabled: POST /test/seed and POST /test/reset are unauthenticated"
+    );
+
+    Router::new()
+        .route("/test/seed", post(seed))
+        .route("/test/reset", post(reset))
+}
+
+#[derive(Deserialize)]
+pub struct SeedRequest {
+    pub user_id: Uuid,
+    pub transactions: Vec<SeedTransaction>,
+}
+
+/// A transaction row as stored; omitted fields take the defaults of a settled payment.
+#[derive(Deserialize)]
+pub struct SeedTransaction {
+    pub id: Option<Uuid>,
+    pub tx_type: Option<String>,
+    pub amount: BigDecimal,
+    pub currency: String,
+    pub status: Option<String>,
+    pub customer_email: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub parent_transaction_id: Option<Uuid>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize)]
+pub struct SeedResponse {
+    pub ids: Vec<Uuid>,
+}
+
+/// Inserts the given transactions for `user_id` in one statement, bypassing payment
+/// validation, fees and BUS locks. The database constraints still apply.
+async fn seed(
+    State(pool): State<PgPool>,
+    State(list_cache): State<ListCache<TransactionPage>>,
+    Json(payload): Json<SeedRequest>,
+) -> Result<(StatusCode, Json<SeedResponse>), ApiError> {
+    if payload.transactions.is_empty() || payload.transactions.len() > MAX_SEED_TRANSACTIONS {
+        return Err(ApiError::bad_request(
+            "invalid_seed_size",
+            format!(
+                "seed must contain 1 to {} transactions",
+                MAX_SEED_TRANSACTIONS
+            ),
+        ));
+    }
+
+    let now = Utc::now().naive_utc();
+    let mut query = QueryBuilder::<Postgres>::new(
+        "INSERT INTO transactions (id, user_id, tx_type, amount, currency, status, customer_email, \
+         metadata, fee_amount, net_amount, tags, parent_transaction_id, created_at, settled_at) ",
+    );
+    query.push_values(payload.transactions, |mut row, tx| {
+        let status = tx.status.unwrap_or_else(|| "settled".to_string());
+        let created_at = tx.created_at.unwrap_or(now);
+        let settled_at = (status == "settled").then_some(created_at);
+        row.push_bind(tx.id.unwrap_or_else(Uuid::new_v4))
+            .push_bind(payload.user_id)
+            .push_bind(tx.tx_type.unwrap_or_else(|| "payment".to_string()))
+            .push_bind(tx.amount.clone())
+            .push_bind(tx.currency)
+            .push_bind(status)
+            .push_bind(tx.customer_email)
+            .push_bind(tx.metadata)
+            .push_bind(BigDecimal::from(0))
+            .push_bind(tx.amount)
+            .push_bind(tx.tags)
+            .push_bind(tx.parent_transaction_id)
+            .push_bind(created_at)
+            .push_bind(settled_at);
+    });
+    query.push(" RETURNING id");
+
+    let ids = query.build_query_scalar().fetch_all(&pool).await?;
+    list_cache.invalidate_user(payload.user_id);
+
+    Ok((StatusCode::CREATED, Json(SeedResponse { ids })))
+}
+
+/// Empties every application table, users included.
+async fn reset(
+    State(pool): State<PgPool>,
+    State(list_cache): State<ListCache<TransactionPage>>,
+) -> Result<StatusCode, ApiError> {
+    sqlx::query(&format!("TRUNCATE {} CASCADE", APP_TABLES.join(", ")))
+        .execute(&pool)
+        .await?;
+    list_cache.clear();
+
+    Ok(StatusCode::NO_CONTENT)
+}
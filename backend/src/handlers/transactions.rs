@@ -1,201 +1,2269 @@
-use crate::handlers::auth::Claims;
+use crate::cache::ListCache;
+use crate::config::{PaginationConfig, PaymentConfig, ServerConfig};
+use crate::currency;
+use crate::db::RetryPolicy;
+use crate::error::{ApiError, ErrorBody};
+use crate::events::{TransactionEvent, TransactionEvents};
+use crate::extract::Path;
+use crate::handlers::payments::validate_metadata;
+use crate::middleware::auth::Principal;
+use crate::middleware::metrics::Metrics;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
+    body::Body,
+    extract::{Query, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Extension, Json,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream, ReceiverStream};
+use tokio_stream::{Stream, StreamExt};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct TransactionQuery {
+    /// Case-insensitive match on customer email, status or `metadata.reference`, or an id prefix
     pub search: Option<String>,
+    /// Status filter: `pending`, `settled` or `failed`
     pub filter: Option<String>,
+    /// Page number for offset pagination (default 1)
     pub page: Option<i32>,
+    /// Page size, 1 to `MAX_PAGE_SIZE` (default `DEFAULT_PAGE_SIZE`); out-of-range values are rejected
     pub limit: Option<i32>,
+    /// Comma-separated expansions; supports `disputes`
+    pub include: Option<String>,
+    /// Inclusive lower bound on amount, as a decimal string
+    pub min_amount: Option<String>,
+    /// Inclusive upper bound on amount, as a decimal string
+    pub max_amount: Option<String>,
+    /// Opaque cursor from `next_cursor`; pass empty to start cursor pagination
+    pub cursor: Option<String>,
+    /// Include archived transactions (default false)
+    pub include_archived: Option<bool>,
+    /// Include soft-deleted transactions (default false); admins only
+    pub include_deleted: Option<bool>,
+    /// Top-level metadata key to match; requires `metadata_value`
+    pub metadata_key: Option<String>,
+    /// Value `metadata_key` must equal; only top-level string values are supported
+    pub metadata_value: Option<String>,
+    /// Top-level metadata key that must be present, whatever its value
+    pub metadata_has: Option<String>,
+    /// JSON-encoded object the metadata must contain (JSONB `@>`), e.g. `{"tags":["vip"]}`
+    pub metadata_contains: Option<String>,
+    /// `true` for transactions with a customer email, `false` for anonymous ones
+    pub has_email: Option<bool>,
+    /// Earliest creation date to include (`YYYY-MM-DD`, UTC)
+    pub from: Option<String>,
+    /// Latest creation date to include, inclusive (`YYYY-MM-DD`, UTC)
+    pub to: Option<String>,
+    /// Only transactions carrying this tag
+    pub tag: Option<String>,
+    /// `display` adds `amount_formatted` to each transaction
+    pub format: Option<String>,
 }
 
-#[derive(Serialize)]
+/// Filters shared by the page query and the count query.
+struct ListFilters {
+    /// LIKE-escaped search term
+    search: Option<String>,
+    status: Option<String>,
+    min_amount: Option<BigDecimal>,
+    max_amount: Option<BigDecimal>,
+    include_archived: bool,
+    include_deleted: bool,
+    metadata: Option<(String, String)>,
+    metadata_has: Option<String>,
+    metadata_contains: Option<serde_json::Value>,
+    has_email: Option<bool>,
+    created_from: Option<NaiveDateTime>,
+    created_before: Option<NaiveDateTime>,
+    tag: Option<String>,
+}
+
+impl ListFilters {
+    /// Validates the filter params shared by listing and export.
+    fn from_query(params: &TransactionQuery, principal: &Principal) -> Result<Self, ApiError> {
+        // Escaped so `%` and `_` in the term match literally
+        let search = params
+            .search
+            .as_deref()
+            .filter(|s| !s.is_empty())
+            .map(escape_like);
+
+        // Status whitelist (prevent invalid status injection)
+        let status = params
+            .filter
+            .as_deref()
+            .filter(|f| matches!(*f, "pending" | "settled" | "failed"))
+            .map(str::to_string);
+
+        // Amount range (inclusive bounds)
+        let min_amount = parse_amount_param("min_amount", params.min_amount.as_deref())?;
+        let max_amount = parse_amount_param("max_amount", params.max_amount.as_deref())?;
+        if let (Some(min), Some(max)) = (&min_amount, &max_amount) {
+            if min > max {
+                return Err(ApiError::bad_request(
+                    "invalid_amount_range",
+                    "min_amount must not be greater than max_amount",
+                ));
+            }
+        }
+
+        // Metadata match needs both halves of the pair
+        let metadata =
+            match (&params.metadata_key, &params.metadata_value) {
+                (Some(key), Some(value)) if !key.is_empty() => Some((key.clone(), value.clone())),
+                (None, None) => None,
+                _ => return Err(ApiError::bad_request(
+                    "invalid_metadata_filter",
+                    "metadata_key and metadata_value must be given together, with a non-empty key",
+                )),
+            };
+
+        let metadata_has = match params.metadata_has.as_deref() {
+            Some("") => {
+                return Err(ApiError::bad_request(
+                    "invalid_metadata_filter",
+                    "metadata_has must not be empty",
+                ))
+            }
+            key => key.map(str::to_string),
+        };
+
+        // Objects only: containment of a bare scalar or array isn't a useful metadata filter
+        let metadata_contains = match params.metadata_contains.as_deref() {
+            Some(raw) => match serde_json::from_str(raw) {
+                Ok(object @ serde_json::Value::Object(_)) => Some(object),
+                _ => {
+                    return Err(ApiError::bad_request(
+                        "invalid_metadata_filter",
+                        "metadata_contains must be a JSON object",
+                    ))
+                }
+            },
+            None => None,
+        };
+
+        let include_deleted = params.include_deleted.unwrap_or(false);
+        if include_deleted && !principal.is_admin() {
+            return Err(StatusCode::FORBIDDEN.into());
+        }
+
+        // Date range: whole UTC days, `to` inclusive
+        let created_from =
+            parse_date_param("from", params.from.as_deref())?.map(|d| d.and_time(NaiveTime::MIN));
+        let created_before = parse_date_param("to", params.to.as_deref())?
+            .map(|d| (d + Days::new(1)).and_time(NaiveTime::MIN));
+        if let (Some(from), Some(before)) = (&created_from, &created_before) {
+            if from >= before {
+                return Err(invalid_date_range());
+            }
+        }
+
+        Ok(Self {
+            search,
+            status,
+            min_amount,
+            max_amount,
+            include_archived: params.include_archived.unwrap_or(false),
+            include_deleted,
+            metadata,
+            metadata_has,
+            metadata_contains,
+            has_email: params.has_email,
+            created_from,
+            created_before,
+            tag: params
+                .tag
+                .as_deref()
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string),
+        })
+    }
+
+    fn push_where(&self, query: &mut QueryBuilder<'_, Postgres>, user_id: Uuid) {
+        query.push(" WHERE t.user_id = ").push_bind(user_id);
+
+        if !self.include_archived {
+            query.push(" AND t.archived_at IS NULL");
+        }
+
+        if !self.include_deleted {
+            query.push(" AND t.deleted_at IS NULL");
+        }
+
+        if let Some(term) = &self.search {
+            let contains = format!("%{}%", term);
+            query
+                .push(" AND (t.customer_email ILIKE ")
+                .push_bind(contains.clone())
+                .push(" OR t.status ILIKE ")
+                .push_bind(contains.clone())
+                .push(" OR t.metadata ->> 'reference' ILIKE ")
+                .push_bind(contains)
+                .push(" OR t.id::text LIKE ")
+                .push_bind(format!("{}%", term.to_lowercase()))
+                .push(")");
+        }
+
+        if let Some(status) = &self.status {
+            query.push(" AND t.status = ").push_bind(status.clone());
+        }
+
+        if let Some(min) = &self.min_amount {
+            query.push(" AND t.amount >= ").push_bind(min.clone());
+        }
+
+        if let Some(max) = &self.max_amount {
+            query.push(" AND t.amount <= ").push_bind(max.clone());
+        }
+
+        if let Some((key, value)) = &self.metadata {
+            query
+                .push(" AND t.metadata ->> ")
+                .push_bind(key.clone())
+                .push(" = ")
+                .push_bind(value.clone());
+        }
+
+        if let Some(key) = &self.metadata_has {
+            query.push(" AND t.metadata ? ").push_bind(key.clone());
+        }
+
+        if let Some(contains) = &self.metadata_contains {
+            query
+                .push(" AND t.metadata @> ")
+                .push_bind(contains.clone());
+        }
+
+        match self.has_email {
+            Some(true) => {
+                query.push(" AND t.customer_email IS NOT NULL");
+            }
+            Some(false) => {
+                query.push(" AND t.customer_email IS NULL");
+            }
+            None => {}
+        }
+
+        if let Some(from) = &self.created_from {
+            query.push(" AND t.created_at >= ").push_bind(*from);
+        }
+
+        if let Some(before) = &self.created_before {
+            query.push(" AND t.created_at < ").push_bind(*before);
+        }
+
+        if let Some(tag) = &self.tag {
+            // Array containment so the GIN index on tags is usable
+            query
+                .push(" AND t.tags @> ARRAY[")
+                .push_bind(tag.clone())
+                .push("]::text[]");
+        }
+    }
+}
+
+/// `uri`'s path and query with `key` set to `value`, keeping every other param.
+fn with_query_param(uri: &Uri, key: &str, value: &str) -> String {
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    for (k, v) in form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes()) {
+        if k != key {
+            query.append_pair(&k, &v);
+        }
+    }
+    query.append_pair(key, value);
+
+    format!("{}?{}", uri.path(), query.finish())
+}
+
+/// Escapes LIKE wildcards (backslash is Postgres' default escape character).
+pub(crate) fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Checks offset-pagination params against the configured bounds. Out-of-range values are
+/// rejected rather than clamped so client bugs surface instead of silently truncating pages.
+pub(crate) fn validate_page_params(
+    page: Option<i32>,
+    limit: Option<i32>,
+    default_limit: i32,
+    config: &PaginationConfig,
+) -> Result<(i32, i32), ApiError> {
+    let page = page.unwrap_or(1);
+    if page < 1 {
+        return Err(ApiError::bad_request(
+            "invalid_page",
+            "page must be at least 1",
+        ));
+    }
+
+    let limit = limit.unwrap_or(default_limit.min(config.max_page_size));
+    if !(1..=config.max_page_size).contains(&limit) {
+        return Err(ApiError::bad_request(
+            "invalid_limit",
+            format!("limit must be between 1 and {}", config.max_page_size),
+        ));
+    }
+
+    Ok((page, limit))
+}
+
+/// Opaque keyset cursor: base64 of `<created_at micros>|<id>` for the last row seen.
+pub(crate) fn encode_cursor(created_at: NaiveDateTime, id: Uuid) -> String {
+    URL_SAFE_NO_PAD.encode(format!(
+        "{}|{}",
+        created_at.and_utc().timestamp_micros(),
+        id
+    ))
+}
+
+fn decode_cursor(cursor: &str) -> Option<(NaiveDateTime, Uuid)> {
+    let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+    let (micros, id) = raw.split_once('|')?;
+    let created_at = DateTime::from_timestamp_micros(micros.parse().ok()?)?.naive_utc();
+    Some((created_at, Uuid::parse_str(id).ok()?))
+}
+
+/// Decodes a `cursor` request param; anything [`encode_cursor`] didn't produce is a 400.
+pub(crate) fn parse_cursor(cursor: &str) -> Result<(NaiveDateTime, Uuid), ApiError> {
+    decode_cursor(cursor).ok_or_else(|| {
+        ApiError::bad_request(
+            "invalid_cursor",
+            "cursor must be a next_cursor value from an earlier page",
+        )
+    })
+}
+
+fn parse_amount_param(name: &str, value: Option<&str>) -> Result<Option<BigDecimal>, ApiError> {
+    value
+        .map(|v| {
+            BigDecimal::from_str(v.trim()).map_err(|_| {
+                ApiError::bad_request(
+                    "invalid_amount",
+                    format!("{} must be a decimal number", name),
+                )
+            })
+        })
+        .transpose()
+}
+
+fn parse_date_param(name: &str, value: Option<&str>) -> Result<Option<NaiveDate>, ApiError> {
+    value
+        .map(|v| {
+            NaiveDate::from_str(v.trim()).map_err(|_| {
+                ApiError::bad_request(
+                    "invalid_date",
+                    format!("{} must be a YYYY-MM-DD date", name),
+                )
+            })
+        })
+        .transpose()
+}
+
+fn invalid_date_range() -> ApiError {
+    ApiError::bad_request("invalid_date_range", "from must not be after to")
+}
+
+#[derive(FromRow)]
+struct TransactionRow {
+    id: Uuid,
+    tx_type: String,
+    amount: BigDecimal,
+    currency: String,
+    status: String,
+    customer_email: Option<String>,
+    created_at: NaiveDateTime,
+    dispute_status: Option<String>,
+}
+
+/// A list page row carrying the filtered total from `COUNT(*) OVER()`; `None` in
+/// cursor mode, which doesn't count.
+#[derive(FromRow)]
+struct CountedTransactionRow {
+    #[sqlx(flatten)]
+    row: TransactionRow,
+    total_count: Option<i64>,
+}
+
+#[derive(Clone, Serialize, ToSchema)]
 pub struct Transaction {
     pub id: String,
     pub tx_type: String,
     pub amount: String,
+    /// `amount` formatted for display, e.g. `$1,234.50`; only with `format=display`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_formatted: Option<String>,
     pub currency: String,
     pub status: String,
     pub created_at: String,
     pub customer_email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dispute_status: Option<String>,
+}
+
+/// One page of `GET /api/transactions` as read from the database, before the
+/// request-dependent `next` link is added; this is what the list cache stores.
+pub struct TransactionPage {
+    transactions: Vec<Transaction>,
+    total: Option<i64>,
+    next_cursor: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct TransactionListResponse {
     pub transactions: Vec<Transaction>,
-    pub total: i32,
+    /// Transactions matching the filters across all pages; omitted in cursor mode, where
+    /// counting them would read every match and undo what keyset paging saves
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<i32>,
     pub page: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// Absolute URL of the next page, in the same pagination mode; absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct TransactionDetail {
     pub id: String,
     pub tx_type: String,
     pub amount: String,
+    /// `amount` formatted for display, e.g. `$1,234.50`; only with `format=display`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_formatted: Option<String>,
     pub currency: String,
     pub status: String,
     pub created_at: String,
     pub customer_email: Option<String>,
+    #[schema(value_type = Option<Object>)]
     pub metadata: Option<serde_json::Value>,
+    pub fee_amount: Option<String>,
+    /// Amount less the processing fee
+    pub net_amount: Option<String>,
+    pub tags: Vec<String>,
+    /// When the payment settled; null until then
+    pub settled_at: Option<String>,
+    /// When a pending payment will be failed if it hasn't settled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    /// Set once the transaction has been soft-deleted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    /// Address the creating request came from; admins only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    /// `User-Agent` of the creating request; admins only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TransactionDetailQuery {
+    /// Return the transaction even if soft-deleted (default false); admins only
+    pub include_deleted: Option<bool>,
+    /// `display` adds `amount_formatted`
+    pub format: Option<String>,
 }
 
+/// Whether `format=display` was requested; the default keeps responses machine-only.
+fn display_format(format: Option<&str>) -> Result<bool, ApiError> {
+    match format {
+        None | Some("raw") => Ok(false),
+        Some("display") => Ok(true),
+        Some(_) => Err(ApiError::bad_request(
+            "invalid_format",
+            "format must be raw or display",
+        )),
+    }
+}
+
+/// Lists transactions, newest first.
+///
+/// Offset paging (`page`/`limit`) shifts when rows are archived or inserted between requests,
+/// which can skip or repeat rows. Clients that archive while paging should use cursor mode:
+/// each page continues strictly after the last `(created_at, id)` seen, so archiving rows
+/// (including the cursor row itself) never causes later rows to be skipped.
+#[utoipa::path(
+    get,
+    path = "/api/transactions",
+    tag = "transactions",
+    params(TransactionQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Page of transactions", body = TransactionListResponse,
+            headers(("X-Cache" = String, description = "`HIT` or `MISS`; only sent when TRANSACTION_LIST_CACHE_TTL_MS enables the cache"))),
+        (status = 400, description = "Invalid page, limit, amount bounds, date range, cursor or metadata filter", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "include_deleted requested by a non-admin"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+#[allow(clippy::too_many_arguments)]
 pub async fn list_transactions(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
+    State(retry): State<RetryPolicy>,
+    State(pagination): State<Arc<PaginationConfig>>,
+    State(server): State<Arc<ServerConfig>>,
+    State(cache): State<ListCache<TransactionPage>>,
+    State(metrics): State<Metrics>,
+    Extension(principal): Extension<Principal>,
+    request: Parts,
     Query(params): Query<TransactionQuery>,
-) -> Result<Json<TransactionListResponse>, StatusCode> {
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
-    
-    let page = params.page.unwrap_or(1).max(1);
-    let limit = params.limit.unwrap_or(10).clamp(1, 100);
-    let offset = (page - 1) * limit;
-
-    // Build search pattern (wraps with % for ILIKE)
-    let search_pattern = params.search.as_ref().map(|s| format!("%{}%", s));
-    
-    // Status whitelist (prevent invalid status injection)
-    let status_filter = params.filter.as_ref().and_then(|f| {
-        match f.as_str() {
-            "pending" | "settled" | "failed" => Some(f.as_str()),
-            _ => None
+) -> Result<Response, ApiError> {
+    let user_id = principal.user_id;
+
+    let (page, limit) = validate_page_params(
+        params.page,
+        params.limit,
+        pagination.default_page_size,
+        &pagination,
+    )?;
+
+    let filters = ListFilters::from_query(&params, &principal)?;
+    let display = display_format(params.format.as_deref())?;
+
+    // Keyset pagination: any `cursor` param (empty for the first page) replaces OFFSET paging
+    let cursor_mode = params.cursor.is_some();
+    let cursor_position = match params.cursor.as_deref() {
+        Some(cursor) if !cursor.is_empty() => Some(parse_cursor(cursor)?),
+        _ => None,
+    };
+
+    // Optional expansions (e.g. include=disputes)
+    let include_disputes = params
+        .include
+        .as_deref()
+        .is_some_and(|inc| inc.split(',').any(|i| i.trim() == "disputes"));
+
+    // Looked up only after validation so a bad request is never answered from the cache
+    let cache_key = normalized_query(&request.uri);
+    let cached = cache.get(user_id, &cache_key);
+    if cache.is_enabled() {
+        metrics.record_cache_lookup("transaction_list", cached.is_some());
+    }
+    let cache_hit = cached.is_some();
+
+    let transaction_page = match cached {
+        Some(transaction_page) => transaction_page,
+        None => {
+            let transaction_page = Arc::new(
+                fetch_transaction_page(
+                    &pool,
+                    &retry,
+                    user_id,
+                    &filters,
+                    display,
+                    include_disputes,
+                    page,
+                    limit,
+                    cursor_mode,
+                    cursor_position,
+                )
+                .await?,
+            );
+            cache.insert(user_id, cache_key, transaction_page.clone());
+            transaction_page
         }
+    };
+
+    let next_page_query = if cursor_mode {
+        transaction_page
+            .next_cursor
+            .clone()
+            .map(|cursor| ("cursor", cursor))
+    } else if transaction_page
+        .total
+        .is_some_and(|total| i64::from(page) * i64::from(limit) < total)
+    {
+        Some(("page", (page + 1).to_string()))
+    } else {
+        None
+    };
+    let next = next_page_query.and_then(|(key, value)| {
+        server.absolute_url(
+            &request.headers,
+            &with_query_param(&request.uri, key, &value),
+        )
     });
 
-    // Query based on filter combinations (all use bind parameters)
-    type RowType = (Uuid, String, bigdecimal::BigDecimal, String, String, Option<String>, chrono::NaiveDateTime);
-    
-    let rows: Vec<RowType> = match (search_pattern.as_ref(), status_filter) {
-        (Some(pattern), Some(status)) => {
-            sqlx::query_as(
-                "SELECT id, tx_type, amount, currency, status, customer_email, created_at 
-                 FROM transactions 
-                 WHERE user_id = $1 
-                   AND (customer_email ILIKE $2 OR status ILIKE $2)
-                   AND status = $3
-                 ORDER BY created_at DESC 
-                 LIMIT $4 OFFSET $5"
-            )
-            .bind(user_id)
-            .bind(pattern)
-            .bind(status)
-            .bind(limit)
-            .bind(offset)
-            .fetch_all(&pool)
-            .await
-        },
-        (Some(pattern), None) => {
-            sqlx::query_as(
-                "SELECT id, tx_type, amount, currency, status, customer_email, created_at 
-                 FROM transactions 
-                 WHERE user_id = $1 
-                   AND (customer_email ILIKE $2 OR status ILIKE $2)
-                 ORDER BY created_at DESC 
-                 LIMIT $3 OFFSET $4"
-            )
-            .bind(user_id)
-            .bind(pattern)
-            .bind(limit)
-            .bind(offset)
-            .fetch_all(&pool)
-            .await
-        },
-        (None, Some(status)) => {
-            sqlx::query_as(
-                "SELECT id, tx_type, amount, currency, status, customer_email, created_at 
-                 FROM transactions 
-                 WHERE user_id = $1 
-                   AND status = $2
-                 ORDER BY created_at DESC 
-                 LIMIT $3 OFFSET $4"
-            )
-            .bind(user_id)
-            .bind(status)
-            .bind(limit)
-            .bind(offset)
-            .fetch_all(&pool)
-            .await
-        },
-        (None, None) => {
-            sqlx::query_as(
-                "SELECT id, tx_type, amount, currency, status, customer_email, created_at 
-                 FROM transactions 
-                 WHERE user_id = $1 
-                 ORDER BY created_at DESC 
-                 LIMIT $2 OFFSET $3"
-            )
-            .bind(user_id)
-            .bind(limit)
-            .bind(offset)
-            .fetch_all(&pool)
-            .await
-        }
+    let mut response = Json(TransactionListResponse {
+        transactions: transaction_page.transactions.clone(),
+        total: transaction_page
+            .total
+            .map(|total| i32::try_from(total).unwrap_or(i32::MAX)),
+        page,
+        next_cursor: transaction_page.next_cursor.clone(),
+        next,
+    })
+    .into_response();
+    if cache.is_enabled() {
+        let status = if cache_hit { "HIT" } else { "MISS" };
+        response
+            .headers_mut()
+            .insert("x-cache", HeaderValue::from_static(status));
     }
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(response)
+}
+
+/// The query string with its parameters sorted, so reordered but equivalent
+/// requests share a cache entry.
+fn normalized_query(uri: &Uri) -> String {
+    let mut pairs: Vec<(String, String)> =
+        form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+            .into_owned()
+            .collect();
+    pairs.sort();
+
+    form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(pairs)
+        .finish()
+}
+
+/// Reads one page for [`list_transactions`]; a `cursor_position` of `None` in cursor
+/// mode starts from the newest row.
+#[allow(clippy::too_many_arguments)]
+async fn fetch_transaction_page(
+    pool: &PgPool,
+    retry: &RetryPolicy,
+    user_id: Uuid,
+    filters: &ListFilters,
+    display: bool,
+    include_disputes: bool,
+    page: i32,
+    limit: i32,
+    cursor_mode: bool,
+    cursor_position: Option<(NaiveDateTime, Uuid)>,
+) -> Result<TransactionPage, ApiError> {
+    // Widened first: the last pages of i32 would overflow the product
+    let offset = i64::from(page - 1) * i64::from(limit);
+
+    // Build query from the active filters (all use bind parameters); rebuilt per attempt
+    // since a built QueryBuilder can't be executed twice. In offset mode the total is
+    // counted by a window over the filtered set in the inner query, before LIMIT applies,
+    // so it matches the page's snapshot without a second round trip. Cursor mode doesn't
+    // count: the window would read every match, where the keyset bound lets the
+    // (user_id, created_at, id) index stop after one page.
+    let page_query = || {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT t.id, t.tx_type, t.amount, t.currency, t.status, t.customer_email, t.created_at,
+                    t.total_count",
+        );
+
+        if include_disputes {
+            query.push(", d.status AS dispute_status");
+        } else {
+            query.push(", NULL::VARCHAR AS dispute_status");
+        }
+
+        query.push(
+            " FROM (
+                 SELECT t.id, t.tx_type, t.amount, t.currency, t.status, t.customer_email,
+                        t.created_at, ",
+        );
+        query.push(if cursor_mode {
+            "NULL::BIGINT AS total_count"
+        } else {
+            "COUNT(*) OVER() AS total_count"
+        });
+        query.push(" FROM transactions t");
+        filters.push_where(&mut query, user_id);
+        query.push(") t");
+
+        if include_disputes {
+            query.push(
+                " LEFT JOIN LATERAL (
+                     SELECT status FROM disputes
+                     WHERE transaction_id = t.id
+                     ORDER BY created_at DESC, id DESC
+                     LIMIT 1
+                 ) d ON TRUE",
+            );
+        }
+
+        if cursor_mode {
+            if let Some((created_at, id)) = cursor_position {
+                query
+                    .push(" WHERE (t.created_at, t.id) < (")
+                    .push_bind(created_at)
+                    .push(", ")
+                    .push_bind(id)
+                    .push(")");
+            }
+            // Fetch one extra row to know whether another page exists
+            query
+                .push(" ORDER BY t.created_at DESC, t.id DESC LIMIT ")
+                .push_bind(limit + 1);
+        } else {
+            query
+                .push(" ORDER BY t.created_at DESC, t.id DESC LIMIT ")
+                .push_bind(limit)
+                .push(" OFFSET ")
+                .push_bind(offset);
+        }
+
+        query
+    };
+
+    let counted: Vec<CountedTransactionRow> = retry
+        .run(|| async { page_query().build_query_as().fetch_all(pool).await })
+        .await?;
+
+    // An empty page only proves the total is zero when it is the first page. Past the
+    // end there is no row to carry the count, so that (rare) request counts separately
+    // rather than reporting a wrong total.
+    let total = match counted.first() {
+        _ if cursor_mode => None,
+        Some(first) => first.total_count,
+        None if offset == 0 => Some(0),
+        None => Some(
+            retry
+                .run(|| async {
+                    let mut count_query =
+                        QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM transactions t");
+                    filters.push_where(&mut count_query, user_id);
+                    count_query.build_query_scalar().fetch_one(pool).await
+                })
+                .await?,
+        ),
+    };
+    let mut rows: Vec<TransactionRow> = counted.into_iter().map(|counted| counted.row).collect();
+
+    let next_cursor = if cursor_mode && rows.len() > limit as usize {
+        rows.truncate(limit as usize);
+        rows.last().map(|row| encode_cursor(row.created_at, row.id))
+    } else {
+        None
+    };
 
     let transactions: Vec<Transaction> = rows
         .into_iter()
-        .map(|(id, tx_type, amount, currency, status, customer_email, created_at)| Transaction {
-            id: id.to_string(),
-            tx_type,
-            amount: amount.to_string(),
-            currency,
-            status,
-            created_at: created_at.to_string(),
-            customer_email,
+        .map(|row| Transaction {
+            id: row.id.to_string(),
+            tx_type: row.tx_type,
+            amount: row.amount.to_string(),
+            amount_formatted: display.then(|| currency::format_display(&row.amount, &row.currency)),
+            currency: row.currency,
+            status: row.status,
+            created_at: row.created_at.to_string(),
+            customer_email: row.customer_email,
+            dispute_status: row.dispute_status,
         })
         .collect();
 
-    // Count total (also filtered by user_id)
-    let total: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM transactions WHERE user_id = $1"
+    Ok(TransactionPage {
+        transactions,
+        total,
+        next_cursor,
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TimeseriesQuery {
+    /// Bucket size: `hour`, `day` (default) or `week`
+    pub interval: Option<String>,
+    /// First day to include (`YYYY-MM-DD`, UTC); defaults to 30 days before `to`
+    pub from: Option<String>,
+    /// Last day to include, inclusive (`YYYY-MM-DD`, UTC); defaults to today
+    pub to: Option<String>,
+    /// Timestamp to bucket and filter on: `created_at` (default) or `settled_at`
+    pub by: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TimeseriesPoint {
+    /// Bucket start: `YYYY-MM-DD` for day/week, `YYYY-MM-DDTHH:00` for hour
+    pub bucket: String,
+    pub count: i64,
+    pub total: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TimeseriesSeries {
+    pub currency: String,
+    pub points: Vec<TimeseriesPoint>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TimeseriesResponse {
+    pub interval: String,
+    /// Timestamp the buckets are based on
+    pub by: String,
+    pub series: Vec<TimeseriesSeries>,
+}
+
+/// Default window when `from` is omitted.
+const TIMESERIES_DEFAULT_DAYS: u64 = 30;
+
+/// Settled volume per currency, bucketed with `date_trunc` on creation or settlement
+/// time. Empty buckets are omitted.
+#[utoipa::path(
+    get,
+    path = "/api/transactions/timeseries",
+    tag = "transactions",
+    params(TimeseriesQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Settled volume per bucket and currency", body = TimeseriesResponse),
+        (status = 400, description = "Unknown interval or `by`, or invalid date range"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error")
     )
-    .bind(user_id)
-    .fetch_one(&pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+)]
+pub async fn get_transaction_timeseries(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<TimeseriesQuery>,
+) -> Result<Json<TimeseriesResponse>, ApiError> {
+    let user_id = principal.user_id;
 
-    Ok(Json(TransactionListResponse {
-        transactions,
-        total: total as i32,
-        page,
+    // Whitelisted: the value is passed to date_trunc as its field name
+    let interval = match params.interval.as_deref().unwrap_or("day") {
+        interval @ ("hour" | "day" | "week") => interval.to_string(),
+        _ => {
+            return Err(ApiError::bad_request(
+                "invalid_interval",
+                "interval must be hour, day or week",
+            ))
+        }
+    };
+    let by = match params.by.as_deref().unwrap_or("created_at") {
+        by @ ("created_at" | "settled_at") => by.to_string(),
+        _ => {
+            return Err(ApiError::bad_request(
+                "invalid_by",
+                "by must be created_at or settled_at",
+            ))
+        }
+    };
+
+    let to =
+        parse_date_param("to", params.to.as_deref())?.unwrap_or_else(|| Utc::now().date_naive());
+    let from = parse_date_param("from", params.from.as_deref())?
+        .unwrap_or(to - Days::new(TIMESERIES_DEFAULT_DAYS));
+    if from > to {
+        return Err(invalid_date_range());
+    }
+
+    let rows = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT date_trunc($2, t.ts) AS "bucket!",
+                       currency,
+                       COUNT(*) AS "count!",
+                       SUM(amount) AS "total!"
+                FROM transactions,
+                     LATERAL (SELECT CASE WHEN $5 THEN settled_at ELSE created_at END AS ts) t
+                WHERE user_id = $1
+                  AND status = 'settled'
+                  AND t.ts >= $3
+                  AND t.ts < $4
+                GROUP BY currency, 1
+                ORDER BY currency, 1
+                "#,
+                user_id,
+                interval,
+                from.and_time(NaiveTime::MIN),
+                (to + Days::new(1)).and_time(NaiveTime::MIN),
+                by == "settled_at"
+            )
+            .fetch_all(&pool)
+        })
+        .await?;
+
+    // Rows arrive ordered by currency, so each series is a contiguous run
+    let mut series: Vec<TimeseriesSeries> = Vec::new();
+    for row in rows {
+        let bucket = if interval == "hour" {
+            row.bucket.format("%Y-%m-%dT%H:00").to_string()
+        } else {
+            row.bucket.format("%Y-%m-%d").to_string()
+        };
+        let point = TimeseriesPoint {
+            bucket,
+            count: row.count,
+            total: row.total.to_string(),
+        };
+
+        match series.last_mut() {
+            Some(last) if last.currency == row.currency => last.points.push(point),
+            _ => series.push(TimeseriesSeries {
+                currency: row.currency,
+                points: vec![point],
+            }),
+        }
+    }
+
+    Ok(Json(TimeseriesResponse {
+        interval,
+        by,
+        series,
     }))
 }
 
-pub async fn get_transaction(
+const CSV_HEADER: &str = "id,tx_type,amount,currency,status,created_at,customer_email\n";
+
+/// Rows buffered between the database cursor and the response body.
+const EXPORT_BUFFER_ROWS: usize = 64;
+
+/// Quotes a CSV field when it contains a delimiter, quote or line break (RFC 4180).
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+fn csv_line(row: &TransactionRow) -> String {
+    format!(
+        "{},{},{},{},{},{},{}\n",
+        row.id,
+        csv_field(&row.tx_type),
+        row.amount,
+        csv_field(&row.currency),
+        csv_field(&row.status),
+        row.created_at,
+        csv_field(row.customer_email.as_deref().unwrap_or_default()),
+    )
+}
+
+/// Streams every matching transaction as CSV, newest first.
+///
+/// Accepts the same filters as `GET /api/transactions`; paging params are ignored.
+/// Rows are read through a database cursor and written as they arrive, so large
+/// exports never sit in memory.
+#[utoipa::path(
+    get,
+    path = "/api/transactions/export",
+    tag = "transactions",
+    params(TransactionQuery),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "CSV attachment", content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid amount bounds, date range or metadata filter"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "include_deleted requested by a non-admin")
+    )
+)]
+pub async fn export_transactions(
+    State(pool): State<PgPool>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<TransactionQuery>,
+) -> Result<Response, ApiError> {
+    let user_id = principal.user_id;
+    let filters = ListFilters::from_query(&params, &principal)?;
+
+    let (tx, rx) = mpsc::channel::<Result<String, sqlx::Error>>(EXPORT_BUFFER_ROWS);
+
+    // The row stream borrows the query, so it's driven from its own task and
+    // handed to the body through a bounded channel (backpressure from the client).
+    tokio::spawn(async move {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT t.id, t.tx_type, t.amount, t.currency, t.status, t.customer_email, t.created_at,
+                    NULL::VARCHAR AS dispute_status
+             FROM transactions t",
+        );
+        filters.push_where(&mut query, user_id);
+        query.push(" ORDER BY t.created_at DESC, t.id DESC");
+
+        if tx.send(Ok(CSV_HEADER.to_string())).await.is_err() {
+            return;
+        }
+
+        let mut rows = query.build_query_as::<TransactionRow>().fetch(&pool);
+        while let Some(row) = rows.next().await {
+            let chunk = row.map(|row| csv_line(&row));
+            if let Err(e) = &chunk {
+                tracing::warn!(error = %e, %user_id, "transaction export aborted");
+            }
+            let failed = chunk.is_err();
+            if tx.send(chunk).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"transactions.csv\"",
+            ),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/transactions/stream",
+    tag = "transactions",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Server-sent events for the caller's transactions", content_type = "text/event-stream", body = TransactionEvent),
+        (status = 401, description = "Missing or invalid token")
+    )
+)]
+pub async fn stream_transactions(
+    State(events): State<TransactionEvents>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let user_id = principal.user_id;
+
+    // The receiver is dropped with the stream when the client disconnects
+    let stream =
+        BroadcastStream::new(events.subscribe()).filter_map(move |message| match message {
+            Ok(event) if event.user_id == user_id => Event::default()
+                .id(event.event_id.to_string())
+                .event(event.kind.as_str())
+                .json_data(&event)
+                .ok()
+                .map(Ok),
+            Ok(_) => None,
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "transaction stream subscriber lagged");
+                None
+            }
+        });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ArchiveTransactionResponse {
+    pub id: String,
+    pub archived_at: String,
+}
+
+/// Hides a transaction from default listings; it stays retrievable by id. Idempotent.
+#[utoipa::path(
+    post,
+    path = "/api/transactions/{id}/archive",
+    tag = "transactions",
+    params(("id" = Uuid, Path, description = "Transaction id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Transaction archived", body = ArchiveTransactionResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Transaction not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn archive_transaction(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
+    State(list_cache): State<ListCache<TransactionPage>>,
+    Extension(principal): Extension<Principal>,
     Path(id): Path<Uuid>,
-) -> Result<Json<TransactionDetail>, StatusCode> {
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+) -> Result<Json<ArchiveTransactionResponse>, ApiError> {
+    let user_id = principal.user_id;
 
     let result = sqlx::query!(
         r#"
-        SELECT id, tx_type, amount, currency, status, customer_email, metadata, created_at
-        FROM transactions
-        WHERE id = $1 AND user_id = $2
+        WITH updated AS (
+            UPDATE transactions
+            SET archived_at = COALESCE(archived_at, NOW())
+            WHERE id = $1 AND user_id = $2
+            RETURNING id, archived_at
+        ),
+        -- NOW() is fixed for the statement, so this only matches a row changed just now
+        audit AS (
+            INSERT INTO audit_log (transaction_id, actor_user_id, action)
+            SELECT id, $2, 'archived' FROM updated WHERE archived_at = NOW()
+        )
+        SELECT id AS "id!", archived_at FROM updated
+        "#,
+        id,
+        user_id
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(StatusCode::NOT_FOUND)?;
+    list_cache.invalidate_user(user_id);
+
+    Ok(Json(ArchiveTransactionResponse {
+        id: result.id.to_string(),
+        archived_at: result.archived_at.unwrap_or_default().to_string(),
+    }))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DeleteTransactionResponse {
+    pub id: String,
+    pub deleted_at: String,
+}
+
+/// Soft-deletes a transaction: it disappears from listings and lookups but the row is
+/// kept for audit. Idempotent.
+#[utoipa::path(
+    delete,
+    path = "/api/transactions/{id}",
+    tag = "transactions",
+    params(("id" = Uuid, Path, description = "Transaction id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Transaction deleted", body = DeleteTransactionResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Transaction not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn delete_transaction(
+    State(pool): State<PgPool>,
+    State(list_cache): State<ListCache<TransactionPage>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<DeleteTransactionResponse>, ApiError> {
+    let user_id = principal.user_id;
+
+    let result = sqlx::query!(
+        r#"
+        WITH updated AS (
+            UPDATE transactions
+            SET deleted_at = COALESCE(deleted_at, NOW())
+            WHERE id = $1 AND user_id = $2
+            RETURNING id, deleted_at
+        ),
+        -- NOW() is fixed for the statement, so this only matches a row changed just now
+        audit AS (
+            INSERT INTO audit_log (transaction_id, actor_user_id, action)
+            SELECT id, $2, 'deleted' FROM updated WHERE deleted_at = NOW()
+        )
+        SELECT id AS "id!", deleted_at FROM updated
         "#,
         id,
         user_id
     )
-    .fetch_one(&pool)
-    .await
-    .map_err(|_| StatusCode::NOT_FOUND)?;
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(StatusCode::NOT_FOUND)?;
+    list_cache.invalidate_user(user_id);
+
+    Ok(Json(DeleteTransactionResponse {
+        id: result.id.to_string(),
+        deleted_at: result.deleted_at.unwrap_or_default().to_string(),
+    }))
+}
+
+/// Shallow-merges a JSON object into the transaction's `metadata`; keys set to
+/// `null` are removed. Status and amounts are never touched.
+#[utoipa::path(
+    patch,
+    path = "/api/transactions/{id}/metadata",
+    tag = "transactions",
+    params(("id" = Uuid, Path, description = "Transaction id")),
+    request_body(content = Object, description = "Keys to set; `null` deletes a key"),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Updated transaction", body = TransactionDetail),
+        (status = 400, description = "Body is not a JSON object", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Transaction not found", body = ErrorBody),
+        (status = 413, description = "Merged metadata too large", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn update_transaction_metadata(
+    State(pool): State<PgPool>,
+    State(list_cache): State<ListCache<TransactionPage>>,
+    State(payment_config): State<Arc<PaymentConfig>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Json<TransactionDetail>, ApiError> {
+    let user_id = principal.user_id;
+
+    let serde_json::Value::Object(patch) = patch else {
+        return Err(ApiError::bad_request(
+            "invalid_metadata",
+            "request body must be a JSON object",
+        ));
+    };
+
+    // Row lock so concurrent patches merge instead of overwriting each other
+    let mut tx = pool.begin().await?;
+    let current = sqlx::query_scalar!(
+        r#"
+        SELECT metadata FROM transactions
+        WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL
+        FOR UPDATE
+        "#,
+        id,
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let previous = match current {
+        Some(serde_json::Value::Object(existing)) => existing,
+        _ => serde_json::Map::new(),
+    };
+    let mut metadata = previous.clone();
+    for (key, value) in patch.clone() {
+        if value.is_null() {
+            metadata.remove(&key);
+        } else {
+            metadata.insert(key, value);
+        }
+    }
+    let metadata = serde_json::Value::Object(metadata);
+    validate_metadata(Some(&metadata), &payment_config)?;
+
+    // Only keys whose value actually changed; an unchanged patch leaves no audit entry
+    let mut diff = serde_json::Map::new();
+    for (key, value) in patch {
+        let old = previous
+            .get(&key)
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+        if old != value {
+            diff.insert(key, serde_json::json!({ "old": old, "new": value }));
+        }
+    }
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE transactions SET metadata = $3
+        WHERE id = $1 AND user_id = $2
+        RETURNING id, tx_type, amount, currency, customer_email, metadata, fee_amount, net_amount,
+                  tags, created_at, settled_at, expires_at, deleted_at,
+                  client_ip, user_agent,
+                  CASE WHEN status = 'pending' AND expires_at <= NOW() THEN 'failed'
+                       ELSE status END AS "status!"
+        "#,
+        id,
+        user_id,
+        metadata
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if !diff.is_empty() {
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_log (transaction_id, actor_user_id, action, metadata_diff)
+            VALUES ($1, $2, 'metadata_updated', $3)
+            "#,
+            id,
+            user_id,
+            serde_json::Value::Object(diff)
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    list_cache.invalidate_user(user_id);
 
     Ok(Json(TransactionDetail {
         id: result.id.to_string(),
         tx_type: result.tx_type,
         amount: result.amount.to_string(),
+        amount_formatted: None,
         currency: result.currency,
         status: result.status,
         created_at: result.created_at.unwrap().to_string(),
         customer_email: result.customer_email,
         metadata: result.metadata,
+        fee_amount: result.fee_amount.map(|fee| fee.to_string()),
+        net_amount: result.net_amount.map(|net| net.to_string()),
+        tags: result.tags,
+        settled_at: result.settled_at.map(|t| t.to_string()),
+        expires_at: result.expires_at.map(|t| t.to_string()),
+        deleted_at: result.deleted_at.map(|t| t.to_string()),
+        client_ip: result.client_ip.filter(|_| principal.is_admin()),
+        user_agent: result.user_agent.filter(|_| principal.is_admin()),
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct StatusLookupRequest {
+    /// Transaction ids to look up; at most 500
+    pub ids: Vec<Uuid>,
+}
+
+/// Upper bound on ids accepted by one status lookup.
+const MAX_STATUS_LOOKUP_IDS: usize = 500;
+
+/// Current status of many transactions in one query, as an id-to-status map. Ids that
+/// are unknown, soft-deleted or owned by someone else are omitted rather than reported.
+#[utoipa::path(
+    post,
+    path = "/api/transactions/status-lookup",
+    tag = "transactions",
+    request_body = StatusLookupRequest,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Status per found transaction id", body = BTreeMap<String, String>),
+        (status = 400, description = "Empty or oversized id list", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn lookup_transaction_statuses(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
+    Json(payload): Json<StatusLookupRequest>,
+) -> Result<Json<BTreeMap<String, String>>, ApiError> {
+    let user_id = principal.user_id;
+
+    if payload.ids.is_empty() || payload.ids.len() > MAX_STATUS_LOOKUP_IDS {
+        return Err(ApiError::bad_request(
+            "invalid_ids",
+            format!("ids must contain 1 to {} ids", MAX_STATUS_LOOKUP_IDS),
+        ));
+    }
+
+    let rows = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT id,
+                       CASE WHEN status = 'pending' AND expires_at <= NOW() THEN 'failed'
+                            ELSE status END AS "status!"
+                FROM transactions
+                WHERE id = ANY($1) AND user_id = $2 AND deleted_at IS NULL
+                "#,
+                &payload.ids,
+                user_id
+            )
+            .fetch_all(&pool)
+        })
+        .await?;
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|row| (row.id.to_string(), row.status))
+            .collect(),
+    ))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RefundSummary {
+    pub id: String,
+    pub amount: String,
+    pub status: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TransactionRefundsResponse {
+    pub transaction_id: String,
+    pub currency: String,
+    /// Oldest first
+    pub refunds: Vec<RefundSummary>,
+    /// Sum of refunds that haven't failed
+    pub refunded_total: String,
+    /// Parent amount less `refunded_total`
+    pub refundable_remaining: String,
+}
+
+/// Lists refunds recorded against a transaction (children linked by `parent_transaction_id`).
+#[utoipa::path(
+    get,
+    path = "/api/transactions/{id}/refunds",
+    tag = "transactions",
+    params(("id" = Uuid, Path, description = "Parent transaction id")),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Refunds against the transaction", body = TransactionRefundsResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Transaction not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn list_transaction_refunds(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<TransactionRefundsResponse>, ApiError> {
+    let user_id = principal.user_id;
+
+    let parent = retry
+        .run(|| {
+            sqlx::query!(
+                "SELECT amount, currency FROM transactions
+                 WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL",
+                id,
+                user_id
+            )
+            .fetch_optional(&pool)
+        })
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let refunds = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT id, amount, status, created_at
+                FROM transactions
+                WHERE parent_transaction_id = $1 AND user_id = $2 AND tx_type = 'refund'
+                ORDER BY created_at, id
+                "#,
+                id,
+                user_id
+            )
+            .fetch_all(&pool)
+        })
+        .await?;
+
+    let refunded_total = refunds
+        .iter()
+        .filter(|refund| refund.status != "failed")
+        .fold(BigDecimal::zero(), |total, refund| total + &refund.amount);
+    let refundable_remaining = (&parent.amount - &refunded_total).max(BigDecimal::zero());
+
+    Ok(Json(TransactionRefundsResponse {
+        transaction_id: id.to_string(),
+        currency: parent.currency,
+        refunds: refunds
+            .into_iter()
+            .map(|refund| RefundSummary {
+                id: refund.id.to_string(),
+                amount: refund.amount.to_string(),
+                status: refund.status,
+                created_at: refund.created_at.unwrap_or_default().to_string(),
+            })
+            .collect(),
+        refunded_total: refunded_total.to_string(),
+        refundable_remaining: refundable_remaining.to_string(),
     }))
-}
\ No newline at end of file
+}
+
+/// Returns a weak `ETag` and answers `If-None-Match` with 304 so status pollers
+/// only download the body when something changed.
+#[utoipa::path(
+    get,
+    path = "/api/transactions/{id}",
+    tag = "transactions",
+    params(
+        ("id" = Uuid, Path, description = "Transaction id"),
+        TransactionDetailQuery,
+        ("If-None-Match" = Option<String>, Header, description = "ETag from an earlier response")
+    ),
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Transaction details", body = TransactionDetail,
+            headers(("ETag" = String, description = "Weak validator for this representation"))),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "include_deleted requested by a non-admin"),
+        (status = 404, description = "Transaction not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_transaction(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<TransactionDetailQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let user_id = principal.user_id;
+
+    let include_deleted = params.include_deleted.unwrap_or(false);
+    if include_deleted && !principal.is_admin() {
+        return Err(StatusCode::FORBIDDEN.into());
+    }
+    let display = display_format(params.format.as_deref())?;
+
+    let result = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT id, tx_type, amount, currency, customer_email, metadata, fee_amount, net_amount,
+                       tags, created_at, settled_at, expires_at, deleted_at, updated_at,
+                       client_ip, user_agent,
+                       -- Report expiry immediately rather than waiting for the next sweep
+                       CASE WHEN status = 'pending' AND expires_at <= NOW() THEN 'failed'
+                            ELSE status END AS "status!"
+                FROM transactions
+                WHERE id = $1 AND user_id = $2 AND ($3 OR deleted_at IS NULL)
+                "#,
+                id,
+                user_id,
+                include_deleted
+            )
+            .fetch_one(&pool)
+        })
+        .await?;
+
+    let etag = transaction_etag(
+        result.id,
+        &result.status,
+        result.updated_at,
+        display,
+        principal.is_admin(),
+    );
+    let etag_header = [(header::ETAG, etag.clone())];
+    if if_none_match(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, etag_header).into_response());
+    }
+
+    let detail = TransactionDetail {
+        id: result.id.to_string(),
+        tx_type: result.tx_type,
+        amount: result.amount.to_string(),
+        amount_formatted: display
+            .then(|| currency::format_display(&result.amount, &result.currency)),
+        currency: result.currency,
+        status: result.status,
+        created_at: result.created_at.unwrap().to_string(),
+        customer_email: result.customer_email,
+        metadata: result.metadata,
+        fee_amount: result.fee_amount.map(|fee| fee.to_string()),
+        net_amount: result.net_amount.map(|net| net.to_string()),
+        tags: result.tags,
+        settled_at: result.settled_at.map(|t| t.to_string()),
+        expires_at: result.expires_at.map(|t| t.to_string()),
+        deleted_at: result.deleted_at.map(|t| t.to_string()),
+        client_ip: result.client_ip.filter(|_| principal.is_admin()),
+        user_agent: result.user_agent.filter(|_| principal.is_admin()),
+    };
+
+    Ok((etag_header, Json(detail)).into_response())
+}
+
+/// Weak validator for one representation of a transaction. The derived status is
+/// included because a pending payment reads as failed once `expires_at` passes without
+/// the row changing; `display` and `admin` change the body, so they vary the tag too.
+fn transaction_etag(
+    id: Uuid,
+    status: &str,
+    updated_at: NaiveDateTime,
+    display: bool,
+    admin: bool,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!(
+        "{}:{}:{}:{}:{}",
+        id,
+        status,
+        updated_at.and_utc().timestamp_micros(),
+        display,
+        admin
+    ));
+    let digest = format!("{:x}", hasher.finalize());
+    format!("W/\"{}\"", &digest[..32])
+}
+
+/// Whether `If-None-Match` lists `etag` (or `*`), using the weak comparison RFC 9110
+/// requires for this header.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{JwtConfig, ReceiptConfig};
+    use crate::db::test_support;
+    use crate::handlers::{payments, receipts};
+    use crate::middleware::auth::ADMIN_ROLE;
+    use axum::response::IntoResponse;
+
+    fn status_of<T>(result: Result<T, ApiError>) -> StatusCode
+    where
+        T: IntoResponse,
+    {
+        match result {
+            Ok(response) => response.into_response().status(),
+            Err(e) => e.into_response().status(),
+        }
+    }
+
+    fn pagination(max_page_size: i32) -> PaginationConfig {
+        PaginationConfig {
+            max_page_size,
+            default_page_size: 10,
+            list_cache_ttl: None,
+        }
+    }
+
+    /// Calls `list_transactions` as `GET /api/transactions?<query>` and returns the JSON body.
+    async fn list(
+        pool: &PgPool,
+        user_id: Uuid,
+        query: &str,
+    ) -> Result<serde_json::Value, ApiError> {
+        let uri: Uri = format!("/api/transactions?{}", query).parse().unwrap();
+        let (parts, ()) = axum::http::Request::builder()
+            .uri(uri.clone())
+            .body(())
+            .unwrap()
+            .into_parts();
+        let response = list_transactions(
+            State(pool.clone()),
+            State(test_support::retry()),
+            State(Arc::new(pagination(100))),
+            State(Arc::new(ServerConfig::from_env())),
+            State(ListCache::new(None)),
+            State(Metrics::new()),
+            Extension(test_support::principal(user_id)),
+            parts,
+            Query::try_from_uri(&uri).unwrap(),
+        )
+        .await?;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        Ok(serde_json::from_slice(&body).unwrap())
+    }
+
+    /// The listed transactions as `(id, field)` pairs, in response order.
+    fn listed<'a>(body: &'a serde_json::Value, field: &str) -> Vec<(&'a str, Option<&'a str>)> {
+        body["transactions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| {
+                (
+                    t["id"].as_str().unwrap(),
+                    t.get(field).and_then(|v| v.as_str()),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn include_disputes_attaches_the_latest_dispute_status() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let clean = test_support::insert_payment(&pool, user_id, "10.00", "USD", "settled").await;
+        let disputed =
+            test_support::insert_payment(&pool, user_id, "20.00", "USD", "settled").await;
+        sqlx::query(
+            "INSERT INTO disputes (transaction_id, status, created_at)
+             VALUES ($1, 'open', NOW() - INTERVAL '1 day'), ($1, 'won', NOW())",
+        )
+        .bind(disputed)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let body = list(&pool, user_id, "include=disputes").await.unwrap();
+        let statuses: BTreeMap<_, _> = listed(&body, "dispute_status").into_iter().collect();
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses[clean.to_string().as_str()], None);
+        assert_eq!(statuses[disputed.to_string().as_str()], Some("won"));
+
+        // Without the expansion the field is left out entirely
+        let body = list(&pool, user_id, "").await.unwrap();
+        assert!(listed(&body, "dispute_status")
+            .iter()
+            .all(|(_, status)| status.is_none()));
+        assert_eq!(body["total"], 2);
+    }
+
+    #[test]
+    fn page_params_default_to_first_page_and_default_limit() {
+        assert_eq!(
+            validate_page_params(None, None, 10, &pagination(100)).unwrap(),
+            (1, 10)
+        );
+        // A default above the cap is capped rather than rejected
+        assert_eq!(
+            validate_page_params(None, None, 50, &pagination(20)).unwrap(),
+            (1, 20)
+        );
+    }
+
+    #[test]
+    fn page_params_accept_the_bounds() {
+        assert_eq!(
+            validate_page_params(Some(1), Some(1), 10, &pagination(100)).unwrap(),
+            (1, 1)
+        );
+        assert_eq!(
+            validate_page_params(Some(7), Some(100), 10, &pagination(100)).unwrap(),
+            (7, 100)
+        );
+    }
+
+    #[test]
+    fn page_params_reject_out_of_range_values() {
+        let config = pagination(100);
+        let code = |page, limit| {
+            validate_page_params(page, limit, 10, &config)
+                .unwrap_err()
+                .code()
+        };
+
+        assert_eq!(code(Some(0), None), "invalid_page");
+        assert_eq!(code(Some(-1), None), "invalid_page");
+        assert_eq!(code(None, Some(0)), "invalid_limit");
+        assert_eq!(code(None, Some(101)), "invalid_limit");
+        assert_eq!(code(None, Some(-10)), "invalid_limit");
+    }
+
+    #[tokio::test]
+    async fn soft_deleted_payment_is_hidden_from_every_lookup() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let id = test_support::insert_payment(&pool, user_id, "42.00", "USD", "settled").await;
+        let principal = test_support::principal(user_id);
+        let detail = |include_deleted, principal: Principal| {
+            get_transaction(
+                State(pool.clone()),
+                State(test_support::retry()),
+                Extension(principal),
+                Path(id),
+                Query(TransactionDetailQuery {
+                    include_deleted: Some(include_deleted),
+                    format: None,
+                }),
+                HeaderMap::new(),
+            )
+        };
+
+        assert_eq!(
+            status_of(detail(false, principal.clone()).await),
+            StatusCode::OK
+        );
+
+        let Json(deleted) = delete_transaction(
+            State(pool.clone()),
+            State(ListCache::new(None)),
+            Extension(principal.clone()),
+            Path(id),
+        )
+        .await
+        .unwrap();
+        assert_eq!(deleted.id, id.to_string());
+
+        assert_eq!(
+            status_of(detail(false, principal.clone()).await),
+            StatusCode::NOT_FOUND
+        );
+        let payment = payments::get_payment(
+            State(pool.clone()),
+            State(test_support::retry()),
+            State(Arc::new(PaymentConfig::from_env())),
+            State(Arc::new(JwtConfig::from_env())),
+            Extension(principal.clone()),
+            Path(id),
+        )
+        .await;
+        assert_eq!(status_of(payment), StatusCode::NOT_FOUND);
+        let receipt = receipts::get_transaction_receipt(
+            State(pool.clone()),
+            State(test_support::retry()),
+            State(Arc::new(ReceiptConfig::from_env())),
+            Extension(principal.clone()),
+            Path(id),
+        )
+        .await;
+        assert_eq!(status_of(receipt), StatusCode::NOT_FOUND);
+        let refunds = list_transaction_refunds(
+            State(pool.clone()),
+            State(test_support::retry()),
+            Extension(principal.clone()),
+            Path(id),
+        )
+        .await;
+        assert_eq!(status_of(refunds), StatusCode::NOT_FOUND);
+
+        // Still there for an admin who asks for it
+        let admin = Principal {
+            role: ADMIN_ROLE.to_string(),
+            ..principal
+        };
+        assert_eq!(status_of(detail(true, admin).await), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn amount_range_keeps_only_amounts_within_the_bounds() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let mut ids = BTreeMap::new();
+        for amount in ["5.00", "10.00", "49.99", "100.00", "100.01", "500.00"] {
+            let id = test_support::insert_payment(&pool, user_id, amount, "USD", "settled").await;
+            ids.insert(id.to_string(), amount);
+        }
+        let amounts = |body: &serde_json::Value| {
+            let mut amounts: Vec<&str> = listed(body, "id")
+                .into_iter()
+                .map(|(id, _)| ids[id])
+                .collect();
+            amounts.sort_by_key(|a| BigDecimal::from_str(a).unwrap());
+            amounts
+        };
+
+        let body = list(&pool, user_id, "min_amount=10&max_amount=100")
+            .await
+            .unwrap();
+        assert_eq!(amounts(&body), ["10.00", "49.99", "100.00"]);
+        assert_eq!(body["total"], 3);
+
+        let body = list(&pool, user_id, "min_amount=100.005").await.unwrap();
+        assert_eq!(amounts(&body), ["100.01", "500.00"]);
+
+        let body = list(&pool, user_id, "max_amount=%2010%20").await.unwrap();
+        assert_eq!(amounts(&body), ["5.00", "10.00"]);
+    }
+
+    #[tokio::test]
+    async fn amount_range_rejects_bad_bounds() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+
+        for query in [
+            "min_amount=ten",
+            "max_amount=",
+            "min_amount=100&max_amount=10",
+        ] {
+            assert_eq!(
+                status_of(list(&pool, user_id, query).await.map(Json)),
+                StatusCode::BAD_REQUEST,
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn page_far_past_the_end_is_empty_rather_than_overflowing() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        test_support::insert_payment(&pool, user_id, "10.00", "USD", "settled").await;
+
+        let body = list(&pool, user_id, "page=2147483647&limit=100")
+            .await
+            .unwrap();
+        assert_eq!(body["transactions"], serde_json::json!([]));
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["next"], serde_json::Value::Null);
+    }
+
+    /// Counts the statements sqlx executes while the returned guard is held, on this
+    /// thread; `#[tokio::test]`'s single-threaded runtime keeps the queries on it.
+    fn count_queries() -> (
+        Arc<std::sync::atomic::AtomicUsize>,
+        tracing::subscriber::DefaultGuard,
+    ) {
+        use tracing_subscriber::layer::{Context, SubscriberExt};
+
+        struct QueryCounter(Arc<std::sync::atomic::AtomicUsize>);
+        impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for QueryCounter {
+            fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+                if event.metadata().target() == "sqlx::query" {
+                    self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                }
+            }
+        }
+
+        let count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let subscriber = tracing_subscriber::registry().with(QueryCounter(count.clone()));
+        (count, tracing::subscriber::set_default(subscriber))
+    }
+
+    #[tokio::test]
+    async fn each_page_is_read_in_one_query() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        for amount in ["1.00", "2.00", "3.00"] {
+            test_support::insert_payment(&pool, user_id, amount, "USD", "settled").await;
+        }
+        let queries = |query: &'static str| {
+            let pool = pool.clone();
+            async move {
+                let (count, _guard) = count_queries();
+                let body = list(&pool, user_id, query).await.unwrap();
+                (count.load(std::sync::atomic::Ordering::SeqCst), body)
+            }
+        };
+
+        let (count, body) = queries("limit=2").await;
+        assert_eq!(count, 1);
+        assert_eq!(body["transactions"].as_array().unwrap().len(), 2);
+        assert_eq!(body["total"], 3);
+
+        // Cursor mode doesn't count at all
+        let (count, body) = queries("limit=2&cursor=").await;
+        assert_eq!(count, 1);
+        assert!(body.get("total").is_none());
+        assert!(body["next_cursor"].is_string());
+
+        // Past the end no row carries the window count, so it is counted separately
+        let (count, body) = queries("limit=2&page=5").await;
+        assert_eq!(count, 2);
+        assert_eq!(body["total"], 3);
+    }
+
+    #[test]
+    fn escape_like_makes_wildcards_literal() {
+        assert_eq!(escape_like("plain"), "plain");
+        assert_eq!(escape_like("50%_off"), "50\\%\\_off");
+        assert_eq!(escape_like("a\\b"), "a\\\\b");
+    }
+
+    #[tokio::test]
+    async fn search_matches_reference_and_email_substrings_literally() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        for (reference, email) in [
+            ("INV_100%", "alice@shop.test"),
+            ("INV-1000", "bob@shop.test"),
+        ] {
+            let id = test_support::insert_payment(&pool, user_id, "10.00", "USD", "pending").await;
+            sqlx::query(
+                "UPDATE transactions SET metadata = jsonb_build_object('reference', $2::text),
+                        customer_email = $3
+                 WHERE id = $1",
+            )
+            .bind(id)
+            .bind(reference)
+            .bind(email)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        let emails = |body: serde_json::Value| -> Vec<String> {
+            let mut emails: Vec<String> = body["transactions"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|t| t["customer_email"].as_str().unwrap().to_string())
+                .collect();
+            emails.sort();
+            emails
+        };
+
+        let body = list(&pool, user_id, "search=_100%25").await.unwrap();
+        assert_eq!(emails(body), ["alice@shop.test"]);
+        let body = list(&pool, user_id, "search=INV").await.unwrap();
+        assert_eq!(emails(body), ["alice@shop.test", "bob@shop.test"]);
+        let body = list(&pool, user_id, "search=BOB@").await.unwrap();
+        assert_eq!(emails(body), ["bob@shop.test"]);
+    }
+
+    #[test]
+    fn filter_errors_say_which_param_is_wrong() {
+        let principal = test_support::principal(Uuid::new_v4());
+        let reject = |query: &str| {
+            let uri: axum::http::Uri = format!("/api/transactions?{}", query).parse().unwrap();
+            let Query(params) = Query::<TransactionQuery>::try_from_uri(&uri).unwrap();
+            let Err(err) = ListFilters::from_query(&params, &principal) else {
+                panic!("{} was accepted", query);
+            };
+            (err.code(), err.message().to_string())
+        };
+
+        assert_eq!(
+            reject("min_amount=ten"),
+            (
+                "invalid_amount",
+                "min_amount must be a decimal number".into()
+            )
+        );
+        assert_eq!(
+            reject("min_amount=100&max_amount=10").0,
+            "invalid_amount_range"
+        );
+        assert_eq!(
+            reject("to=yesterday"),
+            ("invalid_date", "to must be a YYYY-MM-DD date".into())
+        );
+        assert_eq!(
+            reject("from=2026-02-01&to=2026-01-01").0,
+            "invalid_date_range"
+        );
+        assert_eq!(reject("metadata_key=order").0, "invalid_metadata_filter");
+        assert_eq!(reject("metadata_has=").0, "invalid_metadata_filter");
+        assert_eq!(
+            reject("metadata_contains=%5B1%5D").0,
+            "invalid_metadata_filter"
+        );
+        assert_eq!(reject("include_deleted=true").0, "forbidden");
+    }
+
+    #[test]
+    fn cursor_round_trips_to_the_microsecond() {
+        let created_at = NaiveDate::from_ymd_opt(2026, 3, 4)
+            .unwrap()
+            .and_hms_micro_opt(5, 6, 7, 891_011)
+            .unwrap();
+        let id = Uuid::new_v4();
+        let cursor = encode_cursor(created_at, id);
+
+        assert!(!cursor.contains(['+', '/', '=']));
+        assert_eq!(decode_cursor(&cursor), Some((created_at, id)));
+        assert_eq!(parse_cursor(&cursor).unwrap(), (created_at, id));
+    }
+
+    #[test]
+    fn cursor_rejects_anything_not_produced_by_encode() {
+        let encoded = |raw: &str| URL_SAFE_NO_PAD.encode(raw);
+        let id = Uuid::new_v4();
+
+        assert_eq!(decode_cursor("not base64!"), None);
+        assert_eq!(decode_cursor(&URL_SAFE_NO_PAD.encode([0xff, 0xfe])), None);
+        assert_eq!(
+            decode_cursor(&encoded(&format!("1700000000000000{}", id))),
+            None
+        );
+        assert_eq!(decode_cursor(&encoded("1700000000000000|not-a-uuid")), None);
+        assert_eq!(decode_cursor(&encoded(&format!("yesterday|{}", id))), None);
+        assert_eq!(decode_cursor(""), None);
+        assert_eq!(
+            parse_cursor("not base64!").unwrap_err().code(),
+            "invalid_cursor"
+        );
+    }
+
+    #[test]
+    fn csv_field_quotes_only_when_needed() {
+        assert_eq!(csv_field("plain@example.com"), "plain@example.com");
+        assert!(matches!(csv_field("plain"), Cow::Borrowed(_)));
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[tokio::test]
+    async fn export_writes_the_header_and_honors_filters() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let settled = test_support::insert_payment(&pool, user_id, "10.00", "USD", "settled").await;
+        let pending = test_support::insert_payment(&pool, user_id, "20.00", "USD", "pending").await;
+        sqlx::query(
+            "UPDATE transactions SET customer_email = 'last, first@example.com' WHERE id = $1",
+        )
+        .bind(settled)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let uri: Uri = "/api/transactions/export?filter=settled".parse().unwrap();
+        let response = export_transactions(
+            State(pool.clone()),
+            Extension(test_support::principal(user_id)),
+            Query::try_from_uri(&uri).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"transactions.csv\""
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let csv = String::from_utf8(body.to_vec()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], CSV_HEADER.trim_end());
+        assert_eq!(lines.len(), 2, "{}", csv);
+        assert!(lines[1].starts_with(&format!("{},payment,", settled)));
+        assert!(lines[1].ends_with(",\"last, first@example.com\""));
+        assert!(!csv.contains(&pending.to_string()));
+    }
+
+    fn if_none_match_headers(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(header::IF_NONE_MATCH, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn etag_is_weak_and_tracks_every_input() {
+        let id = Uuid::new_v4();
+        let at = NaiveDate::from_ymd_opt(2026, 1, 2)
+            .unwrap()
+            .and_hms_micro_opt(3, 4, 5, 6)
+            .unwrap();
+        let etag = transaction_etag(id, "pending", at, false, false);
+
+        assert!(etag.starts_with("W/\"") && etag.ends_with('"'));
+        assert_eq!(etag.len(), "W/\"\"".len() + 32);
+        assert_eq!(etag, transaction_etag(id, "pending", at, false, false));
+
+        let changed = [
+            transaction_etag(Uuid::new_v4(), "pending", at, false, false),
+            transaction_etag(id, "settled", at, false, false),
+            transaction_etag(
+                id,
+                "pending",
+                at + chrono::Duration::microseconds(1),
+                false,
+                false,
+            ),
+            transaction_etag(id, "pending", at, true, false),
+            transaction_etag(id, "pending", at, false, true),
+        ];
+        assert!(changed.iter().all(|other| *other != etag));
+    }
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        let etag = r#"W/"0123abcd""#;
+
+        assert!(if_none_match(&if_none_match_headers(&[etag]), etag));
+        // Weak comparison ignores the W/ prefix on either side
+        assert!(if_none_match(
+            &if_none_match_headers(&[r#""0123abcd""#]),
+            etag
+        ));
+        assert!(if_none_match(
+            &if_none_match_headers(&[r#""other", W/"0123abcd""#]),
+            etag
+        ));
+        assert!(if_none_match(
+            &if_none_match_headers(&[r#""other""#, etag]),
+            etag
+        ));
+
+        assert!(!if_none_match(&HeaderMap::new(), etag));
+        assert!(!if_none_match(
+            &if_none_match_headers(&[r#"W/"0123abce""#]),
+            etag
+        ));
+    }
+
+    #[test]
+    fn if_none_match_star_matches_any_tag() {
+        assert!(if_none_match(
+            &if_none_match_headers(&["*"]),
+            r#"W/"anything""#
+        ));
+        assert!(if_none_match(
+            &if_none_match_headers(&[r#""other", *"#]),
+            r#"W/"anything""#
+        ));
+    }
+
+    #[tokio::test]
+    async fn get_transaction_returns_304_for_a_matching_etag() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let id = test_support::insert_payment(&pool, user_id, "12.00", "USD", "pending").await;
+        let fetch = |headers: HeaderMap| {
+            get_transaction(
+                State(pool.clone()),
+                State(test_support::retry()),
+                Extension(test_support::principal(user_id)),
+                Path(id),
+                Query(TransactionDetailQuery {
+                    include_deleted: None,
+                    format: None,
+                }),
+                headers,
+            )
+        };
+
+        let first = fetch(HeaderMap::new()).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let etag = first.headers()[header::ETAG].to_str().unwrap().to_string();
+
+        let cached = fetch(if_none_match_headers(&[&etag])).await.unwrap();
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(cached.headers()[header::ETAG], etag.as_str());
+
+        let any = fetch(if_none_match_headers(&["*"])).await.unwrap();
+        assert_eq!(any.status(), StatusCode::NOT_MODIFIED);
+
+        sqlx::query("UPDATE transactions SET status = 'settled' WHERE id = $1")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let changed = fetch(if_none_match_headers(&[&etag])).await.unwrap();
+        assert_eq!(changed.status(), StatusCode::OK);
+        assert_ne!(changed.headers()[header::ETAG], etag.as_str());
+    }
+
+    fn normalized(uri: &str) -> String {
+        normalized_query(&uri.parse::<Uri>().unwrap())
+    }
+
+    #[test]
+    fn normalized_query_ignores_parameter_order() {
+        assert_eq!(
+            normalized("/api/transactions?status=pending&page=2&limit=10"),
+            normalized("/api/transactions?limit=10&page=2&status=pending"),
+        );
+        assert_eq!(
+            normalized("/api/transactions?status=pending&page=2&limit=10"),
+            "limit=10&page=2&status=pending"
+        );
+    }
+
+    #[test]
+    fn normalized_query_decodes_before_comparing() {
+        assert_eq!(
+            normalized("/api/transactions?search=a%20b&currency=USD"),
+            normalized("/api/transactions?currency=US%44&search=a+b"),
+        );
+        assert_ne!(
+            normalized("/api/transactions?currency=USD"),
+            normalized("/api/transactions?currency=EUR"),
+        );
+    }
+
+    #[test]
+    fn normalized_query_of_no_query_is_empty() {
+        assert_eq!(normalized("/api/transactions"), "");
+        assert_eq!(normalized("/api/transactions?"), "");
+    }
+}
@@ -1,10 +1,11 @@
-use crate::handlers::auth::Claims;
+use crate::db::RetryPolicy;
+use crate::middleware::auth::Principal;
 use axum::{extract::State, http::StatusCode, Extension, Json};
 use serde::Serialize;
 use sqlx::PgPool;
-use uuid::Uuid;
+use utoipa::ToSchema;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct TreasuryPosition {
     pub name: String,
     pub protocol: String,
@@ -13,30 +14,45 @@ pub struct TreasuryPosition {
     pub apy: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct TreasuryPortfolio {
     pub total_value: f64,
     pub assets: Vec<TreasuryPosition>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/treasury/positions",
+    tag = "treasury",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Treasury positions ordered by USD value", body = Vec<TreasuryPosition>),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error")
+    )
+)]
 pub async fn get_positions(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
 ) -> Result<Json<Vec<TreasuryPosition>>, StatusCode> {
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let user_id = principal.user_id;
 
-    let rows = sqlx::query!(
-        r#"
-        SELECT asset, protocol, balance, apy, usd_value
-        FROM treasury_positions
-        WHERE user_id = $1
-        ORDER BY usd_value DESC NULLS LAST
-        "#,
-        user_id
-    )
-    .fetch_all(&pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let rows = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT asset, protocol, balance, apy, usd_value
+                FROM treasury_positions
+                WHERE user_id = $1
+                ORDER BY usd_value DESC NULLS LAST
+                "#,
+                user_id
+            )
+            .fetch_all(&pool)
+        })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let positions: Vec<TreasuryPosition> = rows
         .into_iter()
@@ -58,24 +74,39 @@ pub async fn get_positions(
     Ok(Json(positions))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/treasury/portfolio",
+    tag = "treasury",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Portfolio total and assets", body = TreasuryPortfolio),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error")
+    )
+)]
 pub async fn get_portfolio(
     State(pool): State<PgPool>,
-    Extension(claims): Extension<Claims>,
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
 ) -> Result<Json<TreasuryPortfolio>, StatusCode> {
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let user_id = principal.user_id;
 
-    let rows = sqlx::query!(
-        r#"
-        SELECT asset, protocol, balance, apy, usd_value
-        FROM treasury_positions
-        WHERE user_id = $1
-        ORDER BY usd_value DESC NULLS LAST
-        "#,
-        user_id
-    )
-    .fetch_all(&pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let rows = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT asset, protocol, balance, apy, usd_value
+                FROM treasury_positions
+                WHERE user_id = $1
+                ORDER BY usd_value DESC NULLS LAST
+                "#,
+                user_id
+            )
+            .fetch_all(&pool)
+        })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let assets: Vec<TreasuryPosition> = rows
         .iter()
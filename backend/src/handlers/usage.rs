@@ -0,0 +1,87 @@
+use crate::config::QuotaConfig;
+use crate::db::RetryPolicy;
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::auth::Principal;
+use axum::{extract::State, Extension, Json};
+use chrono::{Months, NaiveDate};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Request consumption for the current calendar month (UTC).
+#[derive(Serialize, ToSchema)]
+pub struct UsageResponse {
+    /// Month being counted, as `YYYY-MM`
+    pub period: String,
+    pub used: i64,
+    /// Monthly quota; absent when the user is unlimited
+    pub limit: Option<i64>,
+    pub remaining: Option<i64>,
+    /// Start of the next month, when the counter resets
+    pub resets_at: String,
+}
+
+/// Current month's counter and effective quota: the user's plan limit, else the default.
+pub(crate) async fn monthly_usage(
+    pool: &PgPool,
+    user_id: Uuid,
+    quota: &QuotaConfig,
+) -> Result<UsageResponse, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT date_trunc('month', NOW() AT TIME ZONE 'UTC')::date AS "period!",
+               COALESCE(u.monthly_request_quota, $2) AS monthly_limit,
+               COALESCE(c.request_count, 0) AS "used!"
+        FROM users u
+        LEFT JOIN usage_counters c
+          ON c.user_id = u.id AND c.period = date_trunc('month', NOW() AT TIME ZONE 'UTC')::date
+        WHERE u.id = $1
+        "#,
+        user_id,
+        quota.monthly_requests
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let resets_at = row
+        .period
+        .checked_add_months(Months::new(1))
+        .unwrap_or(NaiveDate::MAX)
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default();
+
+    Ok(UsageResponse {
+        period: row.period.format("%Y-%m").to_string(),
+        used: row.used,
+        limit: row.monthly_limit,
+        remaining: row.monthly_limit.map(|limit| (limit - row.used).max(0)),
+        resets_at: resets_at.to_string(),
+    })
+}
+
+/// Shows this month's API request count against the quota; calls to it aren't counted.
+#[utoipa::path(
+    get,
+    path = "/api/usage",
+    tag = "usage",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Current month's request usage", body = UsageResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_usage(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    State(quota): State<Arc<QuotaConfig>>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<UsageResponse>, ApiError> {
+    let usage = retry
+        .run(|| monthly_usage(&pool, principal.user_id, &quota))
+        .await?;
+
+    Ok(Json(usage))
+}
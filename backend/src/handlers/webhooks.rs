@@ -0,0 +1,406 @@
+use crate::config::WebhookConfig;
+use crate::db::RetryPolicy;
+use crate::error::{ApiError, ErrorBody};
+use crate::middleware::auth::Principal;
+use crate::webhooks;
+use axum::{extract::State, Extension, Json};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WebhookDeliverySettings {
+    /// `ordered` (default) delivers one event at a time in order; `parallel` trades order for throughput
+    pub delivery_mode: webhooks::DeliveryMode,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RotateWebhookSecretResponse {
+    /// The new signing secret; it is only returned once
+    pub secret: String,
+    pub algorithm: String,
+    /// Until when the replaced secret still verifies; null when there was none
+    pub previous_expires_at: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyWebhookSignatureRequest {
+    /// Raw delivery body, exactly as received
+    pub payload: String,
+    /// `Bytus-Signature` header that came with the delivery
+    pub signature: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VerifyWebhookSignatureResponse {
+    pub valid: bool,
+    /// Which secret matched: `current` or `previous`
+    pub matched: Option<String>,
+}
+
+/// Replaces the caller's webhook signing secret. Deliveries are signed with the new one
+/// from now on, and also with the old one for `WEBHOOK_SECRET_GRACE_SECS`, during which
+/// it keeps verifying through `/api/webhooks/verify` too. Rotating again within that
+/// window retires the older secret at once.
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/secret/rotate",
+    tag = "webhooks",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "New secret, shown only this once", body = RotateWebhookSecretResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn rotate_webhook_secret(
+    State(pool): State<PgPool>,
+    State(webhook_config): State<Arc<WebhookConfig>>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<RotateWebhookSecretResponse>, ApiError> {
+    let secret = webhooks::generate_secret();
+
+    let previous_expires_at = sqlx::query_scalar!(
+        r#"
+        INSERT INTO webhook_secrets (user_id, secret, rotated_at)
+        VALUES ($1, $2, NOW())
+        ON CONFLICT (user_id) DO UPDATE
+        SET previous_secret = webhook_secrets.secret,
+            previous_expires_at = NOW() + make_interval(secs => $3),
+            secret = EXCLUDED.secret,
+            rotated_at = NOW()
+        RETURNING previous_expires_at
+        "#,
+        principal.user_id,
+        secret,
+        webhook_config.secret_grace.as_secs_f64()
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    Ok(Json(RotateWebhookSecretResponse {
+        secret,
+        algorithm: webhooks::ALGORITHM.to_string(),
+        previous_expires_at: previous_expires_at.map(|t| t.to_string()),
+    }))
+}
+
+/// Checks a delivery signature against the caller's current secret and, during the
+/// grace window, the previous one; useful for testing a receiver after rotating.
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/verify",
+    tag = "webhooks",
+    request_body = VerifyWebhookSignatureRequest,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Whether the signature matches an active secret", body = VerifyWebhookSignatureResponse),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn verify_webhook_signature(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
+    Json(payload): Json<VerifyWebhookSignatureRequest>,
+) -> Result<Json<VerifyWebhookSignatureResponse>, ApiError> {
+    let secrets = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT secret,
+                       CASE WHEN previous_expires_at > NOW() THEN previous_secret END AS previous_secret
+                FROM webhook_secrets
+                WHERE user_id = $1
+                "#,
+                principal.user_id
+            )
+            .fetch_optional(&pool)
+        })
+        .await?;
+
+    let body = payload.payload.as_bytes();
+    let matched = secrets.and_then(|secrets| {
+        if webhooks::verify(&secrets.secret, body, &payload.signature) {
+            Some("current")
+        } else if secrets
+            .previous_secret
+            .is_some_and(|previous| webhooks::verify(&previous, body, &payload.signature))
+        {
+            Some("previous")
+        } else {
+            None
+        }
+    });
+
+    Ok(Json(VerifyWebhookSignatureResponse {
+        valid: matched.is_some(),
+        matched: matched.map(str::to_string),
+    }))
+}
+
+/// Returns how the caller's webhook deliveries are dispatched.
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/delivery",
+    tag = "webhooks",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Current delivery mode", body = WebhookDeliverySettings),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_webhook_delivery(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<WebhookDeliverySettings>, ApiError> {
+    let mode = retry
+        .run(|| {
+            sqlx::query_scalar!(
+                "SELECT webhook_delivery_mode FROM users WHERE id = $1",
+                principal.user_id
+            )
+            .fetch_one(&pool)
+        })
+        .await?;
+
+    Ok(Json(WebhookDeliverySettings {
+        delivery_mode: webhooks::DeliveryMode::from_db(&mode),
+    }))
+}
+
+/// Switches the caller's endpoint between ordered-serial and parallel delivery.
+#[utoipa::path(
+    put,
+    path = "/api/webhooks/delivery",
+    tag = "webhooks",
+    request_body = WebhookDeliverySettings,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Updated delivery mode", body = WebhookDeliverySettings),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 422, description = "Unknown delivery mode"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn update_webhook_delivery(
+    State(pool): State<PgPool>,
+    Extension(principal): Extension<Principal>,
+    Json(payload): Json<WebhookDeliverySettings>,
+) -> Result<Json<WebhookDeliverySettings>, ApiError> {
+    sqlx::query!(
+        "UPDATE users SET webhook_delivery_mode = $1 WHERE id = $2",
+        payload.delivery_mode.as_str(),
+        principal.user_id
+    )
+    .execute(&pool)
+    .await?;
+
+    Ok(Json(payload))
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WebhookEndpoint {
+    /// Absolute `http`/`https` URL events are POSTed to; null stops deliveries and
+    /// leaves new events queued
+    pub url: Option<String>,
+}
+
+/// Longest accepted endpoint URL, in bytes.
+const MAX_WEBHOOK_URL_LEN: usize = 2048;
+
+/// Trims `url` and checks it is an absolute http(s) URL with a host.
+fn validate_webhook_url(url: &str) -> Result<String, ApiError> {
+    let url = url.trim();
+    let valid = url.len() <= MAX_WEBHOOK_URL_LEN
+        && reqwest::Url::parse(url).is_ok_and(|parsed| {
+            matches!(parsed.scheme(), "http" | "https") && parsed.host().is_some()
+        });
+
+    if valid {
+        Ok(url.to_string())
+    } else {
+        Err(ApiError::bad_request(
+            "invalid_webhook_url",
+            format!(
+                "url must be an absolute http or https URL of at most {} bytes",
+                MAX_WEBHOOK_URL_LEN
+            ),
+        ))
+    }
+}
+
+/// Returns the URL the caller's webhook events are delivered to.
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/endpoint",
+    tag = "webhooks",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Current endpoint", body = WebhookEndpoint),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn get_webhook_endpoint(
+    State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<WebhookEndpoint>, ApiError> {
+    let url = retry
+        .run(|| {
+            sqlx::query_scalar!(
+                "SELECT webhook_url FROM users WHERE id = $1",
+                principal.user_id
+            )
+            .fetch_one(&pool)
+        })
+        .await?;
+
+    Ok(Json(WebhookEndpoint { url }))
+}
+
+/// Sets where the caller's webhook events are POSTed. Each delivery carries the event
+/// as JSON with its `event_id` in `Bytus-Event-Id` and the hex HMAC-SHA256 of the body
+/// in `Bytus-Signature`. Nothing is sent until a signing secret has been created with
+/// `POST /api/webhooks/secret/rotate`; events recorded meanwhile stay queued.
+#[utoipa::path(
+    put,
+    path = "/api/webhooks/endpoint",
+    tag = "webhooks",
+    request_body = WebhookEndpoint,
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Updated endpoint", body = WebhookEndpoint),
+        (status = 400, description = "`invalid_webhook_url`", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    )
+)]
+pub async fn update_webhook_endpoint(
+    State(pool): State<PgPool>,
+    Extension(principal): Extension<Principal>,
+    Json(payload): Json<WebhookEndpoint>,
+) -> Result<Json<WebhookEndpoint>, ApiError> {
+    let url = payload
+        .url
+        .as_deref()
+        .map(validate_webhook_url)
+        .transpose()?;
+
+    sqlx::query!(
+        "UPDATE users SET webhook_url = $1 WHERE id = $2",
+        url,
+        principal.user_id
+    )
+    .execute(&pool)
+    .await?;
+
+    Ok(Json(WebhookEndpoint { url }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support;
+    use std::time::Duration;
+
+    fn config(grace_secs: u64) -> Arc<WebhookConfig> {
+        Arc::new(WebhookConfig {
+            secret_grace: Duration::from_secs(grace_secs),
+            ..WebhookConfig::from_env()
+        })
+    }
+
+    async fn rotate(pool: &PgPool, user_id: uuid::Uuid, grace_secs: u64) -> String {
+        let Json(rotated) = rotate_webhook_secret(
+            State(pool.clone()),
+            State(config(grace_secs)),
+            Extension(test_support::principal(user_id)),
+        )
+        .await
+        .unwrap();
+        rotated.secret
+    }
+
+    async fn matched(pool: &PgPool, user_id: uuid::Uuid, secret: &str) -> Option<String> {
+        let payload = r#"{"event":"payment.settled"}"#;
+        let Json(result) = verify_webhook_signature(
+            State(pool.clone()),
+            State(test_support::retry()),
+            Extension(test_support::principal(user_id)),
+            Json(VerifyWebhookSignatureRequest {
+                payload: payload.to_string(),
+                signature: webhooks::sign(secret, payload.as_bytes()),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.valid, result.matched.is_some());
+        result.matched
+    }
+
+    #[tokio::test]
+    async fn rotation_keeps_previous_secret_valid_during_grace() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+
+        let first = rotate(&pool, user_id, 3600).await;
+        let second = rotate(&pool, user_id, 3600).await;
+
+        assert_ne!(first, second);
+        assert_eq!(
+            matched(&pool, user_id, &second).await.as_deref(),
+            Some("current")
+        );
+        assert_eq!(
+            matched(&pool, user_id, &first).await.as_deref(),
+            Some("previous")
+        );
+        assert_eq!(matched(&pool, user_id, "whsec_unrelated").await, None);
+    }
+
+    #[tokio::test]
+    async fn previous_secret_stops_verifying_once_grace_has_passed() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+
+        let first = rotate(&pool, user_id, 0).await;
+        let second = rotate(&pool, user_id, 0).await;
+
+        assert_eq!(
+            matched(&pool, user_id, &second).await.as_deref(),
+            Some("current")
+        );
+        assert_eq!(matched(&pool, user_id, &first).await, None);
+    }
+
+    #[test]
+    fn webhook_url_must_be_absolute_http() {
+        assert_eq!(
+            validate_webhook_url(" https://shop.example/hooks?v=1 ").unwrap(),
+            "https://shop.example/hooks?v=1"
+        );
+        assert!(validate_webhook_url("http://127.0.0.1:9000/hook").is_ok());
+
+        let too_long = format!("https://shop.example/{}", "a".repeat(MAX_WEBHOOK_URL_LEN));
+        for url in [
+            "",
+            "/hooks",
+            "ftp://shop.example/hook",
+            "https://",
+            &too_long,
+        ] {
+            let err = validate_webhook_url(url).unwrap_err();
+            assert_eq!(err.code(), "invalid_webhook_url", "{}", url);
+        }
+    }
+}
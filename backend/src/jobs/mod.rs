@@ -0,0 +1,317 @@
+use crate::events::{TransactionEvent, TransactionEventKind};
+use crate::handlers::payments::{
+    bus_lock_rate, settle_pending_payment, Settlement, BUS_LOCK_SCALE,
+};
+use crate::state::AppState;
+use crate::webhooks;
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How often the auto-settler looks for payments old enough to settle.
+const AUTO_SETTLE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Most payments the auto-settler settles per tick.
+const AUTO_SETTLE_BATCH: i64 = 100;
+
+/// Spawns the background task that fails pending payments past their `expires_at`.
+pub fn spawn_expiry_sweeper(state: AppState) {
+    let interval = state.payments.expiry_sweep_interval;
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            match expire_pending_payments(&state.pool).await {
+                Ok(expired) => {
+                    if !expired.is_empty() {
+                        tracing::info!(count = expired.len(), "expired stale pending payments");
+                    }
+                    for event in expired {
+                        state.transaction_list_cache.invalidate_user(event.user_id);
+                        state.events.publish(event);
+                    }
+                }
+                Err(e) => tracing::warn!(error = %e, "payment expiry sweep failed"),
+            }
+        }
+    });
+}
+
+/// Marks expired pending payments `failed`, unlocks their BUS lock share and records
+/// the recalculation and webhook event in one statement, so a crash mid-sweep can't
+/// leave them out of step.
+pub async fn expire_pending_payments(pool: &PgPool) -> Result<Vec<TransactionEvent>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        WITH expired AS (
+            UPDATE transactions
+            SET status = 'failed',
+                metadata = COALESCE(metadata, '{}'::jsonb)
+                    || jsonb_build_object('failure_reason', 'expired')
+            WHERE status = 'pending' AND expires_at <= NOW()
+            RETURNING id, user_id, amount, currency, status
+        ),
+        released AS (
+            UPDATE bus_locks b
+            SET locked_amount = GREATEST(b.locked_amount - r.total, 0),
+                version = b.version + 1,
+                last_calculated_at = NOW(), updated_at = NOW()
+            FROM (
+                -- Per-payment share truncated like payments::bus_lock_share, then summed
+                SELECT user_id, currency, SUM(TRUNC(amount * $2, $3)) AS total
+                FROM expired
+                GROUP BY user_id, currency
+            ) r
+            WHERE b.user_id = r.user_id AND b.currency = r.currency
+            RETURNING b.user_id, b.currency, b.locked_amount, b.required_amount
+        ),
+        history AS (
+            INSERT INTO bus_lock_history (user_id, currency, locked_amount, required_amount, trigger)
+            SELECT user_id, currency, locked_amount, required_amount, 'expiry' FROM released
+        ),
+        audit AS (
+            INSERT INTO audit_log (transaction_id, action, old_status, new_status)
+            SELECT id, 'status_changed', 'pending', status FROM expired
+        ),
+        recorded AS (
+            INSERT INTO webhook_events (user_id, transaction_id, event_type, status)
+            SELECT user_id, id, $1, status FROM expired WHERE user_id IS NOT NULL
+            ON CONFLICT (transaction_id, status) DO UPDATE SET event_type = EXCLUDED.event_type
+            RETURNING id, transaction_id
+        )
+        SELECT x.id AS "id!", x.user_id, x.amount AS "amount!", x.currency AS "currency!",
+               x.status AS "status!", r.id AS "event_id?"
+        FROM expired x
+        LEFT JOIN recorded r ON r.transaction_id = x.id
+        "#,
+        TransactionEventKind::StatusChanged.as_str(),
+        bus_lock_rate(),
+        BUS_LOCK_SCALE as i32
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let occurred_at = chrono::Utc::now().naive_utc().to_string();
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let user_id: Uuid = row.user_id?;
+            Some(TransactionEvent {
+                event_id: row.event_id?,
+                kind: TransactionEventKind::StatusChanged,
+                user_id,
+                transaction_id: row.id.to_string(),
+                status: row.status,
+                amount: row.amount.to_string(),
+                currency: row.currency,
+                occurred_at: occurred_at.clone(),
+            })
+        })
+        .collect())
+}
+
+/// Spawns the sandbox worker that settles pending payments once they are older than
+/// `AUTO_SETTLE_DELAY_SECS`. Does nothing unless `AUTO_SETTLE=true`, and panics when
+/// that is combined with `APP_ENV=production` so a deployment can't settle unpaid payments.
+pub fn spawn_auto_settler(state: AppState) {
+    let Some(delay) = state.payments.auto_settle_after else {
+        return;
+    };
+    assert!(
+        !std::env::var("APP_ENV").is_ok_and(|v| v.eq_ignore_ascii_case("production")),
+        "AUTO_SETTLE must not be enabled with APP_ENV=production"
+    );
+    tracing::warn!(
+        delay_secs = delay.as_secs(),
+        "AUTO_SETTLE is enabled; pending payments will settle without a processor"
+    );
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(AUTO_SETTLE_POLL_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            match auto_settle_payments(&state, delay).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!(count, "auto-settled pending payments"),
+                Err(e) => tracing::warn!(error = %e, "auto-settle run failed"),
+            }
+        }
+    });
+}
+
+/// Spawns the worker that POSTs queued `webhook_events` to merchants' endpoints; see
+/// [`webhooks::delivery::deliver_due_events`].
+pub fn spawn_webhook_deliverer(state: AppState) {
+    let client = webhooks::delivery::client(&state.webhooks);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(state.webhooks.poll_interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            match webhooks::delivery::deliver_due_events(&state.pool, &client, &state.webhooks)
+                .await
+            {
+                Ok(0) => {}
+                Ok(count) => tracing::debug!(count, "delivered webhook events"),
+                Err(e) => tracing::warn!(error = %e, "webhook delivery run failed"),
+            }
+        }
+    });
+}
+
+/// Settles due payments one at a time through the same path as `POST /settle`, so each
+/// status change and BUS lock requirement increase commit together. A payment is due once it is older
+/// than both the auto-settle delay and its currency's settlement hold.
+async fn auto_settle_payments(state: &AppState, delay: Duration) -> Result<usize, sqlx::Error> {
+    let payments = &state.payments;
+    let (hold_currencies, hold_secs): (Vec<String>, Vec<f64>) = payments
+        .settlement_hold_by_currency
+        .iter()
+        .map(|(currency, hold)| (currency.clone(), hold.as_secs_f64()))
+        .unzip();
+
+    let due = sqlx::query!(
+        r#"
+        SELECT t.id, t.user_id AS "user_id!"
+        FROM transactions t
+        LEFT JOIN UNNEST($3::text[], $4::float8[]) AS h(currency, secs) ON h.currency = UPPER(t.currency)
+        WHERE t.tx_type = 'payment' AND t.status = 'pending' AND t.user_id IS NOT NULL
+          AND t.created_at <= NOW() - make_interval(secs => GREATEST($1, COALESCE(h.secs, $5)))
+          AND (t.expires_at IS NULL OR t.expires_at > NOW())
+        ORDER BY t.created_at, t.id
+        LIMIT $2
+        "#,
+        delay.as_secs_f64(),
+        AUTO_SETTLE_BATCH,
+        &hold_currencies,
+        &hold_secs,
+        payments.settlement_hold.as_secs_f64()
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let mut settled_count = 0;
+    for payment in due {
+        // Anything but `Settled` means it was settled or expired concurrently; nothing to do
+        if let Settlement::Settled(settled) =
+            settle_pending_payment(&state.pool, payments, payment.user_id, payment.id, None).await?
+        {
+            state
+                .transaction_list_cache
+                .invalidate_user(payment.user_id);
+            state.events.publish(settled.event(payment.user_id));
+            settled_count += 1;
+        }
+    }
+
+    Ok(settled_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PaymentConfig;
+    use crate::db::test_support;
+    use crate::handlers::payments::bus_lock_share;
+    use bigdecimal::BigDecimal;
+
+    async fn status_of(pool: &PgPool, id: Uuid) -> String {
+        sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sweep_fails_expired_pending_payments_only() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let old =
+            test_support::insert_payment(&pool, user_id, "10.12345678", "USD", "pending").await;
+        let fresh =
+            test_support::insert_payment(&pool, user_id, "20.98765432", "USD", "pending").await;
+        sqlx::query(
+            "UPDATE transactions
+             SET expires_at = CASE WHEN id = $1 THEN NOW() - INTERVAL '1 minute'
+                                   ELSE NOW() + INTERVAL '1 hour' END
+             WHERE id = ANY($2)",
+        )
+        .bind(old)
+        .bind(vec![old, fresh])
+        .execute(&pool)
+        .await
+        .unwrap();
+        let share = |amount: &str| bus_lock_share(&amount.parse().unwrap());
+        sqlx::query(
+            "INSERT INTO bus_locks (id, user_id, currency, locked_amount, required_amount, last_calculated_at, created_at, updated_at)
+             VALUES ($1, $2, 'USD', $3, 0, NOW(), NOW(), NOW())",
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(share("10.12345678") + share("20.98765432"))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let events = expire_pending_payments(&pool).await.unwrap();
+
+        assert!(events
+            .iter()
+            .any(|e| e.transaction_id == old.to_string() && e.status == "failed"));
+        assert!(!events.iter().any(|e| e.transaction_id == fresh.to_string()));
+        assert_eq!(status_of(&pool, old).await, "failed");
+        assert_eq!(status_of(&pool, fresh).await, "pending");
+
+        // Exactly the expired payment's share is released, truncated like bus_lock_share
+        let locked: BigDecimal =
+            sqlx::query_scalar("SELECT locked_amount FROM bus_locks WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(locked, share("20.98765432"));
+    }
+
+    #[tokio::test]
+    async fn expired_payment_cannot_be_settled_before_the_sweep() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        let id = test_support::insert_payment(&pool, user_id, "10.00", "USD", "pending").await;
+        sqlx::query(
+            "UPDATE transactions SET expires_at = NOW() - INTERVAL '1 second' WHERE id = $1",
+        )
+        .bind(id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let outcome = settle_pending_payment(
+            &pool,
+            &PaymentConfig::from_env(),
+            user_id,
+            id,
+            Some(user_id),
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(outcome, Settlement::NotPending));
+        assert_eq!(status_of(&pool, id).await, "pending");
+    }
+}
@@ -1,25 +1,52 @@
+mod cache;
 mod config;
+mod currency;
 mod db;
+mod error;
+mod events;
+mod extract;
+mod fees;
 mod handlers;
+mod jobs;
 mod middleware;
 mod models;
+mod receipts;
 mod routes;
+mod state;
+mod webhooks;
+
+#[cfg(all(feature = "test-endpoints", not(debug_assertions)))]
+compile_error!("the test-endpoints feature is for debug builds only");
+
+use axum::Router;
+use std::future::{Future, IntoFuture};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::oneshot;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().ok();
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "backend=debug,tower_http=debug".into()),
-        )
-        .init();
+    init_tracing(config::LogFormat::from_env());
+
+    let server_config = config::ServerConfig::from_env();
+    let database_config = config::DatabaseConfig::from_env();
+
+    let pool = match db::create_pool(&database_config).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let pool = sqlx::PgPool::connect(&database_url).await?;
+    let state = state::AppState::new(pool);
+    jobs::spawn_expiry_sweeper(state.clone());
+    jobs::spawn_auto_settler(state.clone());
+    jobs::spawn_webhook_deliverer(state.clone());
 
-    let app = routes::create_router(pool);
+    let app = routes::create_router(state, &server_config);
 
     let port = std::env::var("PORT").unwrap_or_else(|_| "8000".to_string());
     let addr = format!("0.0.0.0:{}", port);
@@ -27,7 +54,172 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Server running on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+
+    serve(
+        listener,
+        app,
+        shutdown_signal(),
+        server_config.shutdown_timeout,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Serves `app` until `shutdown` resolves, then gives in-flight requests `grace` to
+/// finish. Connections still open after that are dropped along with the runtime once
+/// this returns.
+async fn serve(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+    grace: Duration,
+) -> std::io::Result<()> {
+    // Notified once a shutdown signal arrives so the grace period can start
+    let (draining_tx, draining_rx) = oneshot::channel();
+    // Connect info gives handlers the peer address when no proxy header is trusted
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        shutdown.await;
+        let _ = draining_tx.send(());
+    })
+    .into_future();
+
+    let grace_period = async move {
+        if draining_rx.await.is_err() {
+            // Server stopped without a shutdown signal; let `server` report it
+            std::future::pending::<()>().await;
+        }
+        tracing::info!(
+            "Shutdown signal received, draining in-flight requests (timeout {}s)",
+            grace.as_secs()
+        );
+        tokio::time::sleep(grace).await;
+    };
+
+    tokio::select! {
+        result = server => {
+            result?;
+            tracing::info!("Server shut down gracefully");
+        }
+        _ = grace_period => {
+            tracing::warn!(
+                "Graceful shutdown timed out after {}s, dropping remaining connections",
+                grace.as_secs()
+            );
+        }
+    }
 
     Ok(())
 }
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Installs the global subscriber; `RUST_LOG` overrides the default filter in both formats.
+fn init_tracing(format: config::LogFormat) {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "backend=debug,tower_http=debug".into());
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match format {
+        config::LogFormat::Json => builder.json().flatten_event(true).init(),
+        config::LogFormat::Pretty => builder.init(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    async fn sleep_for(millis: u64) -> &'static str {
+        tokio::time::sleep(Duration::from_millis(millis)).await;
+        "done"
+    }
+
+    /// Sends `GET path` and returns whatever arrives before the connection closes.
+    async fn request(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                format!(
+                    "GET {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n",
+                    path
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        let _ =
+            tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response)).await;
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[tokio::test]
+    async fn shutdown_drains_requests_within_the_grace_period_only() {
+        let app = Router::new()
+            .route("/fast", get(|| sleep_for(100)))
+            .route("/slow", get(|| sleep_for(3000)));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+        // Own runtime, like `main`: dropping it after `serve` returns kills leftover connections
+        let server = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                let shutdown = async {
+                    let _ = shutdown_rx.await;
+                };
+                serve(listener, app, shutdown, Duration::from_millis(500)).await
+            })
+        });
+
+        let fast = tokio::spawn(request(addr, "/fast"));
+        let slow = tokio::spawn(request(addr, "/slow"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown_tx.send(()).unwrap();
+
+        let fast = fast.await.unwrap();
+        let slow = slow.await.unwrap();
+        server.join().unwrap().unwrap();
+
+        assert!(
+            fast.starts_with("HTTP/1.1 200"),
+            "fast request got {:?}",
+            fast
+        );
+        assert!(fast.ends_with("done"));
+        assert!(slow.is_empty(), "slow request got {:?}", slow);
+    }
+}
@@ -1,39 +1,277 @@
+use crate::config::JwtConfig;
+use crate::db::RetryPolicy;
+use crate::handlers::api_keys::validate_api_key;
 use crate::handlers::auth::Claims;
 use axum::{
     extract::{Request, State},
     http::StatusCode,
     middleware::Next,
     response::Response,
+    Extension,
 };
 use jsonwebtoken::{decode, DecodingKey, Validation};
+use serde::Serialize;
 use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
 
+/// Header carrying an API key; `Authorization: Bearer sk_live_...` is accepted too.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+const API_KEY_PREFIX: &str = "sk_live_";
+
+/// `users.role` value that grants access to `/api/admin` routes.
+pub const ADMIN_ROLE: &str = "admin";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    Jwt,
+    ApiKey,
+}
+
+/// Authenticated caller, attached to request extensions regardless of auth scheme.
+#[derive(Clone, Debug, Serialize)]
+pub struct Principal {
+    pub user_id: Uuid,
+    pub role: String,
+    pub auth_method: AuthMethod,
+}
+
+impl Principal {
+    pub fn is_admin(&self) -> bool {
+        self.role == ADMIN_ROLE
+    }
+}
+
+/// Accepts either a JWT or an API key and attaches a [`Principal`].
+/// Anonymous requests are rejected with 401; public routes don't use this layer.
 pub async fn auth_middleware(
     State(pool): State<PgPool>,
+    State(retry): State<RetryPolicy>,
+    State(jwt): State<Arc<JwtConfig>>,
     mut request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    let auth_header = request
-        .headers()
+    let headers = request.headers();
+    let api_key = headers
+        .get(API_KEY_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string);
+    let bearer = headers
         .get("authorization")
         .and_then(|h| h.to_str().ok())
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(str::to_string);
 
-    let token = auth_header
-        .strip_prefix("Bearer ")
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let principal = match (api_key, bearer) {
+        (Some(key), _) => validate_api_key(&pool, retry, &key).await?,
+        (None, Some(token)) if token.starts_with(API_KEY_PREFIX) => {
+            validate_api_key(&pool, retry, &token).await?
+        }
+        (None, Some(token)) => {
+            let (principal, claims) = principal_from_jwt(&pool, retry, &jwt, &token).await?;
+            // Kept for token-specific handlers such as logout
+            request.extensions_mut().insert(claims);
+            principal
+        }
+        (None, None) => return Err(StatusCode::UNAUTHORIZED),
+    };
+
+    request.extensions_mut().insert(principal);
+
+    Ok(next.run(request).await)
+}
+
+/// Restricts a route to admins; must run inside [`auth_middleware`].
+pub async fn require_admin(
+    Extension(principal): Extension<Principal>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    if !principal.is_admin() {
+        return Err(StatusCode::FORBIDDEN);
+    }
 
-    let secret = std::env::var("JWT_SECRET")
-        .unwrap_or_else(|_| "dev_secret_key_change_in_production".to_string());
+    Ok(next.run(request).await)
+}
+
+async fn principal_from_jwt(
+    pool: &PgPool,
+    retry: RetryPolicy,
+    config: &JwtConfig,
+    token: &str,
+) -> Result<(Principal, Claims), StatusCode> {
+    // `exp` is checked by default; issuer and audience must match this deployment
+    let mut validation = Validation::default();
+    validation.set_issuer(&[&config.issuer]);
+    validation.set_audience(&[&config.audience]);
 
     let token_data = decode::<Claims>(
         token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &Validation::default(),
+        &DecodingKey::from_secret(config.secret.as_bytes()),
+        &validation,
     )
     .map_err(|_| StatusCode::UNAUTHORIZED)?;
 
-    request.extensions_mut().insert(token_data.claims);
+    let user_id = Uuid::parse_str(&token_data.claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let jti = Uuid::parse_str(&token_data.claims.jti).map_err(|_| StatusCode::UNAUTHORIZED)?;
 
-    Ok(next.run(request).await)
+    // Role is read per request so demotions apply without waiting for token expiry;
+    // the denylist check rides along in the same round trip.
+    let user = retry
+        .run(|| {
+            sqlx::query!(
+                r#"
+                SELECT role,
+                       EXISTS(SELECT 1 FROM revoked_tokens WHERE jti = $2) AS "revoked!"
+                FROM users
+                WHERE id = $1
+                "#,
+                user_id,
+                jti
+            )
+            .fetch_optional(pool)
+        })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if user.revoked {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok((
+        Principal {
+            user_id,
+            role: user.role,
+            auth_method: AuthMethod::Jwt,
+        },
+        token_data.claims,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support;
+    use crate::handlers::{api_keys, auth::generate_jwt};
+    use crate::state::AppState;
+    use axum::{body::Body, http::Request as HttpRequest, routing::get, Json, Router};
+    use tower::ServiceExt;
+
+    /// Echoes the attached principal back, behind the real auth layer.
+    fn whoami(state: AppState) -> Router {
+        Router::new()
+            .route(
+                "/whoami",
+                get(|Extension(principal): Extension<Principal>| async { Json(principal) }),
+            )
+            .route_layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                auth_middleware,
+            ))
+            .with_state(state)
+    }
+
+    async fn call(app: &Router, header: Option<(&str, String)>) -> (StatusCode, serde_json::Value) {
+        let mut request = HttpRequest::get("/whoami");
+        if let Some((name, value)) = header {
+            request = request.header(name, value);
+        }
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn jwt_and_api_key_resolve_to_the_same_principal() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let user_id = test_support::create_user(&pool).await;
+        sqlx::query("UPDATE users SET role = $1 WHERE id = $2")
+            .bind(ADMIN_ROLE)
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let state = AppState::new(pool.clone());
+        let app = whoami(state.clone());
+
+        let token = generate_jwt(&state.jwt, &user_id.to_string(), "owner@example.test").unwrap();
+        let Json(key) = api_keys::create_key(
+            State(pool.clone()),
+            Extension(test_support::principal(user_id)),
+            Json(api_keys::CreateApiKeyRequest {
+                name: "ci".to_string(),
+                permissions: None,
+            }),
+        )
+        .await
+        .unwrap();
+
+        let (status, jwt) = call(&app, Some(("authorization", format!("Bearer {}", token)))).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, api_key) = call(&app, Some((API_KEY_HEADER, key.secret_key.clone()))).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, bearer_key) = call(
+            &app,
+            Some(("authorization", format!("Bearer {}", key.secret_key))),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let expected = |method: &str| {
+            serde_json::json!({
+                "user_id": user_id,
+                "role": ADMIN_ROLE,
+                "auth_method": method,
+            })
+        };
+        assert_eq!(jwt, expected("jwt"));
+        assert_eq!(api_key, expected("api_key"));
+        assert_eq!(bearer_key, expected("api_key"));
+    }
+
+    #[tokio::test]
+    async fn anonymous_and_unknown_credentials_are_rejected() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let app = whoami(AppState::new(pool));
+
+        assert_eq!(call(&app, None).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            call(
+                &app,
+                Some(("authorization", "Bearer not.a.jwt".to_string()))
+            )
+            .await
+            .0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call(&app, Some((API_KEY_HEADER, "sk_live_unknown".to_string())))
+                .await
+                .0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call(
+                &app,
+                Some(("authorization", "Basic dXNlcjpwdw==".to_string()))
+            )
+            .await
+            .0,
+            StatusCode::UNAUTHORIZED
+        );
+    }
 }
@@ -0,0 +1,133 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use sqlx::PgPool;
+use std::time::Instant;
+
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    requests_total: IntCounterVec,
+    request_duration: HistogramVec,
+    db_connections: IntGaugeVec,
+    cache_lookups: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let requests_total = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "path", "status"],
+        )
+        .expect("valid http_requests_total metric");
+
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route",
+            ),
+            &["method", "path"],
+        )
+        .expect("valid http_request_duration_seconds metric");
+
+        let db_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections by state"),
+            &["state"],
+        )
+        .expect("valid db_pool_connections metric");
+
+        let cache_lookups = IntCounterVec::new(
+            Opts::new(
+                "cache_lookups_total",
+                "In-process cache lookups by cache and result",
+            ),
+            &["cache", "result"],
+        )
+        .expect("valid cache_lookups_total metric");
+
+        registry
+            .register(Box::new(requests_total.clone()))
+            .expect("register http_requests_total");
+        registry
+            .register(Box::new(request_duration.clone()))
+            .expect("register http_request_duration_seconds");
+        registry
+            .register(Box::new(db_connections.clone()))
+            .expect("register db_pool_connections");
+        registry
+            .register(Box::new(cache_lookups.clone()))
+            .expect("register cache_lookups_total");
+
+        Self {
+            registry,
+            requests_total,
+            request_duration,
+            db_connections,
+            cache_lookups,
+        }
+    }
+
+    pub fn record_cache_lookup(&self, cache: &str, hit: bool) {
+        self.cache_lookups
+            .with_label_values(&[cache, if hit { "hit" } else { "miss" }])
+            .inc();
+    }
+
+    /// Prometheus text exposition, with pool gauges sampled at scrape time.
+    pub fn render(&self, pool: &PgPool) -> String {
+        let idle = pool.num_idle() as i64;
+        let total = pool.size() as i64;
+        self.db_connections.with_label_values(&["idle"]).set(idle);
+        self.db_connections
+            .with_label_values(&["active"])
+            .set(total - idle);
+
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .ok();
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub async fn metrics_middleware(
+    State(metrics): State<Metrics>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Label by route template, not raw path, to keep cardinality bounded
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16().to_string();
+    metrics
+        .requests_total
+        .with_label_values(&[method.as_str(), path.as_str(), status.as_str()])
+        .inc();
+    metrics
+        .request_duration
+        .with_label_values(&[method.as_str(), path.as_str()])
+        .observe(start.elapsed().as_secs_f64());
+
+    response
+}
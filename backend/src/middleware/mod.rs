@@ -1,5 +1,8 @@
 pub mod auth;
+pub mod metrics;
+pub mod quota;
 pub mod rate_limit;
+pub mod request_id;
 
 use tower_http::cors::{Any, CorsLayer};
 
@@ -8,4 +11,5 @@ pub fn cors() -> CorsLayer {
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any)
+        .expose_headers([request_id::REQUEST_ID_HEADER])
 }
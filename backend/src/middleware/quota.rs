@@ -0,0 +1,65 @@
+use crate::config::QuotaConfig;
+use crate::error::ApiError;
+use crate::handlers::usage::monthly_usage;
+use crate::middleware::auth::Principal;
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Counts the request against the caller's monthly quota, rejecting it with 429 once
+/// the quota is used up; must run inside [`auth_middleware`](super::auth::auth_middleware).
+/// Rejected requests aren't counted, so usage never exceeds the limit.
+pub async fn quota_middleware(
+    State(pool): State<PgPool>,
+    State(quota): State<Arc<QuotaConfig>>,
+    Extension(principal): Extension<Principal>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    // Increment and limit check in one statement, so concurrent requests can't overshoot
+    let counted = sqlx::query_scalar!(
+        r#"
+        WITH quota AS (
+            SELECT COALESCE(monthly_request_quota, $2) AS monthly_limit FROM users WHERE id = $1
+        )
+        INSERT INTO usage_counters AS c (user_id, period, request_count)
+        SELECT $1, date_trunc('month', NOW() AT TIME ZONE 'UTC')::date, 1
+        FROM quota
+        WHERE monthly_limit IS NULL OR monthly_limit > 0
+        ON CONFLICT (user_id, period) DO UPDATE
+        SET request_count = c.request_count + 1, updated_at = NOW()
+        WHERE c.request_count < COALESCE((SELECT monthly_limit FROM quota), c.request_count + 1)
+        RETURNING request_count
+        "#,
+        principal.user_id,
+        quota.monthly_requests
+    )
+    .fetch_optional(&pool)
+    .await?;
+
+    if counted.is_none() {
+        let usage = monthly_usage(&pool, principal.user_id, &quota).await?;
+        return Err(ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "quota_exceeded",
+            format!(
+                "Monthly request quota of {} used up; it resets at {}",
+                usage.limit.unwrap_or_default(),
+                usage.resets_at
+            ),
+        )
+        .with_details(serde_json::json!({
+            "limit": usage.limit,
+            "used": usage.used,
+            "resets_at": usage.resets_at,
+        })));
+    }
+
+    Ok(next.run(request).await)
+}
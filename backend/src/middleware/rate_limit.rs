@@ -1,5 +1,4 @@
 use axum::{
-    body::Body,
     extract::{Request, State},
     http::StatusCode,
     middleware::Next,
@@ -29,7 +28,7 @@ impl RateLimiter {
         let mut requests = self.requests.lock().unwrap();
         let now = Instant::now();
 
-        let times = requests.entry(key.to_string()).or_insert_with(Vec::new);
+        let times = requests.entry(key.to_string()).or_default();
         times.retain(|&t| now.duration_since(t) < self.window);
 
         if times.len() < self.max_requests {
@@ -0,0 +1,148 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde_json::Value;
+
+pub const ALGORITHM: &str = "ed25519";
+
+/// Canonical form that signatures cover: compact JSON with object keys sorted at every level.
+pub fn canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(&map[key], out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// Detached base64 signature over the canonical form of `receipt`.
+pub fn sign(signing_key: &SigningKey, receipt: &Value) -> String {
+    let signature = signing_key.sign(canonical_json(receipt).as_bytes());
+    STANDARD.encode(signature.to_bytes())
+}
+
+/// Checks a detached signature produced by [`sign`]; any tampering with the receipt fails.
+pub fn verify(verifying_key: &VerifyingKey, receipt: &Value, signature_b64: &str) -> bool {
+    let Some(bytes) = STANDARD
+        .decode(signature_b64)
+        .ok()
+        .and_then(|b| <[u8; 64]>::try_from(b).ok())
+    else {
+        return false;
+    };
+
+    verifying_key
+        .verify(
+            canonical_json(receipt).as_bytes(),
+            &Signature::from_bytes(&bytes),
+        )
+        .is_ok()
+}
+
+pub fn encode_public_key(verifying_key: &VerifyingKey) -> String {
+    STANDARD.encode(verifying_key.to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    fn receipt() -> Value {
+        json!({
+            "transaction_id": "5f0c6f8e-2f8c-4a8e-9a7e-3b2b1c0d9e8f",
+            "amount": "125.50",
+            "currency": "USD",
+            "status": "settled",
+            "customer_email": null,
+            "issued_at": "2026-10-15T09:00:00+00:00"
+        })
+    }
+
+    #[test]
+    fn canonical_json_sorts_keys_at_every_level() {
+        let value = json!({"b": 1, "a": {"d": [{"z": 1, "y": 2}], "c": "x"}});
+        assert_eq!(
+            canonical_json(&value),
+            r#"{"a":{"c":"x","d":[{"y":2,"z":1}]},"b":1}"#
+        );
+    }
+
+    #[test]
+    fn signed_receipt_verifies_with_the_public_key() {
+        let key = signing_key();
+        let receipt = receipt();
+        let signature = sign(&key, &receipt);
+
+        assert!(verify(&key.verifying_key(), &receipt, &signature));
+    }
+
+    #[test]
+    fn tampered_receipt_fails_verification() {
+        let key = signing_key();
+        let signature = sign(&key, &receipt());
+
+        let mut tampered = receipt();
+        tampered["amount"] = json!("1255.00");
+        assert!(!verify(&key.verifying_key(), &tampered, &signature));
+
+        let mut extended = receipt();
+        extended["refunded"] = json!(false);
+        assert!(!verify(&key.verifying_key(), &extended, &signature));
+    }
+
+    #[test]
+    fn tampered_or_foreign_signature_fails_verification() {
+        let key = signing_key();
+        let receipt = receipt();
+        let signature = sign(&key, &receipt);
+
+        let mut bytes = STANDARD.decode(&signature).unwrap();
+        bytes[0] ^= 1;
+        assert!(!verify(
+            &key.verifying_key(),
+            &receipt,
+            &STANDARD.encode(bytes)
+        ));
+
+        let other = SigningKey::from_bytes(&[8; 32]);
+        assert!(!verify(&other.verifying_key(), &receipt, &signature));
+
+        assert!(!verify(&key.verifying_key(), &receipt, "not base64!"));
+        assert!(!verify(
+            &key.verifying_key(),
+            &receipt,
+            &STANDARD.encode([0u8; 10])
+        ));
+    }
+}
@@ -1,20 +1,56 @@
+use crate::config::ServerConfig;
+use crate::error::ApiError;
 use crate::handlers;
 use crate::middleware as mw;
+use crate::state::AppState;
 use axum::{
+    extract::DefaultBodyLimit,
+    http::{Method, StatusCode, Uri},
     middleware,
-    routing::{delete, get, post, put},
+    routing::{delete, get, patch, post, put},
     Router,
 };
-use sqlx::PgPool;
+use tower_http::limit::RequestBodyLimitLayer;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+mod openapi;
 
 async fn health_check() -> &'static str {
     "OK"
 }
 
-pub fn create_router(pool: PgPool) -> Router {
+async fn route_not_found(method: Method, uri: Uri) -> ApiError {
+    ApiError::new(
+        StatusCode::NOT_FOUND,
+        "route_not_found",
+        format!("No route for {} {}", method, uri.path()),
+    )
+    .with_details(serde_json::json!({ "path": uri.path() }))
+}
+
+pub fn create_router(state: AppState, server_config: &ServerConfig) -> Router {
     let rate_limiter = mw::rate_limit::RateLimiter::new(100, 60);
 
+    // Nested inside the protected routes so `require_admin` sees the principal
+    let admin_routes = Router::new()
+        .route(
+            "/api/admin/bus-lock/reconcile",
+            get(handlers::admin::reconcile_bus_locks),
+        )
+        .route(
+            "/api/admin/bus-lock/:user_id/adjust",
+            post(handlers::admin::adjust_bus_lock),
+        )
+        .route("/api/admin/users", get(handlers::admin::list_users))
+        .route(
+            "/api/transactions/:id/audit",
+            get(handlers::admin::get_transaction_audit),
+        )
+        .route_layer(middleware::from_fn(mw::auth::require_admin));
+
     let protected_routes = Router::new()
+        .route("/api/auth/logout", post(handlers::auth::logout))
         .route(
             "/api/dashboard/overview",
             get(handlers::dashboard::get_dashboard_overview),
@@ -23,9 +59,42 @@ pub fn create_router(pool: PgPool) -> Router {
             "/api/transactions",
             get(handlers::transactions::list_transactions),
         )
+        .route(
+            "/api/transactions/timeseries",
+            get(handlers::transactions::get_transaction_timeseries),
+        )
+        .route(
+            "/api/transactions/export",
+            get(handlers::transactions::export_transactions),
+        )
+        .route(
+            "/api/transactions/status-lookup",
+            post(handlers::transactions::lookup_transaction_statuses),
+        )
+        .route(
+            "/api/transactions/stream",
+            get(handlers::transactions::stream_transactions),
+        )
         .route(
             "/api/transactions/:id",
-            get(handlers::transactions::get_transaction),
+            get(handlers::transactions::get_transaction)
+                .delete(handlers::transactions::delete_transaction),
+        )
+        .route(
+            "/api/transactions/:id/archive",
+            post(handlers::transactions::archive_transaction),
+        )
+        .route(
+            "/api/transactions/:id/metadata",
+            patch(handlers::transactions::update_transaction_metadata),
+        )
+        .route(
+            "/api/transactions/:id/refunds",
+            get(handlers::transactions::list_transaction_refunds),
+        )
+        .route(
+            "/api/transactions/:id/receipt.json",
+            get(handlers::receipts::get_transaction_receipt),
         )
         .route(
             "/api/treasury/positions",
@@ -44,13 +113,60 @@ pub fn create_router(pool: PgPool) -> Router {
             put(handlers::settings::update_settings),
         )
         .route("/api/payments", post(handlers::payments::create_payment))
+        .route(
+            "/api/payments/batch",
+            post(handlers::payments::create_payment_batch),
+        )
+        .route(
+            "/api/payments/fee-preview",
+            post(handlers::payments::preview_fee),
+        )
         .route("/api/payments/:id", get(handlers::payments::get_payment))
+        .route(
+            "/api/payments/:id/settle",
+            post(handlers::payments::settle_payment),
+        )
+        .route("/api/balance", get(handlers::balance::get_balance))
+        .route(
+            "/api/webhooks/secret/rotate",
+            post(handlers::webhooks::rotate_webhook_secret),
+        )
+        .route(
+            "/api/webhooks/verify",
+            post(handlers::webhooks::verify_webhook_signature),
+        )
+        .route(
+            "/api/webhooks/delivery",
+            get(handlers::webhooks::get_webhook_delivery)
+                .put(handlers::webhooks::update_webhook_delivery),
+        )
+        .route(
+            "/api/webhooks/endpoint",
+            get(handlers::webhooks::get_webhook_endpoint)
+                .put(handlers::webhooks::update_webhook_endpoint),
+        )
         .route(
             "/api/bus-lock/balance",
             get(handlers::bus_lock::get_bus_lock_balance),
         )
+        .route(
+            "/api/bus-lock/history",
+            get(handlers::bus_lock::get_bus_lock_history),
+        )
+        .route(
+            "/api/bus-lock/contributors",
+            get(handlers::bus_lock::get_bus_lock_contributors),
+        )
+        .merge(admin_routes)
+        // Inside auth so the quota is keyed by principal; `/api/usage` is merged after
+        // this layer so checking usage never consumes quota
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            mw::quota::quota_middleware,
+        ))
+        .route("/api/usage", get(handlers::usage::get_usage))
         .route_layer(middleware::from_fn_with_state(
-            pool.clone(),
+            state.clone(),
             mw::auth::auth_middleware,
         ))
         .layer(middleware::from_fn_with_state(
@@ -58,11 +174,175 @@ pub fn create_router(pool: PgPool) -> Router {
             mw::rate_limit::rate_limit_middleware,
         ));
 
-    Router::new()
+    let router = Router::new()
         .route("/health", get(health_check))
+        .route("/metrics", get(handlers::metrics::get_metrics))
         .route("/api/auth/signup", post(handlers::auth::signup))
         .route("/api/auth/login", post(handlers::auth::login))
-        .merge(protected_routes)
+        .route(
+            "/api/receipts/public-key",
+            get(handlers::receipts::get_public_key),
+        )
+        .route(
+            "/api/receipts/verify",
+            post(handlers::receipts::verify_receipt),
+        )
+        .route(
+            "/api/currencies",
+            get(handlers::currencies::list_currencies),
+        )
+        .route(
+            "/api/payments/:id/status",
+            get(handlers::payments::get_payment_status),
+        )
+        .merge(protected_routes);
+
+    #[cfg(feature = "test-endpoints")]
+    let router = router.merge(handlers::testing::routes());
+
+    router
+        .merge(SwaggerUi::new("/docs").url("/api-docs/openapi.json", openapi::ApiDoc::openapi()))
+        .fallback(route_not_found)
+        // Oversized bodies are refused up front (by Content-Length) before auth or any
+        // handler runs; the default extractor limit is lifted so this is the only cap.
+        .layer(RequestBodyLimitLayer::new(server_config.max_body_bytes))
+        .layer(DefaultBodyLimit::disable())
+        .layer(middleware::from_fn_with_state(
+            state.metrics.clone(),
+            mw::metrics::metrics_middleware,
+        ))
+        .layer(mw::request_id::trace())
+        .layer(mw::request_id::propagate_request_id())
+        .layer(mw::request_id::set_request_id())
         .layer(mw::cors())
-        .with_state(pool)
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+    use sqlx::postgres::PgPoolOptions;
+    use tower::ServiceExt;
+
+    /// Router over a pool that never connects, for checking what's answered before
+    /// any query runs; anything that did reach the database would fail with a 5xx.
+    fn offline_router(max_body_bytes: usize) -> Router {
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(std::time::Duration::from_millis(200))
+            .connect_lazy("postgres://bytus@127.0.0.1:1/unreachable")
+            .unwrap();
+        let server = ServerConfig {
+            max_body_bytes,
+            ..ServerConfig::from_env()
+        };
+        create_router(AppState::new(pool), &server)
+    }
+
+    fn post_payment(body: String) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/api/payments")
+            .header("content-type", "application/json")
+            .header("content-length", body.len())
+            .header("authorization", "Bearer not-a-real-token")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn oversized_body_is_rejected_before_auth_or_the_database() {
+        let app = offline_router(1024);
+
+        let oversized = format!(
+            r#"{{"amount":"10","currency":"USD","description":"{}"}}"#,
+            "x".repeat(2048)
+        );
+        let response = app.clone().oneshot(post_payment(oversized)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Under the limit the same request gets as far as auth, which turns it away
+        let small = r#"{"amount":"10","currency":"USD"}"#.to_string();
+        let response = app.oneshot(post_payment(small)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    async fn json_body(response: axum::response::Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn unknown_route_gets_the_structured_not_found_body() {
+        let request = Request::builder()
+            .method("DELETE")
+            .uri("/api/nowhere?page=2")
+            .body(Body::empty())
+            .unwrap();
+        let response = offline_router(1024).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            json_body(response).await,
+            serde_json::json!({
+                "error": {
+                    "code": "route_not_found",
+                    "message": "No route for DELETE /api/nowhere",
+                    "details": { "path": "/api/nowhere" },
+                }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn malformed_id_in_the_path_gets_the_structured_400_body() {
+        let request = Request::builder()
+            .uri("/api/payments/not-a-uuid/status?token=anything")
+            .body(Body::empty())
+            .unwrap();
+        let response = offline_router(1024).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            json_body(response).await,
+            serde_json::json!({
+                "error": {
+                    "code": "invalid_path_param",
+                    "message": "path parameter `id` must be a valid UUID",
+                    "details": { "params": { "id": "not-a-uuid" } },
+                }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn currencies_are_public_cacheable_and_carry_their_precision() {
+        let request = Request::builder()
+            .uri("/api/currencies")
+            .body(Body::empty())
+            .unwrap();
+        let response = offline_router(1024).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[axum::http::header::CACHE_CONTROL],
+            "public, max-age=86400"
+        );
+        let body = json_body(response).await;
+        let currencies = body.as_array().unwrap();
+        assert!(!currencies.is_empty());
+        let find = |code: &str| {
+            currencies
+                .iter()
+                .find(|c| c["code"] == code)
+                .unwrap_or_else(|| panic!("{} missing", code))
+                .clone()
+        };
+        assert_eq!(find("USD")["minor_units"], 2);
+        assert_eq!(find("USD")["example"], "$1,234.57");
+        assert_eq!(find("JPY")["minor_units"], 0);
+        assert_eq!(find("JPY")["example"], "¥1,235");
+    }
 }
@@ -0,0 +1,99 @@
+use crate::error::ErrorBody;
+use crate::handlers;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Bytus API",
+        description = "Crypto payment infrastructure: payments, transactions, treasury and BUS locks."
+    ),
+    paths(
+        handlers::auth::signup,
+        handlers::auth::login,
+        handlers::auth::logout,
+        handlers::dashboard::get_dashboard_overview,
+        handlers::transactions::list_transactions,
+        handlers::transactions::export_transactions,
+        handlers::transactions::get_transaction_timeseries,
+        handlers::transactions::stream_transactions,
+        handlers::transactions::get_transaction,
+        handlers::transactions::archive_transaction,
+        handlers::transactions::delete_transaction,
+        handlers::transactions::update_transaction_metadata,
+        handlers::transactions::list_transaction_refunds,
+        handlers::transactions::lookup_transaction_statuses,
+        handlers::receipts::get_transaction_receipt,
+        handlers::receipts::get_public_key,
+        handlers::receipts::verify_receipt,
+        handlers::treasury::get_positions,
+        handlers::treasury::get_portfolio,
+        handlers::api_keys::list_keys,
+        handlers::api_keys::create_key,
+        handlers::api_keys::delete_key,
+        handlers::settings::get_settings,
+        handlers::settings::update_settings,
+        handlers::currencies::list_currencies,
+        handlers::payments::create_payment,
+        handlers::payments::create_payment_batch,
+        handlers::payments::preview_fee,
+        handlers::payments::get_payment,
+        handlers::payments::get_payment_status,
+        handlers::payments::settle_payment,
+        handlers::balance::get_balance,
+        handlers::usage::get_usage,
+        handlers::webhooks::rotate_webhook_secret,
+        handlers::webhooks::verify_webhook_signature,
+        handlers::webhooks::get_webhook_delivery,
+        handlers::webhooks::update_webhook_delivery,
+        handlers::webhooks::get_webhook_endpoint,
+        handlers::webhooks::update_webhook_endpoint,
+        handlers::bus_lock::get_bus_lock_balance,
+        handlers::bus_lock::get_bus_lock_history,
+        handlers::bus_lock::get_bus_lock_contributors,
+        handlers::metrics::get_metrics,
+        handlers::admin::reconcile_bus_locks,
+        handlers::admin::adjust_bus_lock,
+        handlers::admin::list_users,
+        handlers::admin::get_transaction_audit,
+    ),
+    components(schemas(ErrorBody)),
+    modifiers(&SecurityAddon),
+    tags(
+        (name = "auth", description = "Signup, login and logout"),
+        (name = "dashboard", description = "Dashboard summaries"),
+        (name = "transactions", description = "Transaction history"),
+        (name = "receipts", description = "Signed proof-of-payment receipts"),
+        (name = "treasury", description = "Treasury positions"),
+        (name = "api_keys", description = "API key management"),
+        (name = "settings", description = "Account settings"),
+        (name = "currencies", description = "Supported currencies and their precision"),
+        (name = "payments", description = "Payment creation and lookup"),
+        (name = "balance", description = "Ledger balances"),
+        (name = "usage", description = "Monthly API request quota"),
+        (name = "webhooks", description = "Webhook endpoint, signing secrets and delivery mode"),
+        (name = "bus_lock", description = "BUS collateral locks"),
+        (name = "operations", description = "Operational endpoints"),
+        (name = "admin", description = "Operator-only endpoints; require the admin role")
+    )
+)]
+pub struct ApiDoc;
+
+/// Registers the bearer scheme referenced by protected paths; it accepts a JWT or an `sk_live_` API key.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT or API key")
+                    .build(),
+            ),
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use crate::cache::ListCache;
+use crate::config::{
+    DatabaseConfig, FeeConfig, JwtConfig, PaginationConfig, PaymentConfig, QuotaConfig,
+    ReceiptConfig, ServerConfig, WebhookConfig,
+};
+use crate::db::RetryPolicy;
+use crate::events::TransactionEvents;
+use crate::handlers::transactions::TransactionPage;
+use crate::middleware::metrics::Metrics;
+use axum::extract::FromRef;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Shared router state; handlers extract only the parts they need via `FromRef`.
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub pool: PgPool,
+    pub fees: Arc<FeeConfig>,
+    pub jwt: Arc<JwtConfig>,
+    pub payments: Arc<PaymentConfig>,
+    pub receipts: Arc<ReceiptConfig>,
+    pub pagination: Arc<PaginationConfig>,
+    pub server: Arc<ServerConfig>,
+    pub quota: Arc<QuotaConfig>,
+    pub webhooks: Arc<WebhookConfig>,
+    pub metrics: Metrics,
+    pub events: TransactionEvents,
+    pub retry: RetryPolicy,
+    pub transaction_list_cache: ListCache<TransactionPage>,
+}
+
+impl AppState {
+    pub fn new(pool: PgPool) -> Self {
+        let pagination = PaginationConfig::from_env();
+        let transaction_list_cache = ListCache::new(pagination.list_cache_ttl);
+
+        Self {
+            pool,
+            fees: Arc::new(FeeConfig::from_env()),
+            jwt: Arc::new(JwtConfig::from_env()),
+            payments: Arc::new(PaymentConfig::from_env()),
+            receipts: Arc::new(ReceiptConfig::from_env()),
+            pagination: Arc::new(pagination),
+            server: Arc::new(ServerConfig::from_env()),
+            quota: Arc::new(QuotaConfig::from_env()),
+            webhooks: Arc::new(WebhookConfig::from_env()),
+            metrics: Metrics::new(),
+            events: TransactionEvents::new(),
+            retry: RetryPolicy::from_config(&DatabaseConfig::from_env()),
+            transaction_list_cache,
+        }
+    }
+}
@@ -0,0 +1,642 @@
+use super::{dispatch, DeliveryMode};
+use crate::config::WebhookConfig;
+use chrono::NaiveDateTime;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+/// Hex HMAC-SHA256 of the request body under the merchant's signing secret. While a
+/// rotated-out secret is in its grace window its signature follows, comma-separated,
+/// so receivers that haven't switched yet keep verifying.
+pub const SIGNATURE_HEADER: &str = "bytus-signature";
+
+/// The payload's `event_id`, so receivers can dedup without parsing the body first.
+pub const EVENT_ID_HEADER: &str = "bytus-event-id";
+
+/// Most events claimed per run.
+const DELIVERY_BATCH: i64 = 50;
+
+/// Longest `last_error` kept, in characters.
+const MAX_ERROR_LEN: usize = 500;
+
+/// Body POSTed for each `webhook_events` row. `event_id` is stable across redeliveries.
+#[derive(Debug, Serialize)]
+pub struct WebhookPayload {
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub transaction_id: Uuid,
+    pub status: String,
+    pub amount: String,
+    pub currency: String,
+    pub occurred_at: String,
+}
+
+struct ClaimedEvent {
+    user_id: Uuid,
+    mode: DeliveryMode,
+    attempts: i32,
+    created_at: NaiveDateTime,
+    url: String,
+    secret: String,
+    /// Rotated-out secret still inside its grace window
+    previous_secret: Option<String>,
+    payload: WebhookPayload,
+}
+
+enum Outcome {
+    Delivered,
+    Failed(String),
+    /// Not attempted because an earlier event to the same endpoint failed
+    Deferred,
+}
+
+/// HTTP client for deliveries. Redirects aren't followed: the endpoint is the URL the
+/// merchant configured, and a 3xx counts as a failed attempt.
+pub fn client(config: &WebhookConfig) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(config.delivery_timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("webhook HTTP client configuration is valid")
+}
+
+/// Sends due events from the outbox and records each outcome; returns how many were
+/// delivered. Endpoints are served concurrently, each per its delivery mode: `ordered`
+/// sends one event at a time in the order they were recorded and stops at the first
+/// failure, so a later event never overtakes an earlier one; `parallel` keeps up to
+/// `max_parallel_deliveries` in flight and retries failures independently. Only merchants with a `webhook_url` and a signing secret
+/// are delivered to; everyone else's events stay queued.
+pub async fn deliver_due_events(
+    pool: &PgPool,
+    client: &reqwest::Client,
+    config: &WebhookConfig,
+) -> Result<usize, sqlx::Error> {
+    // Long enough for a whole batch to go out serially before anyone else may retry it
+    let lease = config.delivery_timeout.as_secs_f64() * DELIVERY_BATCH as f64;
+
+    let rows = sqlx::query!(
+        r#"
+        WITH due AS (
+            SELECT e.id
+            FROM webhook_events e
+            JOIN users u ON u.id = e.user_id
+            JOIN webhook_secrets s ON s.user_id = e.user_id
+            WHERE e.delivered_at IS NULL AND e.attempts < $1 AND e.next_attempt_at <= NOW()
+              AND u.webhook_url IS NOT NULL
+              -- Ordered endpoints: held back while an earlier event waits on a retry or lease
+              AND (u.webhook_delivery_mode = 'parallel' OR NOT EXISTS (
+                  SELECT 1 FROM webhook_events p
+                  WHERE p.user_id = e.user_id AND p.delivered_at IS NULL AND p.attempts < $1
+                    AND (p.created_at, p.id) < (e.created_at, e.id)
+                    AND p.next_attempt_at > NOW()
+              ))
+            ORDER BY e.created_at, e.id
+            LIMIT $2
+            FOR UPDATE OF e SKIP LOCKED
+        )
+        UPDATE webhook_events e
+        SET next_attempt_at = NOW() + make_interval(secs => $3)
+        FROM due, users u, webhook_secrets s, transactions t
+        WHERE e.id = due.id AND u.id = e.user_id AND s.user_id = e.user_id
+          AND t.id = e.transaction_id
+        RETURNING e.id, e.user_id, e.attempts, e.event_type, e.status, e.transaction_id, e.created_at,
+                  u.webhook_url AS "url!", u.webhook_delivery_mode, s.secret,
+                  CASE WHEN s.previous_expires_at > NOW() THEN s.previous_secret END AS previous_secret,
+                  t.amount, t.currency
+        "#,
+        config.max_attempts,
+        DELIVERY_BATCH,
+        lease
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut events: Vec<ClaimedEvent> = rows
+        .into_iter()
+        .map(|row| ClaimedEvent {
+            user_id: row.user_id,
+            mode: DeliveryMode::from_db(&row.webhook_delivery_mode),
+            attempts: row.attempts,
+            created_at: row.created_at,
+            url: row.url,
+            secret: row.secret,
+            previous_secret: row.previous_secret,
+            payload: WebhookPayload {
+                event_id: row.id,
+                event_type: row.event_type,
+                transaction_id: row.transaction_id,
+                status: row.status,
+                amount: row.amount.to_string(),
+                currency: row.currency,
+                occurred_at: row.created_at.to_string(),
+            },
+        })
+        .collect();
+    if events.is_empty() {
+        return Ok(0);
+    }
+    // RETURNING has no defined order
+    events.sort_by_key(|event| (event.created_at, event.payload.event_id));
+
+    let mut endpoints: Vec<(Uuid, Vec<ClaimedEvent>)> = Vec::new();
+    for event in events {
+        match endpoints
+            .iter_mut()
+            .find(|(user_id, _)| *user_id == event.user_id)
+        {
+            Some((_, queue)) => queue.push(event),
+            None => endpoints.push((event.user_id, vec![event])),
+        }
+    }
+
+    let mut tasks = JoinSet::new();
+    for (_, queue) in endpoints {
+        let client = client.clone();
+        let max_in_flight = config.max_parallel_deliveries;
+        tasks.spawn(async move { deliver_to_endpoint(client, max_in_flight, queue).await });
+    }
+    let mut outcomes = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        outcomes.extend(joined.expect("webhook delivery task panicked"));
+    }
+
+    record_outcomes(pool, config, outcomes).await
+}
+
+/// Sends one endpoint's queue through [`dispatch`]. In ordered mode everything after
+/// a failed send is deferred rather than attempted.
+async fn deliver_to_endpoint(
+    client: reqwest::Client,
+    max_in_flight: usize,
+    queue: Vec<ClaimedEvent>,
+) -> Vec<(Uuid, i32, Outcome)> {
+    let mode = queue.first().map(|event| event.mode).unwrap_or_default();
+    let held_back = Arc::new(AtomicBool::new(false));
+
+    dispatch(mode, max_in_flight, queue, move |event: ClaimedEvent| {
+        let client = client.clone();
+        let held_back = held_back.clone();
+        async move {
+            let outcome = if held_back.load(Ordering::SeqCst) {
+                Outcome::Deferred
+            } else {
+                send(&client, &event).await
+            };
+            if mode == DeliveryMode::Ordered && matches!(outcome, Outcome::Failed(_)) {
+                held_back.store(true, Ordering::SeqCst);
+            }
+            (event.payload.event_id, event.attempts, outcome)
+        }
+    })
+    .await
+}
+
+async fn send(client: &reqwest::Client, event: &ClaimedEvent) -> Outcome {
+    let body = serde_json::to_vec(&event.payload).expect("webhook payload serializes");
+    let signature = match &event.previous_secret {
+        Some(previous) => format!(
+            "{},{}",
+            super::sign(&event.secret, &body),
+            super::sign(previous, &body)
+        ),
+        None => super::sign(&event.secret, &body),
+    };
+
+    let result = client
+        .post(&event.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(EVENT_ID_HEADER, event.payload.event_id.to_string())
+        .header(SIGNATURE_HEADER, signature)
+        .body(body)
+        .send()
+        .await;
+
+    match result {
+        Ok(response) if response.status().is_success() => Outcome::Delivered,
+        Ok(response) => Outcome::Failed(format!("endpoint responded {}", response.status())),
+        Err(e) => Outcome::Failed(e.to_string()),
+    }
+}
+
+/// Marks delivered events, schedules failed ones for a retry with backoff, and
+/// releases the lease on deferred ones so they follow as soon as their predecessor
+/// goes through.
+async fn record_outcomes(
+    pool: &PgPool,
+    config: &WebhookConfig,
+    outcomes: Vec<(Uuid, i32, Outcome)>,
+) -> Result<usize, sqlx::Error> {
+    let count = outcomes.len();
+    let mut ids = Vec::with_capacity(count);
+    let mut delivered = Vec::with_capacity(count);
+    let mut errors: Vec<Option<String>> = Vec::with_capacity(count);
+    let mut delays = Vec::with_capacity(count);
+    for (id, attempts, outcome) in outcomes {
+        ids.push(id);
+        delivered.push(matches!(outcome, Outcome::Delivered));
+        match outcome {
+            Outcome::Failed(error) => {
+                tracing::warn!(event_id = %id, attempt = attempts + 1, error = %error, "webhook delivery failed");
+                errors.push(Some(error.chars().take(MAX_ERROR_LEN).collect()));
+                delays.push(config.retry_delay(attempts + 1).as_secs_f64());
+            }
+            Outcome::Delivered | Outcome::Deferred => {
+                errors.push(None);
+                delays.push(0.0);
+            }
+        }
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE webhook_events e
+        SET delivered_at = CASE WHEN o.delivered THEN NOW() END,
+            attempts = e.attempts + CASE WHEN o.delivered OR o.error IS NOT NULL THEN 1 ELSE 0 END,
+            last_error = CASE WHEN o.delivered THEN NULL ELSE COALESCE(o.error, e.last_error) END,
+            next_attempt_at = NOW() + make_interval(secs => o.delay)
+        FROM UNNEST($1::uuid[], $2::bool[], $3::text[], $4::float8[])
+            AS o(id, delivered, error, delay)
+        WHERE e.id = o.id
+        "#,
+        &ids,
+        &delivered,
+        &errors as &[Option<String>],
+        &delays
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(delivered.into_iter().filter(|&d| d).count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support;
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Local endpoint recording every delivery and answering with `status` after
+    /// `delay`, tracking the most requests it had in flight at once.
+    #[derive(Clone)]
+    struct Receiver {
+        received: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+        status: StatusCode,
+        delay: Duration,
+        in_flight: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    impl Receiver {
+        async fn start(status: StatusCode, delay: Duration) -> (Self, String) {
+            let receiver = Self {
+                received: Arc::default(),
+                status,
+                delay,
+                in_flight: Arc::default(),
+                peak: Arc::default(),
+            };
+            let app = Router::new()
+                .route("/hook", post(Self::handle))
+                .with_state(receiver.clone());
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, app).await });
+            (receiver, url)
+        }
+
+        async fn handle(
+            State(receiver): State<Self>,
+            headers: HeaderMap,
+            body: Bytes,
+        ) -> StatusCode {
+            receiver.received.lock().unwrap().push((headers, body));
+            let now = receiver.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            receiver.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(receiver.delay).await;
+            receiver.in_flight.fetch_sub(1, Ordering::SeqCst);
+            receiver.status
+        }
+
+        fn event_ids(&self) -> Vec<Uuid> {
+            self.received
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(_, body)| {
+                    let payload: serde_json::Value = serde_json::from_slice(body).unwrap();
+                    payload["event_id"].as_str().unwrap().parse().unwrap()
+                })
+                .collect()
+        }
+    }
+
+    fn config() -> WebhookConfig {
+        WebhookConfig {
+            delivery_timeout: Duration::from_secs(5),
+            retry_base: Duration::from_secs(60),
+            ..WebhookConfig::from_env()
+        }
+    }
+
+    /// Merchant delivering to `url`, with a signing secret; returns the secret.
+    async fn endpoint_user(pool: &PgPool, url: &str) -> (Uuid, String) {
+        endpoint_user_with_mode(pool, url, DeliveryMode::Ordered).await
+    }
+
+    async fn endpoint_user_with_mode(
+        pool: &PgPool,
+        url: &str,
+        mode: DeliveryMode,
+    ) -> (Uuid, String) {
+        let user_id = test_support::create_user(pool).await;
+        let secret = super::super::generate_secret();
+        sqlx::query("UPDATE users SET webhook_url = $2, webhook_delivery_mode = $3 WHERE id = $1")
+            .bind(user_id)
+            .bind(url)
+            .bind(mode.as_str())
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO webhook_secrets (user_id, secret) VALUES ($1, $2)")
+            .bind(user_id)
+            .bind(&secret)
+            .execute(pool)
+            .await
+            .unwrap();
+        (user_id, secret)
+    }
+
+    /// Queues an event for a new payment, `age_secs` in the past so order is explicit.
+    async fn queue_event(pool: &PgPool, user_id: Uuid, age_secs: i32) -> Uuid {
+        let transaction_id =
+            test_support::insert_payment(pool, user_id, "12.50", "USD", "pending").await;
+        sqlx::query_scalar(
+            "INSERT INTO webhook_events (user_id, transaction_id, event_type, status, created_at)
+             VALUES ($1, $2, 'transaction.created', 'pending', NOW() - make_interval(secs => $3))
+             RETURNING id",
+        )
+        .bind(user_id)
+        .bind(transaction_id)
+        .bind(age_secs)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn event_state(pool: &PgPool, id: Uuid) -> (bool, i32, Option<String>) {
+        sqlx::query_as(
+            "SELECT delivered_at IS NOT NULL, attempts, last_error FROM webhook_events WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    /// Detaches the endpoint so later runs don't keep retrying a receiver that's gone.
+    async fn detach(pool: &PgPool, user_id: Uuid) {
+        sqlx::query("UPDATE users SET webhook_url = NULL WHERE id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn due_events_are_delivered_signed_and_once() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let (receiver, url) = Receiver::start(StatusCode::NO_CONTENT, Duration::ZERO).await;
+        let (user_id, secret) = endpoint_user(&pool, &url).await;
+        let first = queue_event(&pool, user_id, 20).await;
+        let second = queue_event(&pool, user_id, 10).await;
+        let config = config();
+        let client = client(&config);
+
+        deliver_due_events(&pool, &client, &config).await.unwrap();
+        deliver_due_events(&pool, &client, &config).await.unwrap();
+        detach(&pool, user_id).await;
+
+        assert_eq!(receiver.event_ids(), [first, second]);
+        assert_eq!(event_state(&pool, first).await, (true, 1, None));
+        assert_eq!(event_state(&pool, second).await, (true, 1, None));
+
+        let received = receiver.received.lock().unwrap();
+        let (headers, body) = &received[0];
+        assert_eq!(headers[EVENT_ID_HEADER], first.to_string());
+        assert!(super::super::verify(
+            &secret,
+            body,
+            headers[SIGNATURE_HEADER].to_str().unwrap()
+        ));
+        let payload: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(payload["event_type"], "transaction.created");
+        assert_eq!(payload["status"], "pending");
+        assert_eq!(
+            payload["amount"]
+                .as_str()
+                .unwrap()
+                .parse::<bigdecimal::BigDecimal>()
+                .unwrap(),
+            "12.5".parse::<bigdecimal::BigDecimal>().unwrap()
+        );
+        assert_eq!(payload["currency"], "USD");
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_backs_off_and_holds_back_later_events() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let (receiver, url) =
+            Receiver::start(StatusCode::SERVICE_UNAVAILABLE, Duration::ZERO).await;
+        let (user_id, _) = endpoint_user(&pool, &url).await;
+        let first = queue_event(&pool, user_id, 20).await;
+        let second = queue_event(&pool, user_id, 10).await;
+        let config = config();
+        let client = client(&config);
+
+        deliver_due_events(&pool, &client, &config).await.unwrap();
+        // The first event is now waiting out its backoff, so nothing goes out
+        deliver_due_events(&pool, &client, &config).await.unwrap();
+        detach(&pool, user_id).await;
+
+        assert_eq!(receiver.event_ids(), [first]);
+        assert_eq!(
+            event_state(&pool, first).await,
+            (
+                false,
+                1,
+                Some("endpoint responded 503 Service Unavailable".into())
+            )
+        );
+        assert_eq!(event_state(&pool, second).await, (false, 0, None));
+
+        let retry_in: f64 = sqlx::query_scalar(
+            "SELECT EXTRACT(EPOCH FROM next_attempt_at - NOW())::float8 FROM webhook_events WHERE id = $1",
+        )
+        .bind(first)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert!((50.0..=60.0).contains(&retry_in), "{}", retry_in);
+    }
+
+    #[tokio::test]
+    async fn events_without_an_endpoint_or_secret_stay_queued() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let (receiver, url) = Receiver::start(StatusCode::OK, Duration::ZERO).await;
+        let no_url = test_support::create_user(&pool).await;
+        let no_secret = test_support::create_user(&pool).await;
+        sqlx::query("UPDATE users SET webhook_url = $2 WHERE id = $1")
+            .bind(no_secret)
+            .bind(&url)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let events = [
+            queue_event(&pool, no_url, 0).await,
+            queue_event(&pool, no_secret, 0).await,
+        ];
+        let config = config();
+
+        deliver_due_events(&pool, &client(&config), &config)
+            .await
+            .unwrap();
+        detach(&pool, no_secret).await;
+
+        assert!(receiver.event_ids().is_empty());
+        for event in events {
+            assert_eq!(event_state(&pool, event).await, (false, 0, None));
+        }
+    }
+
+    /// Queues six events for an endpoint in `mode` whose receiver takes 100ms per
+    /// request and answers `status`, runs one delivery pass, and returns the receiver
+    /// with the events in the order they were queued.
+    async fn deliver_six(
+        pool: &PgPool,
+        mode: DeliveryMode,
+        status: StatusCode,
+    ) -> (Receiver, Vec<Uuid>) {
+        let (receiver, url) = Receiver::start(status, Duration::from_millis(100)).await;
+        let (user_id, _) = endpoint_user_with_mode(pool, &url, mode).await;
+        let mut events = Vec::new();
+        for age in (1..=6).rev() {
+            events.push(queue_event(pool, user_id, age * 10).await);
+        }
+        let config = WebhookConfig {
+            max_parallel_deliveries: 4,
+            ..config()
+        };
+
+        deliver_due_events(pool, &client(&config), &config)
+            .await
+            .unwrap();
+        detach(pool, user_id).await;
+        (receiver, events)
+    }
+
+    #[tokio::test]
+    async fn parallel_endpoint_receives_deliveries_concurrently() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let (receiver, events) = deliver_six(&pool, DeliveryMode::Parallel, StatusCode::OK).await;
+
+        assert_eq!(receiver.peak.load(Ordering::SeqCst), 4);
+        let mut received = receiver.event_ids();
+        received.sort();
+        let mut expected = events.clone();
+        expected.sort();
+        assert_eq!(received, expected);
+        for event in events {
+            assert!(event_state(&pool, event).await.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn ordered_endpoint_receives_deliveries_serially_in_order() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let (receiver, events) = deliver_six(&pool, DeliveryMode::Ordered, StatusCode::OK).await;
+
+        assert_eq!(receiver.peak.load(Ordering::SeqCst), 1);
+        assert_eq!(receiver.event_ids(), events);
+    }
+
+    #[tokio::test]
+    async fn parallel_failures_do_not_hold_back_other_events() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let (receiver, events) =
+            deliver_six(&pool, DeliveryMode::Parallel, StatusCode::BAD_GATEWAY).await;
+
+        // Every event was attempted, not just the first
+        assert_eq!(receiver.event_ids().len(), 6);
+        for event in events {
+            let (delivered, attempts, _) = event_state(&pool, event).await;
+            assert!(!delivered);
+            assert_eq!(attempts, 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn previous_secret_also_signs_until_its_grace_expires() {
+        let Some(pool) = test_support::pool().await else {
+            return;
+        };
+        let config = config();
+        let client = client(&config);
+
+        for (grace, previous_verifies) in [("1 hour", true), ("-1 second", false)] {
+            let (receiver, url) = Receiver::start(StatusCode::OK, Duration::ZERO).await;
+            let (user_id, current) = endpoint_user(&pool, &url).await;
+            let previous = super::super::generate_secret();
+            sqlx::query(
+                "UPDATE webhook_secrets
+                 SET previous_secret = $2, previous_expires_at = NOW() + $3::interval
+                 WHERE user_id = $1",
+            )
+            .bind(user_id)
+            .bind(&previous)
+            .bind(grace)
+            .execute(&pool)
+            .await
+            .unwrap();
+            queue_event(&pool, user_id, 0).await;
+
+            deliver_due_events(&pool, &client, &config).await.unwrap();
+            detach(&pool, user_id).await;
+
+            let received = receiver.received.lock().unwrap();
+            let (headers, body) = &received[0];
+            let signature = headers[SIGNATURE_HEADER].to_str().unwrap();
+            assert!(super::super::verify(&current, body, signature), "{}", grace);
+            assert_eq!(
+                super::super::verify(&previous, body, signature),
+                previous_verifies,
+                "{}",
+                grace
+            );
+        }
+    }
+}
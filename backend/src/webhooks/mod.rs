@@ -0,0 +1,220 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use utoipa::ToSchema;
+
+pub mod delivery;
+
+pub const ALGORITHM: &str = "hmac-sha256";
+
+const SECRET_PREFIX: &str = "whsec_";
+
+/// New signing secret: a recognizable prefix and 32 random bytes, hex-encoded.
+pub fn generate_secret() -> String {
+    let random_part: String = (0..32)
+        .map(|_| format!("{:02x}", rand::random::<u8>()))
+        .collect();
+    format!("{}{}", SECRET_PREFIX, random_part)
+}
+
+/// Hex HMAC-SHA256 of `payload`.
+pub fn sign(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Checks a signature in constant time, so timing doesn't reveal a matching prefix.
+/// `signature` may be a `Bytus-Signature` value with several comma-separated hex
+/// signatures; one match is enough.
+pub fn verify(secret: &str, payload: &[u8], signature: &str) -> bool {
+    let expected = sign(secret, payload);
+
+    signature
+        .split(',')
+        .map(|candidate| candidate.trim().to_ascii_lowercase())
+        .fold(false, |matched, candidate| {
+            let equal = expected.len() == candidate.len()
+                && expected
+                    .bytes()
+                    .zip(candidate.bytes())
+                    .fold(0, |diff, (a, b)| diff | (a ^ b))
+                    == 0;
+            matched | equal
+        })
+}
+
+/// Per-merchant choice between strict event order and delivery throughput.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryMode {
+    /// One delivery in flight at a time, in event order
+    #[default]
+    Ordered,
+    /// Up to `WEBHOOK_MAX_PARALLEL_DELIVERIES` in flight; receivers must tolerate reordering
+    Parallel,
+}
+
+impl DeliveryMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeliveryMode::Ordered => "ordered",
+            DeliveryMode::Parallel => "parallel",
+        }
+    }
+
+    /// Reads the stored column; anything unexpected falls back to the safe default.
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "parallel" => DeliveryMode::Parallel,
+            _ => DeliveryMode::Ordered,
+        }
+    }
+}
+
+/// Sends `deliveries` to one endpoint according to `mode` and returns the outcomes in
+/// input order. Ordered mode awaits each send before starting the next; parallel mode
+/// keeps at most `max_in_flight` sends running at once.
+pub async fn dispatch<T, F, Fut, R>(
+    mode: DeliveryMode,
+    max_in_flight: usize,
+    deliveries: Vec<T>,
+    send: F,
+) -> Vec<R>
+where
+    T: Send + 'static,
+    F: Fn(T) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    R: Send + 'static,
+{
+    match mode {
+        DeliveryMode::Ordered => {
+            let mut outcomes = Vec::with_capacity(deliveries.len());
+            for delivery in deliveries {
+                outcomes.push(send(delivery).await);
+            }
+            outcomes
+        }
+        DeliveryMode::Parallel => {
+            let permits = Arc::new(Semaphore::new(max_in_flight.max(1)));
+            let mut tasks = JoinSet::new();
+            let count = deliveries.len();
+
+            for (index, delivery) in deliveries.into_iter().enumerate() {
+                let permits = permits.clone();
+                let send = send.clone();
+                tasks.spawn(async move {
+                    let _permit = permits
+                        .acquire_owned()
+                        .await
+                        .expect("semaphore is never closed");
+                    (index, send(delivery).await)
+                });
+            }
+
+            let mut outcomes: Vec<Option<R>> = (0..count).map(|_| None).collect();
+            while let Some(joined) = tasks.join_next().await {
+                let (index, outcome) = joined.expect("delivery task panicked");
+                outcomes[index] = Some(outcome);
+            }
+            outcomes
+                .into_iter()
+                .map(|o| o.expect("every delivery ran"))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Fake sender recording the peak number of concurrent sends and the start order.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        in_flight: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+        started: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl Recorder {
+        async fn send(self, event: u32) -> u32 {
+            self.started.lock().unwrap().push(event);
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            event
+        }
+    }
+
+    async fn run(mode: DeliveryMode, max_in_flight: usize) -> (Recorder, Vec<u32>) {
+        let recorder = Recorder::default();
+        let sender = recorder.clone();
+        let outcomes = dispatch(mode, max_in_flight, (0..6).collect(), move |event| {
+            sender.clone().send(event)
+        })
+        .await;
+        (recorder, outcomes)
+    }
+
+    #[tokio::test]
+    async fn ordered_endpoint_delivers_serially_in_order() {
+        let (recorder, outcomes) = run(DeliveryMode::Ordered, 8).await;
+
+        assert_eq!(recorder.peak.load(Ordering::SeqCst), 1);
+        assert_eq!(*recorder.started.lock().unwrap(), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(outcomes, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn parallel_endpoint_delivers_concurrently() {
+        let (recorder, outcomes) = run(DeliveryMode::Parallel, 8).await;
+
+        assert_eq!(recorder.peak.load(Ordering::SeqCst), 6);
+        assert_eq!(outcomes, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn parallel_endpoint_respects_in_flight_limit() {
+        let (recorder, outcomes) = run(DeliveryMode::Parallel, 2).await;
+
+        assert_eq!(recorder.peak.load(Ordering::SeqCst), 2);
+        assert_eq!(outcomes, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn verify_accepts_any_listed_signature() {
+        let body = br#"{"event":"payment.settled"}"#;
+        let current = sign("whsec_current", body);
+        let previous = sign("whsec_previous", body);
+        let header = format!("{}, {}", current, previous.to_ascii_uppercase());
+
+        assert!(verify("whsec_current", body, &current));
+        assert!(verify("whsec_current", body, &header));
+        assert!(verify("whsec_previous", body, &header));
+        assert!(!verify("whsec_other", body, &header));
+        assert!(!verify("whsec_current", body, ""));
+        assert!(!verify("whsec_current", body, &current[..32]));
+    }
+
+    #[test]
+    fn delivery_mode_defaults_to_ordered() {
+        assert_eq!(DeliveryMode::default(), DeliveryMode::Ordered);
+        assert_eq!(DeliveryMode::from_db("parallel"), DeliveryMode::Parallel);
+        assert_eq!(DeliveryMode::from_db("ordered"), DeliveryMode::Ordered);
+        assert_eq!(DeliveryMode::from_db("bogus"), DeliveryMode::Ordered);
+    }
+}